@@ -1,11 +1,28 @@
 use aptprep_lib::cli::{
-    ResolvedCommand, parse_args, resolve_command, run_download,
-    run_generate_packages_file_from_lockfile, run_lock,
+    ResolvedCommand, parse_args, resolve_command, run_audit, run_cache_gc, run_download,
+    run_export_sbom, run_generate_packages_file_from_lockfile, run_licenses, run_lock,
+    run_lockfile_diff, run_lockfile_query, run_lockfile_size, run_prune, run_sign, run_verify,
 };
 use aptprep_lib::error::AptPrepError;
+use std::process::ExitCode;
 
 #[tokio::main(flavor = "multi_thread")]
-async fn main() -> Result<(), AptPrepError> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            let exit_code = error.exit_code();
+            // The debug format of a report lists the whole chain of causes
+            match error {
+                AptPrepError::Unexpected(report) => eprintln!("Error: {:?}", report),
+                error => eprintln!("Error: {:?}", color_eyre::Report::new(error)),
+            }
+            ExitCode::from(exit_code)
+        }
+    }
+}
+
+async fn run() -> Result<(), AptPrepError> {
     color_eyre::install()?;
 
     let args = parse_args();
@@ -17,6 +34,16 @@ async fn main() -> Result<(), AptPrepError> {
         ResolvedCommand::GeneratePackagesFileFromLockfile(params) => {
             run_generate_packages_file_from_lockfile(params).await?
         }
+        ResolvedCommand::LockfileDiff(params) => run_lockfile_diff(params).await?,
+        ResolvedCommand::LockfileQuery(params) => run_lockfile_query(params).await?,
+        ResolvedCommand::LockfileSize(params) => run_lockfile_size(params).await?,
+        ResolvedCommand::ExportSbom(params) => run_export_sbom(params).await?,
+        ResolvedCommand::Audit(params) => run_audit(params).await?,
+        ResolvedCommand::Licenses(params) => run_licenses(params).await?,
+        ResolvedCommand::Sign(params) => run_sign(params).await?,
+        ResolvedCommand::CacheGc(params) => run_cache_gc(params).await?,
+        ResolvedCommand::Prune(params) => run_prune(params).await?,
+        ResolvedCommand::Verify(params) => run_verify(params).await?,
     }
 
     Ok(())
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How the test server answers requests.
+#[derive(Clone, Debug, Default)]
+pub struct ServerBehavior {
+    /// Answer `Range: bytes=<start>-` requests with the rest of the file
+    pub ranges: bool,
+    /// Close the connection after this many body bytes while announcing the full length
+    pub truncate_after: Option<usize>,
+}
+
+/// Minimal HTTP/1.1 server for download tests, serving files from memory.
+pub struct TestServer {
+    address: SocketAddr,
+    files: Arc<HashMap<String, Vec<u8>>>,
+    behavior: Arc<Mutex<ServerBehavior>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Serves `files` by request path, e.g. `/pool/hello_1.0_amd64.deb`.
+    pub async fn start(files: HashMap<String, Vec<u8>>, behavior: ServerBehavior) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind test server");
+        let address = listener.local_addr().unwrap();
+        let files = Arc::new(files);
+        let behavior = Arc::new(Mutex::new(behavior));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let task = tokio::spawn({
+            let files = files.clone();
+            let behavior = behavior.clone();
+            let requests = requests.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let files = files.clone();
+                    let behavior = behavior.lock().unwrap().clone();
+                    let requests = requests.clone();
+                    tokio::spawn(async move {
+                        let _ = serve(stream, &files, &behavior, &requests).await;
+                    });
+                }
+            }
+        });
+
+        Self {
+            address,
+            files,
+            behavior,
+            requests,
+            task,
+        }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Changes how subsequent connections are answered.
+    pub fn set_behavior(&self, behavior: ServerBehavior) {
+        *self.behavior.lock().unwrap() = behavior;
+    }
+
+    /// `Range` headers of the GET requests so far, `None` for requests without one.
+    pub fn ranges_requested(&self) -> Vec<Option<String>> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.starts_with("GET "))
+            .map(|request| {
+                request.lines().find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("range")
+                        .then(|| value.trim().to_string())
+                })
+            })
+            .collect()
+    }
+
+    pub fn file(&self, path: &str) -> &[u8] {
+        &self.files[path]
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    files: &HashMap<String, Vec<u8>>,
+    behavior: &ServerBehavior,
+    requests: &Mutex<Vec<String>>,
+) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..bytes_read]);
+    }
+    let request = String::from_utf8_lossy(&request).into_owned();
+    requests.lock().unwrap().push(request.clone());
+
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+    let Some(file) = files.get(path) else {
+        return stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await;
+    };
+
+    let start = request
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if !name.eq_ignore_ascii_case("range") {
+                return None;
+            }
+            value
+                .trim()
+                .strip_prefix("bytes=")?
+                .split('-')
+                .next()?
+                .parse::<usize>()
+                .ok()
+        })
+        .filter(|_| behavior.ranges);
+    let (status, body) = match start {
+        Some(start) if start >= file.len() => {
+            let header = format!(
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                file.len()
+            );
+            return stream.write_all(header.as_bytes()).await;
+        }
+        Some(start) => (
+            format!(
+                "206 Partial Content\r\nContent-Range: bytes {}-{}/{}",
+                start,
+                file.len() - 1,
+                file.len()
+            ),
+            &file[start..],
+        ),
+        None => ("200 OK".to_string(), file.as_slice()),
+    };
+    let accept_ranges = if behavior.ranges { "bytes" } else { "none" };
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nAccept-Ranges: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len(),
+        accept_ranges
+    );
+    stream.write_all(header.as_bytes()).await?;
+    if method == "HEAD" {
+        return Ok(());
+    }
+    let body = match behavior.truncate_after {
+        Some(limit) => &body[..limit.min(body.len())],
+        None => body,
+    };
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
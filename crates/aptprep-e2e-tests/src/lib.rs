@@ -1,3 +1,5 @@
+pub mod http_server;
 pub mod test_utils;
 
+pub use http_server::*;
 pub use test_utils::*;
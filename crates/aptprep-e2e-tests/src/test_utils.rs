@@ -1,4 +1,5 @@
-use aptprep_lib::config::{Config, DistributionDef, OutputConfig, SourceRepository};
+use aptprep_lib::cli::{Command, LockParams, ResolvedCommand, resolve_command};
+use aptprep_lib::config::{Config, DistributionDef, OutputConfig, SnapshotDef, SourceRepository};
 use eyre::Result;
 use std::path::Path;
 use std::sync::Arc;
@@ -8,13 +9,21 @@ pub fn create_test_config() -> Config {
     Config {
         packages: vec![Arc::from("curl"), Arc::from("vim")],
         source_repositories: vec![Arc::new(SourceRepository {
-            source_url: "https://snapshot.ubuntu.com/ubuntu/20250910T140000Z".to_string(),
+            source_url: "https://snapshot.ubuntu.com/ubuntu".to_string(),
+            snapshot: Some(SnapshotDef {
+                timestamp: Some("20250910T140000Z".parse().unwrap()),
+            }),
+            mirrors: Vec::new(),
             distributions: vec![DistributionDef::Simple("noble".to_string())],
             architectures: vec!["amd64".to_string()],
+            architecture_map: Default::default(),
+            release_checks: Default::default(),
+            signed_by: None,
         })],
         output: OutputConfig {
             target_architectures: vec!["amd64".to_string()],
             path: Some("/tmp/test_output".into()),
+            layout: Default::default(),
         },
     }
 }
@@ -39,3 +48,39 @@ pub async fn wait_for_file_creation(path: &Path, timeout_secs: u64) -> bool {
     }
     false
 }
+
+/// `Packages` index of the repositories written by [`write_flat_repository`].
+const FLAT_PACKAGES: &str = "Package: hello
+Version: 1.0
+Architecture: amd64
+Filename: ./hello_1.0_amd64.deb
+Size: 5
+SHA256: 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
+";
+
+/// Writes a flat repository at `path` with a single `hello` package containing "hello".
+pub fn write_flat_repository(path: &Path) {
+    std::fs::create_dir_all(path).unwrap();
+    std::fs::write(path.join("Packages"), FLAT_PACKAGES).unwrap();
+    std::fs::write(path.join("hello_1.0_amd64.deb"), "hello").unwrap();
+}
+
+pub fn file_url(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+/// Parameters of `aptprep lock`, or of `aptprep lock --check` if `check` is set.
+pub fn lock_params(config_path: &Path, lockfile_path: &Path, check: bool) -> LockParams {
+    let command = Command::Lock {
+        config_path: config_path.to_str().unwrap().to_string(),
+        lockfile_path: lockfile_path.to_str().unwrap().to_string(),
+        target_architectures: vec![],
+        snapshot_date: None,
+        bump_snapshot: None,
+        check,
+    };
+    match resolve_command(command).expect("Failed to resolve lock command") {
+        ResolvedCommand::Lock(params) => params,
+        _ => unreachable!("Resolved command type mismatch"),
+    }
+}
@@ -0,0 +1,117 @@
+use aptprep_lib::cache::{GcOptions, PackageCache};
+use aptprep_lib::lockfile::LockfileDigest;
+use chrono::TimeDelta;
+use std::path::Path;
+
+fn write_entry(cache: &Path, hex: &str, size: usize) {
+    let directory = cache.join("packages/sha256").join(&hex[..2]);
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join(hex), vec![0u8; size]).unwrap();
+}
+
+fn entry_exists(cache: &Path, hex: &str) -> bool {
+    cache
+        .join("packages/sha256")
+        .join(&hex[..2])
+        .join(hex)
+        .exists()
+}
+
+#[test]
+fn test_cache_gc_evicts_unreferenced_and_oversized_entries() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let cache_dir = temp_dir.path().join("cache");
+    let cache = PackageCache::new(&cache_dir);
+
+    write_entry(&cache_dir, "aa01", 100);
+    write_entry(&cache_dir, "bb02", 200);
+    write_entry(&cache_dir, "cc03", 400);
+
+    let old_lockfile = temp_dir.path().join("old.lock");
+    let new_lockfile = temp_dir.path().join("new.lock");
+    std::fs::write(&old_lockfile, "{}").unwrap();
+    std::fs::write(&new_lockfile, "{}").unwrap();
+    cache
+        .record_lockfile_use(&old_lockfile, ["sha256/bb02".to_string()])
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    cache
+        .record_lockfile_use(&new_lockfile, ["sha256/cc03".to_string()])
+        .unwrap();
+
+    // Dry runs leave everything in place
+    let report = cache
+        .gc(GcOptions {
+            max_age: TimeDelta::days(30),
+            max_size: Some(500),
+            dry_run: true,
+        })
+        .unwrap();
+    assert_eq!(report.removed_entries, 2);
+    assert!(entry_exists(&cache_dir, "aa01"));
+
+    // The unreferenced entry goes, then the one of the less recently used lockfile
+    let report = cache
+        .gc(GcOptions {
+            max_age: TimeDelta::days(30),
+            max_size: Some(500),
+            dry_run: false,
+        })
+        .unwrap();
+    assert_eq!(report.removed_entries, 2);
+    assert_eq!(report.removed_bytes, 300);
+    assert_eq!(report.kept_bytes, 400);
+    assert!(!entry_exists(&cache_dir, "aa01"));
+    assert!(!entry_exists(&cache_dir, "bb02"));
+    assert!(entry_exists(&cache_dir, "cc03"));
+
+    // Lockfiles older than the maximum age no longer keep their entries
+    let report = cache
+        .gc(GcOptions {
+            max_age: TimeDelta::zero(),
+            max_size: None,
+            dry_run: false,
+        })
+        .unwrap();
+    assert_eq!(report.expired_lockfiles.len(), 2);
+    assert!(!entry_exists(&cache_dir, "cc03"));
+}
+
+#[tokio::test]
+async fn test_cache_gc_skips_partial_and_locked_entries() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let cache = PackageCache::new(temp_dir.path().join("cache"));
+    let digest = LockfileDigest {
+        algorithm: "SHA256".to_string(),
+        value: "dd".repeat(32),
+    }
+    .content_digest()
+    .unwrap();
+    let entry_path = cache.entry_path(&digest);
+    let part_path = entry_path.with_file_name(format!("{}.part", "dd".repeat(32)));
+    let lock_path = entry_path.with_file_name(format!("{}.lock", "dd".repeat(32)));
+    std::fs::create_dir_all(entry_path.parent().unwrap()).unwrap();
+    std::fs::write(&entry_path, vec![0u8; 100]).unwrap();
+    std::fs::write(&part_path, vec![0u8; 50]).unwrap();
+    let options = GcOptions {
+        max_age: TimeDelta::days(30),
+        max_size: None,
+        dry_run: false,
+    };
+
+    // A download holds the lock of the entry
+    let lock = cache.lock_entry(&digest).await.unwrap();
+    let report = cache.gc(options).unwrap();
+    assert_eq!(report.removed_entries, 0);
+    assert_eq!(report.kept_entries, 1);
+    assert!(entry_path.exists());
+
+    drop(lock);
+    let report = cache.gc(options).unwrap();
+    assert_eq!(report.removed_entries, 1);
+    assert_eq!(report.removed_bytes, 100);
+    assert!(!entry_path.exists());
+    assert!(!lock_path.exists());
+    // Partial downloads are never entries
+    assert!(part_path.exists());
+}
@@ -0,0 +1,255 @@
+use aptprep_e2e_tests::{ServerBehavior, TestServer};
+use aptprep_lib::cache::PackageCache;
+use aptprep_lib::download::{
+    BandwidthLimit, DownloadAndCheckOptions, DownloadItem, download_and_check_all,
+};
+use aptprep_lib::lockfile::LockfileDigest;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const PACKAGE_PATH: &str = "/pool/main/h/hello/hello_1.0_amd64.deb";
+const PACKAGE_SIZE: usize = 3 * 1024 * 1024;
+/// SHA256 of [`package_bytes`]
+const PACKAGE_SHA256: &str = "a1feacf0d812ba4d0b0e463ed45bbd583cea1de55c54693116754b30b5794745";
+
+/// Contents of the served package, which differ at every position within a 251 byte cycle.
+fn package_bytes() -> Vec<u8> {
+    (0..PACKAGE_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+async fn start_server(behavior: ServerBehavior) -> TestServer {
+    TestServer::start(
+        HashMap::from([(PACKAGE_PATH.to_string(), package_bytes())]),
+        behavior,
+    )
+    .await
+}
+
+fn item(server: &TestServer) -> DownloadItem {
+    DownloadItem {
+        base_url: server.base_url(),
+        rel_path: PACKAGE_PATH.to_string(),
+        mirrors: Vec::new(),
+        size: Some(PACKAGE_SIZE as u64),
+        digest: LockfileDigest {
+            algorithm: "SHA256".to_string(),
+            value: PACKAGE_SHA256.to_string(),
+        }
+        .content_digest()
+        .unwrap(),
+        output_path: Some("hello_1.0_amd64.deb".to_string()),
+    }
+}
+
+fn options() -> DownloadAndCheckOptions {
+    DownloadAndCheckOptions {
+        max_retries: 0,
+        ..Default::default()
+    }
+}
+
+fn assert_downloaded(output_dir: &Path) {
+    let content = std::fs::read(output_dir.join("hello_1.0_amd64.deb")).unwrap();
+    assert!(content == package_bytes(), "Downloaded file differs");
+    assert!(!output_dir.join("hello_1.0_amd64.deb.part").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_per_host_bandwidth_limit_throttles_downloads() {
+    let server = start_server(ServerBehavior {
+        ranges: true,
+        ..Default::default()
+    })
+    .await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+
+    // The first MiB is within the burst, the remaining two take a second each
+    let started = Instant::now();
+    download_and_check_all(
+        vec![item(&server)],
+        temp_dir.path(),
+        DownloadAndCheckOptions {
+            bandwidth_limit_per_host: Some(BandwidthLimit::new(1024 * 1024, None).unwrap()),
+            ..options()
+        },
+    )
+    .await
+    .expect("Throttled download should succeed");
+    let elapsed = started.elapsed();
+
+    assert_downloaded(temp_dir.path());
+    assert!(
+        elapsed >= Duration::from_millis(1800),
+        "3 MiB at 1 MiB/s with a 1 MiB burst took only {:?}",
+        elapsed
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_runs_share_a_cache_entry() {
+    let server = start_server(ServerBehavior {
+        ranges: true,
+        ..Default::default()
+    })
+    .await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let cache = PackageCache::new(temp_dir.path().join("cache"));
+    let first_dir = temp_dir.path().join("first");
+    let second_dir = temp_dir.path().join("second");
+    let options = DownloadAndCheckOptions {
+        cache: Some(cache.clone()),
+        ..options()
+    };
+
+    let (first, second) = tokio::join!(
+        download_and_check_all(vec![item(&server)], &first_dir, options.clone()),
+        download_and_check_all(vec![item(&server)], &second_dir, options.clone()),
+    );
+    first.expect("First download should succeed");
+    second.expect("Second download should succeed");
+
+    assert_downloaded(&first_dir);
+    assert_downloaded(&second_dir);
+    // The run that got the entry lock second found the entry complete
+    assert_eq!(server.ranges_requested().len(), 1);
+    let entry_path = cache.entry_path(&item(&server).digest);
+    assert_eq!(std::fs::read(&entry_path).unwrap(), package_bytes());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resumes_partial_download_with_range_request() {
+    let server = start_server(ServerBehavior {
+        ranges: true,
+        ..Default::default()
+    })
+    .await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let offset = 1024 * 1024;
+    std::fs::write(
+        temp_dir.path().join("hello_1.0_amd64.deb.part"),
+        &server.file(PACKAGE_PATH)[..offset],
+    )
+    .unwrap();
+
+    download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect("Resumed download should succeed");
+
+    assert_downloaded(temp_dir.path());
+    assert_eq!(
+        server.ranges_requested(),
+        vec![Some(format!("bytes={}-", offset))]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_restarts_when_server_ignores_range_requests() {
+    let server = start_server(ServerBehavior::default()).await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let part_path = temp_dir.path().join("hello_1.0_amd64.deb.part");
+    std::fs::write(&part_path, &server.file(PACKAGE_PATH)[..1024 * 1024]).unwrap();
+
+    // The whole file arrives after the existing part, which overflows the expected size
+    let error = download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect_err("Appending the whole file should fail");
+    assert!(error.to_string().contains("downloads failed"));
+    assert!(!part_path.exists());
+
+    // Without a part file the next run fetches the whole file
+    download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect("Download from the beginning should succeed");
+    assert_downloaded(temp_dir.path());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_restarts_when_resumed_download_fails_verification() {
+    let server = start_server(ServerBehavior {
+        ranges: true,
+        ..Default::default()
+    })
+    .await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let offset = 1024 * 1024;
+    std::fs::write(
+        temp_dir.path().join("hello_1.0_amd64.deb.part"),
+        vec![0u8; offset],
+    )
+    .unwrap();
+
+    download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect("Download should succeed after restarting");
+
+    assert_downloaded(temp_dir.path());
+    let ranges = server.ranges_requested();
+    assert_eq!(ranges.len(), 2);
+    assert_eq!(ranges[0], Some(format!("bytes={}-", offset)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_truncated_response_keeps_part_file_for_resuming() {
+    let server = start_server(ServerBehavior {
+        ranges: true,
+        truncate_after: Some(1024 * 1024),
+    })
+    .await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let part_path = temp_dir.path().join("hello_1.0_amd64.deb.part");
+
+    let _ = download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect_err("Truncated download should fail");
+    assert!(!temp_dir.path().join("hello_1.0_amd64.deb").exists());
+    let received = std::fs::metadata(&part_path)
+        .expect("Part file should be kept")
+        .len();
+    assert!(received <= 1024 * 1024);
+
+    server.set_behavior(ServerBehavior {
+        ranges: true,
+        ..Default::default()
+    });
+    download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect("Resumed download should succeed");
+
+    assert_downloaded(temp_dir.path());
+    if received > 0 {
+        assert_eq!(
+            server.ranges_requested().last().unwrap(),
+            &Some(format!("bytes={}-", received))
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_removes_stale_part_files_but_resumes_pending_ones() {
+    let server = start_server(ServerBehavior {
+        ranges: true,
+        ..Default::default()
+    })
+    .await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let stale_path = temp_dir.path().join("goodbye_1.0_amd64.deb.part");
+    std::fs::write(&stale_path, "stale").unwrap();
+    let offset = 512 * 1024;
+    std::fs::write(
+        temp_dir.path().join("hello_1.0_amd64.deb.part"),
+        &server.file(PACKAGE_PATH)[..offset],
+    )
+    .unwrap();
+
+    download_and_check_all(vec![item(&server)], temp_dir.path(), options())
+        .await
+        .expect("Download should succeed");
+
+    assert!(!stale_path.exists());
+    assert_downloaded(temp_dir.path());
+    assert_eq!(
+        server.ranges_requested(),
+        vec![Some(format!("bytes={}-", offset))]
+    );
+}
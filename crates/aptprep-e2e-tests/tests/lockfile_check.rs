@@ -0,0 +1,77 @@
+use aptprep_e2e_tests::{file_url, lock_params, write_flat_repository};
+use aptprep_lib::cli::run_lock;
+use aptprep_lib::config::{Config, DistributionDef, OutputConfig, OutputLayout, SourceRepository};
+use aptprep_lib::error::AptPrepError;
+use std::path::Path;
+use std::sync::Arc;
+
+fn config(repository: &Path, mirrors: Vec<String>, output: OutputConfig) -> Config {
+    Config {
+        packages: vec![Arc::from("hello")],
+        source_repositories: vec![Arc::new(SourceRepository {
+            source_url: file_url(repository),
+            snapshot: None,
+            mirrors,
+            distributions: vec![DistributionDef::Flat {
+                flat_path: "./".to_string(),
+            }],
+            architectures: vec!["amd64".to_string()],
+            architecture_map: Default::default(),
+            release_checks: Default::default(),
+            signed_by: None,
+        })],
+        output,
+    }
+}
+
+fn write_config(path: &Path, config: &Config) {
+    std::fs::write(path, serde_json::to_string_pretty(config).unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_check_ignores_output_changes() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let repository = temp_dir.path().join("repository");
+    write_flat_repository(&repository);
+    let config_path = temp_dir.path().join("config.json");
+    let lockfile_path = temp_dir.path().join("aptprep.lock");
+
+    write_config(
+        &config_path,
+        &config(
+            &repository,
+            Vec::new(),
+            OutputConfig {
+                target_architectures: vec!["amd64".to_string()],
+                path: Some(temp_dir.path().join("output")),
+                layout: OutputLayout::Flat,
+            },
+        ),
+    );
+    run_lock(lock_params(&config_path, &lockfile_path, false))
+        .await
+        .expect("Locking should succeed");
+
+    let moved_output = OutputConfig {
+        target_architectures: vec!["amd64".to_string()],
+        path: Some(temp_dir.path().join("elsewhere")),
+        layout: OutputLayout::Pool,
+    };
+    write_config(
+        &config_path,
+        &config(&repository, Vec::new(), moved_output.clone()),
+    );
+    run_lock(lock_params(&config_path, &lockfile_path, true))
+        .await
+        .expect("Output changes should not count as drift");
+
+    // Repository changes do
+    write_config(
+        &config_path,
+        &config(&repository, vec![file_url(&repository)], moved_output),
+    );
+    let error = run_lock(lock_params(&config_path, &lockfile_path, true))
+        .await
+        .expect_err("Repository changes should count as drift");
+    assert!(matches!(error, AptPrepError::LockfileDrift { .. }));
+}
@@ -7,6 +7,9 @@ fn build_lock_params(config_path: &str, lockfile_path: &str) -> aptprep_lib::cli
         config_path: config_path.to_string(),
         lockfile_path: lockfile_path.to_string(),
         target_architectures: vec![],
+        snapshot_date: None,
+        bump_snapshot: None,
+        check: false,
     };
     match resolve_command(command).expect("Failed to resolve lock command") {
         ResolvedCommand::Lock(params) => params,
@@ -85,6 +88,26 @@ async fn test_lockfile_generation_end_to_end() {
         lockfile.required_packages, config.packages,
         "Lockfile should contain the same required packages as config"
     );
+    assert_eq!(
+        lockfile.snapshots,
+        config.snapshot_timestamps(),
+        "Lockfile should record the snapshot timestamps from config"
+    );
+    assert!(
+        !lockfile.sources.is_empty(),
+        "Lockfile should record the sources of the packages"
+    );
+    for package in lockfile.packages.values() {
+        let source = package
+            .source
+            .as_ref()
+            .and_then(|source| lockfile.sources.get(source))
+            .expect("Every package should reference a recorded source");
+        assert!(
+            source.release_date.is_some() && source.release_sha256.is_some(),
+            "Sources should record the Release date and digest"
+        );
+    }
 }
 
 #[tokio::test]
@@ -162,7 +185,7 @@ async fn test_lockfile_reproducibility() {
         "Lockfiles should be identical when generated from same config"
     );
     assert_eq!(
-        lockfile1.config_hash, lockfile2.config_hash,
+        lockfile1.config_hashes, lockfile2.config_hashes,
         "Config hashes should be identical"
     );
 }
@@ -0,0 +1,52 @@
+use aptprep_e2e_tests::{file_url, lock_params, write_flat_repository};
+use aptprep_lib::cli::run_lock;
+use aptprep_lib::config::{Config, DistributionDef, OutputConfig, SourceRepository};
+use aptprep_lib::lockfile::Lockfile;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_failover_does_not_change_lockfile() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let primary = temp_dir.path().join("primary");
+    let mirror = temp_dir.path().join("mirror");
+    write_flat_repository(&primary);
+    write_flat_repository(&mirror);
+
+    let config = Config {
+        packages: vec![Arc::from("hello")],
+        source_repositories: vec![Arc::new(SourceRepository {
+            source_url: file_url(&primary),
+            snapshot: None,
+            mirrors: vec![file_url(&mirror)],
+            distributions: vec![DistributionDef::Flat {
+                flat_path: "./".to_string(),
+            }],
+            architectures: vec!["amd64".to_string()],
+            architecture_map: Default::default(),
+            release_checks: Default::default(),
+            signed_by: None,
+        })],
+        output: OutputConfig {
+            target_architectures: vec!["amd64".to_string()],
+            path: Some(temp_dir.path().join("output")),
+            layout: Default::default(),
+        },
+    };
+    let config_path = temp_dir.path().join("config.json");
+    std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+    let lockfile_path = temp_dir.path().join("aptprep.lock");
+
+    run_lock(lock_params(&config_path, &lockfile_path, false))
+        .await
+        .expect("Locking with both mirrors available should succeed");
+    let lockfile = Lockfile::load_from_file(&lockfile_path).unwrap();
+    let hello = lockfile.packages.values().next().unwrap();
+    assert!(hello.download_url.starts_with(&file_url(&primary)));
+    assert_eq!(hello.mirror_urls.len(), 1);
+
+    // The mirror serves the index now, but packages stay recorded under the primary URL
+    std::fs::remove_dir_all(&primary).unwrap();
+    run_lock(lock_params(&config_path, &lockfile_path, true))
+        .await
+        .expect("Lockfile should still match after failing over to the mirror");
+}
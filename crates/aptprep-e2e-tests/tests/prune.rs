@@ -0,0 +1,85 @@
+use aptprep_lib::output::{PruneOptions, prune_output_dir};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+fn write_file(path: &Path, size: usize) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, vec![0u8; size]).unwrap();
+}
+
+#[test]
+fn test_prune_output_dir() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let output_dir = temp_dir.path().join("packages");
+    let quarantine_dir = output_dir.join("quarantine");
+
+    write_file(&output_dir.join("Packages"), 10);
+    write_file(
+        &output_dir.join("pool/main/h/hello/hello_2.10-3_amd64.deb"),
+        100,
+    );
+    write_file(
+        &output_dir.join("pool/main/h/hello/hello_2.10-2_amd64.deb"),
+        200,
+    );
+    write_file(
+        &output_dir.join("pool/main/c/curl/curl_7.88.1-10_amd64.deb"),
+        300,
+    );
+    write_file(&quarantine_dir.join("old_1.0_amd64.deb"), 400);
+
+    let referenced = BTreeSet::from([
+        PathBuf::from("Packages"),
+        PathBuf::from("pool/main/h/hello/hello_2.10-3_amd64.deb"),
+    ]);
+
+    // Dry runs only report
+    let report = prune_output_dir(
+        &output_dir,
+        &referenced,
+        &PruneOptions {
+            dry_run: true,
+            quarantine_dir: Some(quarantine_dir.clone()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        report.pruned_files,
+        vec![
+            PathBuf::from("pool/main/c/curl/curl_7.88.1-10_amd64.deb"),
+            PathBuf::from("pool/main/h/hello/hello_2.10-2_amd64.deb"),
+        ]
+    );
+    assert_eq!(report.pruned_bytes, 500);
+    assert_eq!(report.kept_files, 2);
+    assert!(output_dir.join("pool/main/c/curl").exists());
+
+    // Stale files move to the quarantine, which is not pruned itself
+    let report = prune_output_dir(
+        &output_dir,
+        &referenced,
+        &PruneOptions {
+            dry_run: false,
+            quarantine_dir: Some(quarantine_dir.clone()),
+        },
+    )
+    .unwrap();
+    assert_eq!(report.pruned_files.len(), 2);
+    assert!(!output_dir.join("pool/main/c").exists());
+    assert!(
+        !output_dir
+            .join("pool/main/h/hello/hello_2.10-2_amd64.deb")
+            .exists()
+    );
+    assert!(
+        output_dir
+            .join("pool/main/h/hello/hello_2.10-3_amd64.deb")
+            .exists()
+    );
+    assert!(
+        quarantine_dir
+            .join("pool/main/h/hello/hello_2.10-2_amd64.deb")
+            .exists()
+    );
+    assert!(quarantine_dir.join("old_1.0_amd64.deb").exists());
+}
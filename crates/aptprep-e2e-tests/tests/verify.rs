@@ -0,0 +1,95 @@
+use aptprep_lib::config::OutputLayout;
+use aptprep_lib::lockfile::Lockfile;
+use aptprep_lib::output::{generate_packages_file_from_lockfile, referenced_files};
+use aptprep_lib::verification::verify_bundle;
+use std::path::PathBuf;
+
+const LOCKFILE: &str = r#"{
+    "version": 2,
+    "required_packages": ["hello"],
+    "packages": {
+        "amd64_hello_1_0": {
+            "name": "hello",
+            "version": "1.0",
+            "architecture": "amd64",
+            "download_url": "https://deb.example.com/pool/main/h/hello/hello_1.0_amd64.deb",
+            "size": 5,
+            "digest": {
+                "algorithm": "SHA256",
+                "value": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            },
+            "dependencies": ["amd64_world_1_0"],
+            "control_file": "Package: hello\nVersion: 1.0\nArchitecture: amd64\nFilename: pool/main/h/hello/hello_1.0_amd64.deb\n"
+        },
+        "amd64_world_1_0": {
+            "name": "world",
+            "version": "1.0",
+            "architecture": "amd64",
+            "download_url": "https://deb.example.com/pool/main/w/world/world_1.0_amd64.deb",
+            "size": 5,
+            "digest": {
+                "algorithm": "SHA256",
+                "value": "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7"
+            },
+            "dependencies": [],
+            "control_file": "Package: world\nVersion: 1.0\nArchitecture: amd64\nFilename: pool/main/w/world/world_1.0_amd64.deb\n"
+        }
+    },
+    "package_groups": {}
+}"#;
+
+#[tokio::test]
+async fn test_verify_bundle() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let dir = temp_dir.path().join("bundle");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let lockfile: Lockfile = serde_json::from_str(LOCKFILE).unwrap();
+    let layout = OutputLayout::Flat;
+    let allowed = referenced_files(&lockfile, layout).unwrap();
+    std::fs::write(dir.join("hello_1.0_amd64.deb"), "hello").unwrap();
+    std::fs::write(dir.join("world_1.0_amd64.deb"), "world").unwrap();
+    generate_packages_file_from_lockfile(&lockfile, &dir.join("Packages"), layout).unwrap();
+
+    let report = verify_bundle(&lockfile, &dir, layout, &allowed, 4)
+        .await
+        .unwrap();
+    assert_eq!(report.verified, 3);
+    assert!(report.clone().into_result().is_ok());
+
+    // Only extra files
+    std::fs::write(dir.join("hello_0.9_amd64.deb"), "old").unwrap();
+    let report = verify_bundle(&lockfile, &dir, layout, &allowed, 4)
+        .await
+        .unwrap();
+    assert_eq!(report.extra, vec![PathBuf::from("hello_0.9_amd64.deb")]);
+    assert_eq!(report.into_result().unwrap_err().exit_code(), 6);
+
+    // Missing files take precedence over extra ones
+    std::fs::remove_file(dir.join("world_1.0_amd64.deb")).unwrap();
+    let report = verify_bundle(&lockfile, &dir, layout, &allowed, 4)
+        .await
+        .unwrap();
+    assert_eq!(report.missing, vec![PathBuf::from("world_1.0_amd64.deb")]);
+    assert_eq!(report.into_result().unwrap_err().exit_code(), 4);
+
+    // Corrupt files take precedence over missing ones
+    std::fs::write(dir.join("hello_1.0_amd64.deb"), "jello").unwrap();
+    std::fs::write(dir.join("Packages"), "Package: hello\n").unwrap();
+    let report = verify_bundle(&lockfile, &dir, layout, &allowed, 4)
+        .await
+        .unwrap();
+    let corrupt = report
+        .corrupt
+        .iter()
+        .map(|file| file.path.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        corrupt,
+        vec![
+            PathBuf::from("Packages"),
+            PathBuf::from("hello_1.0_amd64.deb")
+        ]
+    );
+    assert_eq!(report.into_result().unwrap_err().exit_code(), 5);
+}
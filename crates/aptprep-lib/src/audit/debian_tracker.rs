@@ -0,0 +1,104 @@
+use super::{Advisory, AdvisoryRelease, RangeEvent, Severity};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Issues of one source package in the Debian Security Tracker JSON export
+/// (`https://security-tracker.debian.org/tracker/data/json`), by issue name.
+type TrackerPackage = BTreeMap<String, TrackerIssue>;
+
+#[derive(Debug, Deserialize)]
+struct TrackerIssue {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    releases: BTreeMap<String, TrackerRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackerRelease {
+    status: String,
+    #[serde(default)]
+    fixed_version: Option<String>,
+    #[serde(default)]
+    urgency: Option<String>,
+}
+
+/// Converts the tracker export into one advisory per source package, issue and release.
+pub(super) fn parse(value: serde_json::Value) -> Result<Vec<Advisory>, serde_json::Error> {
+    let packages: BTreeMap<String, TrackerPackage> = serde_json::from_value(value)?;
+
+    let mut advisories = Vec::new();
+    for (source_package, issues) in packages {
+        for (issue, details) in issues {
+            for (release, status) in details.releases {
+                let range = match (status.status.as_str(), status.fixed_version) {
+                    // A fixed version of 0 marks releases that were never affected
+                    (_, Some(fixed_version)) if fixed_version == "0" => continue,
+                    ("resolved", Some(fixed_version)) => vec![
+                        RangeEvent::Introduced("0".to_string()),
+                        RangeEvent::Fixed(fixed_version),
+                    ],
+                    ("resolved", None) => continue,
+                    _ => vec![RangeEvent::Introduced("0".to_string())],
+                };
+                advisories.push(Advisory {
+                    id: issue.clone(),
+                    aliases: Vec::new(),
+                    summary: details.description.clone(),
+                    severity: Severity::from_rating(status.urgency.as_deref()),
+                    source_package: source_package.clone(),
+                    release: AdvisoryRelease::Codename(release),
+                    ranges: vec![range],
+                    versions: Vec::new(),
+                });
+            }
+        }
+    }
+    Ok(advisories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debian_packaging::package_version::PackageVersion;
+
+    #[test]
+    fn test_parse_tracker_export() {
+        let advisories = parse(serde_json::json!({
+            "openssl": {
+                "CVE-2024-0727": {
+                    "description": "PKCS12 NULL dereference",
+                    "releases": {
+                        "bookworm": {
+                            "status": "resolved",
+                            "fixed_version": "3.0.13-1~deb12u1",
+                            "urgency": "low**"
+                        },
+                        "trixie": { "status": "resolved", "fixed_version": "0", "urgency": "low" },
+                        "sid": { "status": "open", "urgency": "not yet assigned" }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(advisories.len(), 2);
+        let bookworm = &advisories[0];
+        assert_eq!(
+            bookworm.release,
+            AdvisoryRelease::Codename("bookworm".to_string())
+        );
+        assert_eq!(bookworm.severity, Severity::Low);
+        assert_eq!(
+            bookworm.affects(&PackageVersion::parse("3.0.11-1~deb12u2").unwrap()),
+            Some(Some("3.0.13-1~deb12u1".to_string()))
+        );
+
+        let sid = &advisories[1];
+        assert_eq!(sid.severity, Severity::Unknown);
+        assert_eq!(
+            sid.affects(&PackageVersion::parse("3.1.5-1").unwrap()),
+            Some(None)
+        );
+    }
+}
@@ -0,0 +1,600 @@
+mod debian_tracker;
+mod osv;
+
+use crate::error::AptPrepError;
+use crate::lockfile::{Lockfile, ReportFormat};
+use debian_packaging::control::ControlFile;
+use debian_packaging::package_version::PackageVersion;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Severity of an advisory. Advisories without a usable severity are `Unknown`, which is
+/// ordered above all others so that severity thresholds never ignore them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Negligible,
+    Low,
+    Medium,
+    High,
+    Critical,
+    Unknown,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // Debian urgencies may carry `*` or `**` markers for unconfirmed ratings
+        match value
+            .trim()
+            .trim_end_matches('*')
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "negligible" | "unimportant" => Ok(Self::Negligible),
+            "low" => Ok(Self::Low),
+            "medium" | "moderate" => Ok(Self::Medium),
+            "high" | "important" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            _ => Err(format!(
+                "Unknown severity '{value}'. Expected negligible, low, medium, high or critical"
+            )),
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Negligible => write!(f, "negligible"),
+            Self::Low => write!(f, "low"),
+            Self::Medium => write!(f, "medium"),
+            Self::High => write!(f, "high"),
+            Self::Critical => write!(f, "critical"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl Severity {
+    fn from_rating(rating: Option<&str>) -> Self {
+        rating
+            .and_then(|rating| rating.parse().ok())
+            .unwrap_or(Self::Unknown)
+    }
+}
+
+/// A known vulnerability of a source package, read from local advisory data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advisory {
+    id: String,
+    aliases: Vec<String>,
+    summary: Option<String>,
+    severity: Severity,
+    source_package: String,
+    release: AdvisoryRelease,
+    /// Affected version ranges, each a list of events in version order
+    ranges: Vec<Vec<RangeEvent>>,
+    /// Individually listed affected versions
+    versions: Vec<String>,
+}
+
+/// The distribution release an advisory applies to.
+#[derive(Debug, Clone, PartialEq)]
+enum AdvisoryRelease {
+    /// Release codename of a Debian Security Tracker entry, e.g. `bookworm`
+    Codename(String),
+    /// Release part of an OSV ecosystem, e.g. `12` for `Debian:12`
+    Ecosystem(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RangeEvent {
+    Introduced(String),
+    Fixed(String),
+    LastAffected(String),
+}
+
+/// Loads advisories from a Debian Security Tracker JSON export, an OSV file or a directory
+/// of OSV files. The format is detected from the JSON structure.
+pub fn load_advisories(path: &Path) -> Result<Vec<Advisory>, AptPrepError> {
+    if path.is_dir() {
+        let mut advisories = Vec::new();
+        for file in json_files(path)? {
+            advisories.extend(load_advisory_file(&file)?);
+        }
+        return Ok(advisories);
+    }
+    load_advisory_file(path)
+}
+
+fn load_advisory_file(path: &Path) -> Result<Vec<Advisory>, AptPrepError> {
+    let advisory_error = |reason: String| AptPrepError::AdvisoryData {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let value: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(path)?).map_err(|e| advisory_error(e.to_string()))?;
+
+    let advisories = if value.is_array() || value.get("affected").is_some() {
+        osv::parse(value)
+    } else {
+        debian_tracker::parse(value)
+    };
+    advisories.map_err(|e| advisory_error(e.to_string()))
+}
+
+fn json_files(dir: &Path) -> Result<Vec<std::path::PathBuf>, AptPrepError> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+impl Advisory {
+    /// Returns the version the package is fixed in (`None` if there is no fix yet) when
+    /// `version` is affected, or `None` if it is not affected.
+    fn affects(&self, version: &PackageVersion) -> Option<Option<String>> {
+        if self
+            .versions
+            .iter()
+            .any(|affected| parse_version(affected).as_ref() == Some(version))
+        {
+            return Some(None);
+        }
+        self.ranges
+            .iter()
+            .find_map(|range| range_affects(range, version))
+    }
+
+    fn applies_to_release(&self, package_release: Option<&str>, release: Option<&str>) -> bool {
+        let release = release.or(package_release);
+        match &self.release {
+            AdvisoryRelease::Codename(codename) => release == Some(codename.as_str()),
+            AdvisoryRelease::Ecosystem(Some(ecosystem_release)) => release.is_some_and(|release| {
+                release == ecosystem_release.as_str()
+                    || release_version(release) == Some(ecosystem_release.as_str())
+            }),
+            AdvisoryRelease::Ecosystem(None) => true,
+        }
+    }
+}
+
+/// Version numbers of Debian and Ubuntu releases by codename, as used in OSV ecosystems.
+const RELEASE_VERSIONS: [(&str, &str); 15] = [
+    ("jessie", "8"),
+    ("stretch", "9"),
+    ("buster", "10"),
+    ("bullseye", "11"),
+    ("bookworm", "12"),
+    ("trixie", "13"),
+    ("forky", "14"),
+    ("xenial", "16.04"),
+    ("bionic", "18.04"),
+    ("focal", "20.04"),
+    ("jammy", "22.04"),
+    ("noble", "24.04"),
+    ("oracular", "24.10"),
+    ("plucky", "25.04"),
+    ("questing", "25.10"),
+];
+
+fn release_version(codename: &str) -> Option<&'static str> {
+    RELEASE_VERSIONS
+        .iter()
+        .find(|(known, _)| *known == codename)
+        .map(|(_, version)| *version)
+}
+
+fn range_affects(events: &[RangeEvent], version: &PackageVersion) -> Option<Option<String>> {
+    let mut affected = false;
+    let mut fixed = None;
+    for event in events {
+        match event {
+            RangeEvent::Introduced(introduced) => {
+                if introduced == "0"
+                    || parse_version(introduced).is_some_and(|introduced| *version >= introduced)
+                {
+                    affected = true;
+                    fixed = None;
+                }
+            }
+            RangeEvent::Fixed(fixed_version) => match parse_version(fixed_version) {
+                Some(parsed) if *version >= parsed => affected = false,
+                Some(_) if affected && fixed.is_none() => fixed = Some(fixed_version.clone()),
+                _ => {}
+            },
+            RangeEvent::LastAffected(last_affected) => {
+                if parse_version(last_affected).is_some_and(|last| *version > last) {
+                    affected = false;
+                }
+            }
+        }
+    }
+    affected.then_some(fixed)
+}
+
+fn parse_version(version: &str) -> Option<PackageVersion> {
+    PackageVersion::parse(version)
+        .inspect_err(|e| tracing::debug!("Ignoring invalid advisory version {}: {}", version, e))
+        .ok()
+}
+
+/// Locked packages affected by known vulnerabilities.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct AuditReport {
+    /// Findings ordered by descending severity
+    pub findings: Vec<Finding>,
+}
+
+/// An advisory affecting one version of a source package.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Finding {
+    pub advisory: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub severity: Severity,
+    pub source_package: String,
+    pub source_version: String,
+    /// First version that fixes the vulnerability, if a fix exists
+    pub fixed_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Keys of the locked packages built from the source package
+    pub packages: Vec<String>,
+}
+
+/// Locked packages of one source package version.
+struct SourceGroup<'a> {
+    version: PackageVersion,
+    release: Option<&'a str>,
+    packages: Vec<&'a str>,
+}
+
+impl AuditReport {
+    /// Matches the locked packages against `advisories` by source package and version.
+    /// `release` overrides the release each package's source distribution was taken from.
+    pub fn run(
+        lockfile: &Lockfile,
+        advisories: &[Advisory],
+        release: Option<&str>,
+    ) -> Result<Self, AptPrepError> {
+        let mut sources: BTreeMap<(String, String), SourceGroup<'_>> = BTreeMap::new();
+        for (package_key, entry) in &lockfile.packages {
+            let control_file = ControlFile::parse_str(&entry.control_file)?;
+            let (source_package, source_version) = control_file
+                .paragraphs()
+                .next()
+                .and_then(|paragraph| paragraph.field_str("Source"))
+                .map(parse_source_field)
+                .unwrap_or((entry.name.as_str(), None));
+            let source_version = source_version.unwrap_or(&entry.version);
+
+            let key = (source_package.to_string(), source_version.to_string());
+            if let Some(group) = sources.get_mut(&key) {
+                group.packages.push(package_key.as_str());
+                continue;
+            }
+            let version = PackageVersion::parse(source_version).map_err(|e| {
+                AptPrepError::LockfileValidation {
+                    details: format!(
+                        "Invalid source version {} of {}: {}",
+                        source_version, entry.name, e
+                    ),
+                }
+            })?;
+            let package_release = entry
+                .source
+                .as_ref()
+                .and_then(|source| lockfile.sources.get(source))
+                // Sources locked before codenames were recorded only have the suite
+                .and_then(|source| source.codename.as_deref().or(source.suite.as_deref()))
+                .map(|codename| {
+                    codename
+                        .split_once('-')
+                        .map_or(codename, |(codename, _)| codename)
+                });
+            sources.insert(
+                key,
+                SourceGroup {
+                    version,
+                    release: package_release,
+                    packages: vec![package_key.as_str()],
+                },
+            );
+        }
+
+        let mut advisories_by_source: BTreeMap<&str, Vec<&Advisory>> = BTreeMap::new();
+        for advisory in advisories {
+            advisories_by_source
+                .entry(advisory.source_package.as_str())
+                .or_default()
+                .push(advisory);
+        }
+
+        let mut findings: BTreeMap<(&str, &str, &str), Finding> = BTreeMap::new();
+        for ((source_package, source_version), group) in &sources {
+            let Some(candidates) = advisories_by_source.get(source_package.as_str()) else {
+                continue;
+            };
+            for advisory in candidates {
+                if !advisory.applies_to_release(group.release, release) {
+                    continue;
+                }
+                let Some(fixed_version) = advisory.affects(&group.version) else {
+                    continue;
+                };
+                findings
+                    .entry((
+                        source_package.as_str(),
+                        source_version.as_str(),
+                        advisory.id.as_str(),
+                    ))
+                    .or_insert_with(|| Finding {
+                        advisory: advisory.id.clone(),
+                        aliases: advisory.aliases.clone(),
+                        severity: advisory.severity,
+                        source_package: source_package.clone(),
+                        source_version: source_version.clone(),
+                        fixed_version,
+                        summary: advisory.summary.clone(),
+                        packages: group.packages.iter().map(|key| key.to_string()).collect(),
+                    });
+            }
+        }
+
+        let mut findings = findings.into_values().collect::<Vec<_>>();
+        findings.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.source_package.cmp(&b.source_package))
+                .then_with(|| a.advisory.cmp(&b.advisory))
+        });
+        Ok(Self { findings })
+    }
+
+    /// Number of findings at or above `threshold`. Findings of unknown severity always count.
+    pub fn count_at_least(&self, threshold: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity >= threshold)
+            .count()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String, AptPrepError> {
+        match format {
+            ReportFormat::Text => Ok(self.render_text()),
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        if self.findings.is_empty() {
+            return "No known vulnerabilities\n".to_string();
+        }
+        let mut out = String::new();
+        let mut counts: BTreeMap<Severity, usize> = BTreeMap::new();
+        for finding in &self.findings {
+            *counts.entry(finding.severity).or_default() += 1;
+            let fix = match &finding.fixed_version {
+                Some(fixed_version) => format!("fixed in {fixed_version}"),
+                None => "no fix available".to_string(),
+            };
+            writeln!(
+                out,
+                "{:<10} {} {} {}: {}",
+                finding.severity.to_string().to_uppercase(),
+                finding.advisory,
+                finding.source_package,
+                finding.source_version,
+                fix
+            )
+            .unwrap();
+            writeln!(out, "           {}", finding.packages.join(", ")).unwrap();
+        }
+        let counts = counts
+            .iter()
+            .rev()
+            .map(|(severity, count)| format!("{count} {severity}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "\n{} findings: {}", self.findings.len(), counts).unwrap();
+        out
+    }
+}
+
+/// Splits a `Source` field of the form `name` or `name (version)`.
+fn parse_source_field(source: &str) -> (&str, Option<&str>) {
+    match source.split_once('(') {
+        Some((name, version)) => (
+            name.trim(),
+            Some(version.trim_end().trim_end_matches(')').trim()),
+        ),
+        None => (source.trim(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::LockfileSource;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder, source};
+
+    fn package(name: &str, version: &str, source: Option<&str>) -> PackageBuilder {
+        let package = PackageBuilder::new("amd64", name, version).source("bookworm");
+        match source {
+            Some(source) => package.control_field("Source", source),
+            None => package,
+        }
+    }
+
+    fn lockfile() -> Lockfile {
+        LockfileBuilder::new(&["openssl"])
+            .source(
+                "bookworm",
+                LockfileSource {
+                    repository_url: Some("https://deb.debian.org/debian".to_string()),
+                    suite: Some("stable-security".to_string()),
+                    codename: Some("bookworm-security".to_string()),
+                    component: Some("main".to_string()),
+                    ..source("https://deb.debian.org/debian/dists/bookworm/")
+                },
+            )
+            .package(package("openssl", "3.0.11-1~deb12u2", None))
+            .package(package("libssl3", "3.0.11-1~deb12u2", Some("openssl")))
+            .package(package(
+                "zlib1g",
+                "1:1.2.13.dfsg-1+b1",
+                Some("zlib (1:1.2.13.dfsg-1)"),
+            ))
+            .build()
+    }
+
+    fn advisory(id: &str, source: &str, ranges: Vec<Vec<RangeEvent>>) -> Advisory {
+        Advisory {
+            id: id.to_string(),
+            aliases: Vec::new(),
+            summary: None,
+            severity: Severity::High,
+            source_package: source.to_string(),
+            release: AdvisoryRelease::Ecosystem(Some("12".to_string())),
+            ranges,
+            versions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_range_events() {
+        let range = vec![
+            RangeEvent::Introduced("0".to_string()),
+            RangeEvent::Fixed("2.0".to_string()),
+            RangeEvent::Introduced("3.0".to_string()),
+            RangeEvent::Fixed("4.0".to_string()),
+        ];
+        let check = |version: &str| range_affects(&range, &PackageVersion::parse(version).unwrap());
+
+        assert_eq!(check("1.0"), Some(Some("2.0".to_string())));
+        assert_eq!(check("2.5"), None);
+        assert_eq!(check("3.5"), Some(Some("4.0".to_string())));
+        assert_eq!(check("4.0"), None);
+    }
+
+    #[test]
+    fn test_findings_group_binary_packages_by_source() {
+        let advisories = [
+            advisory(
+                "DSA-1",
+                "openssl",
+                vec![vec![
+                    RangeEvent::Introduced("0".to_string()),
+                    RangeEvent::Fixed("3.0.13-1~deb12u1".to_string()),
+                ]],
+            ),
+            advisory(
+                "DSA-2",
+                "zlib",
+                vec![vec![
+                    RangeEvent::Introduced("0".to_string()),
+                    RangeEvent::Fixed("1:1.2.13.dfsg-1".to_string()),
+                ]],
+            ),
+        ];
+
+        let report = AuditReport::run(&lockfile(), &advisories, None).unwrap();
+        assert_eq!(report.findings.len(), 1);
+        let finding = &report.findings[0];
+        assert_eq!(finding.advisory, "DSA-1");
+        assert_eq!(finding.fixed_version.as_deref(), Some("3.0.13-1~deb12u1"));
+        assert_eq!(
+            finding.packages,
+            [
+                "amd64_libssl3_3_0_11_1_deb12u2",
+                "amd64_openssl_3_0_11_1_deb12u2"
+            ]
+        );
+
+        let other_release = AuditReport::run(&lockfile(), &advisories, Some("11")).unwrap();
+        assert!(other_release.findings.is_empty());
+    }
+
+    #[test]
+    fn test_osv_advisories_match_the_release_version() {
+        let advisory = |release: &str| Advisory {
+            release: AdvisoryRelease::Ecosystem(Some(release.to_string())),
+            ..advisory(
+                "DEBIAN-CVE-2024-0727",
+                "openssl",
+                vec![vec![
+                    RangeEvent::Introduced("0".to_string()),
+                    RangeEvent::Fixed("3.0.13-1~deb12u1".to_string()),
+                ]],
+            )
+        };
+        // Only the entry for Debian:12 applies to packages from bookworm
+        let advisories = [advisory("11"), advisory("12"), advisory("13")];
+
+        let report = AuditReport::run(&lockfile(), &advisories, None).unwrap();
+        assert_eq!(report.findings.len(), 1);
+        let by_codename = AuditReport::run(&lockfile(), &advisories, Some("bookworm")).unwrap();
+        assert_eq!(by_codename, report);
+    }
+
+    #[test]
+    fn test_tracker_advisories_match_the_release_codename() {
+        let advisory = Advisory {
+            release: AdvisoryRelease::Codename("bookworm".to_string()),
+            ..advisory(
+                "CVE-2024-0727",
+                "openssl",
+                vec![vec![
+                    RangeEvent::Introduced("0".to_string()),
+                    RangeEvent::Fixed("3.0.13-1~deb12u1".to_string()),
+                ]],
+            )
+        };
+
+        // The fixture is locked from a Release file with `Suite: stable-security`
+        let report = AuditReport::run(&lockfile(), std::slice::from_ref(&advisory), None).unwrap();
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].advisory, "CVE-2024-0727");
+
+        let other_release =
+            AuditReport::run(&lockfile(), std::slice::from_ref(&advisory), Some("trixie")).unwrap();
+        assert!(other_release.findings.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_severity_meets_every_threshold() {
+        let report = AuditReport {
+            findings: vec![Finding {
+                advisory: "CVE-2024-0001".to_string(),
+                aliases: Vec::new(),
+                severity: Severity::Unknown,
+                source_package: "openssl".to_string(),
+                source_version: "3.0.11-1".to_string(),
+                fixed_version: None,
+                summary: None,
+                packages: Vec::new(),
+            }],
+        };
+        assert_eq!(report.count_at_least(Severity::Critical), 1);
+        assert_eq!("low**".parse(), Ok(Severity::Low));
+    }
+}
@@ -0,0 +1,189 @@
+use super::{Advisory, AdvisoryRelease, RangeEvent, Severity};
+use serde::Deserialize;
+
+/// An entry in the Open Source Vulnerability format, as published for Debian and Ubuntu.
+#[derive(Debug, Deserialize)]
+struct OsvEntry {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    details: Option<String>,
+    #[serde(default)]
+    severity: Vec<OsvSeverity>,
+    #[serde(default)]
+    affected: Vec<OsvAffected>,
+    #[serde(default)]
+    database_specific: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvSeverity {
+    #[serde(rename = "type")]
+    severity_type: String,
+    score: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvAffected {
+    package: OsvPackage,
+    #[serde(default)]
+    ranges: Vec<OsvRange>,
+    #[serde(default)]
+    versions: Vec<String>,
+    #[serde(default)]
+    ecosystem_specific: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvPackage {
+    ecosystem: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvRange {
+    #[serde(rename = "type")]
+    range_type: String,
+    #[serde(default)]
+    events: Vec<OsvEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvEvent {
+    introduced: Option<String>,
+    fixed: Option<String>,
+    last_affected: Option<String>,
+}
+
+/// Converts a single OSV entry or an array of entries. Packages of ecosystems other than
+/// Debian and Ubuntu are skipped.
+pub(super) fn parse(value: serde_json::Value) -> Result<Vec<Advisory>, serde_json::Error> {
+    let entries: Vec<OsvEntry> = if value.is_array() {
+        serde_json::from_value(value)?
+    } else {
+        vec![serde_json::from_value(value)?]
+    };
+
+    let mut advisories = Vec::new();
+    for entry in entries {
+        let entry_severity = entry_severity(&entry);
+        for affected in &entry.affected {
+            let mut ecosystem = affected.package.ecosystem.split(':');
+            if !matches!(ecosystem.next(), Some("Debian" | "Ubuntu")) {
+                continue;
+            }
+            // Debian's own OSV data rates entries per package
+            let severity = rating(affected.ecosystem_specific.as_ref(), "urgency")
+                .map(|urgency| Severity::from_rating(Some(urgency)))
+                .unwrap_or(entry_severity);
+
+            advisories.push(Advisory {
+                id: entry.id.clone(),
+                aliases: entry.aliases.clone(),
+                summary: entry.summary.clone().or_else(|| {
+                    entry
+                        .details
+                        .as_deref()
+                        .and_then(|details| details.lines().next())
+                        .map(str::to_string)
+                }),
+                severity,
+                source_package: affected.package.name.clone(),
+                release: AdvisoryRelease::Ecosystem(ecosystem.next().map(str::to_string)),
+                ranges: affected
+                    .ranges
+                    .iter()
+                    .filter(|range| range.range_type == "ECOSYSTEM")
+                    .map(|range| range.events.iter().filter_map(range_event).collect())
+                    .collect(),
+                versions: affected.versions.clone(),
+            });
+        }
+    }
+    Ok(advisories)
+}
+
+fn range_event(event: &OsvEvent) -> Option<RangeEvent> {
+    if let Some(introduced) = &event.introduced {
+        Some(RangeEvent::Introduced(introduced.clone()))
+    } else if let Some(fixed) = &event.fixed {
+        Some(RangeEvent::Fixed(fixed.clone()))
+    } else {
+        event
+            .last_affected
+            .as_ref()
+            .map(|last_affected| RangeEvent::LastAffected(last_affected.clone()))
+    }
+}
+
+/// Severity from Ubuntu's priority rating or a `database_specific.severity` word. CVSS
+/// vectors are not scored, so entries rated only by CVSS have an unknown severity.
+fn entry_severity(entry: &OsvEntry) -> Severity {
+    entry
+        .severity
+        .iter()
+        .find(|severity| severity.severity_type == "Ubuntu")
+        .map(|severity| severity.score.as_str())
+        .or_else(|| rating(entry.database_specific.as_ref(), "severity"))
+        .map(|rating| Severity::from_rating(Some(rating)))
+        .unwrap_or(Severity::Unknown)
+}
+
+fn rating<'a>(value: Option<&'a serde_json::Value>, field: &str) -> Option<&'a str> {
+    value?.get(field)?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debian_packaging::package_version::PackageVersion;
+
+    #[test]
+    fn test_parse_ubuntu_osv_entry() {
+        let advisories = parse(serde_json::json!({
+            "id": "UBUNTU-CVE-2024-0727",
+            "aliases": ["CVE-2024-0727"],
+            "details": "PKCS12 NULL dereference\nMore details.",
+            "severity": [
+                { "type": "CVSS_V3", "score": "CVSS:3.1/AV:L/AC:L/PR:N/UI:R/S:U/C:N/I:N/A:H" },
+                { "type": "Ubuntu", "score": "low" }
+            ],
+            "affected": [
+                {
+                    "package": { "ecosystem": "Ubuntu:22.04:LTS", "name": "openssl" },
+                    "ranges": [
+                        {
+                            "type": "ECOSYSTEM",
+                            "events": [{ "introduced": "0" }, { "fixed": "3.0.2-0ubuntu1.14" }]
+                        }
+                    ]
+                },
+                {
+                    "package": { "ecosystem": "PyPI", "name": "openssl" },
+                    "versions": ["1.0"]
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(advisories.len(), 1);
+        let advisory = &advisories[0];
+        assert_eq!(advisory.severity, Severity::Low);
+        assert_eq!(advisory.summary.as_deref(), Some("PKCS12 NULL dereference"));
+        assert_eq!(
+            advisory.release,
+            AdvisoryRelease::Ecosystem(Some("22.04".to_string()))
+        );
+        assert_eq!(
+            advisory.affects(&PackageVersion::parse("3.0.2-0ubuntu1.12").unwrap()),
+            Some(Some("3.0.2-0ubuntu1.14".to_string()))
+        );
+        assert_eq!(
+            advisory.affects(&PackageVersion::parse("3.0.2-0ubuntu1.14").unwrap()),
+            None
+        );
+    }
+}
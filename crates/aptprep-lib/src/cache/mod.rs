@@ -0,0 +1,353 @@
+use crate::error::AptPrepError;
+use chrono::{DateTime, TimeDelta, Utc};
+use debian_packaging::checksum::AnyContentDigest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory below the cache root holding the package files.
+const PACKAGES_DIR: &str = "packages";
+/// Directory below the cache root recording which lockfiles use which entries.
+const LOCKFILES_DIR: &str = "lockfiles";
+/// Extensions of the files next to an entry that are not entries themselves.
+const AUXILIARY_EXTENSIONS: [&str; 2] = ["part", "lock"];
+
+/// Package files shared by all output directories, stored by content digest.
+///
+/// Entries live at `packages/<algorithm>/<first two hex digits>/<hex digest>`. Every download
+/// records the lockfile it was made for, so that `gc` can tell which entries are still in use.
+/// Downloading, linking and evicting an entry happens under its lock file, `<entry>.lock`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageCache {
+    root: PathBuf,
+}
+
+/// Lockfile that used the cache and the entries it references.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LockfileUse {
+    lockfile: PathBuf,
+    last_used: DateTime<Utc>,
+    entries: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcOptions {
+    /// Lockfiles not used for this long no longer keep their entries alive
+    pub max_age: TimeDelta,
+    /// Total size of the entries to keep, evicting the least recently used ones first
+    pub max_size: Option<u64>,
+    /// Only report what would be removed
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    pub removed_entries: usize,
+    pub removed_bytes: u64,
+    pub kept_entries: usize,
+    pub kept_bytes: u64,
+    /// Usage records of lockfiles older than the maximum age
+    pub expired_lockfiles: Vec<PathBuf>,
+}
+
+struct CacheEntry {
+    key: String,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Exclusive access to one cache entry, released when dropped.
+#[derive(Debug)]
+pub struct EntryLock {
+    _file: File,
+}
+
+impl PackageCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// `$XDG_CACHE_HOME/aptprep`, falling back to `~/.cache/aptprep`.
+    pub fn default_root() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .map(|cache| cache.join("aptprep"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Cache key of a digest, `<algorithm>/<hex digest>`.
+    pub fn key(digest: &AnyContentDigest) -> String {
+        let algorithm = match digest {
+            AnyContentDigest::Md5(_) => "md5",
+            AnyContentDigest::Sha1(_) => "sha1",
+            AnyContentDigest::Sha256(_) => "sha256",
+            AnyContentDigest::Sha384(_) => "sha384",
+            AnyContentDigest::Sha512(_) => "sha512",
+        };
+        format!("{}/{}", algorithm, digest.digest_hex())
+    }
+
+    /// Where the file with `digest` is stored.
+    pub fn entry_path(&self, digest: &AnyContentDigest) -> PathBuf {
+        self.path_of_key(&Self::key(digest))
+    }
+
+    /// Waits for exclusive access to the entry with `digest`, so that concurrent runs neither
+    /// download into the same part file nor use an entry while it is written or evicted.
+    pub async fn lock_entry(&self, digest: &AnyContentDigest) -> Result<EntryLock, AptPrepError> {
+        let lock_path = lock_path(&self.entry_path(digest));
+        tokio::task::spawn_blocking(move || {
+            if let Some(parent) = lock_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            loop {
+                let file = open_lock_file(&lock_path)?;
+                file.lock()?;
+                // Eviction removes the lock file while holding it, so lock the new one then
+                if is_current(&file, &lock_path)? {
+                    return Ok::<_, AptPrepError>(EntryLock { _file: file });
+                }
+            }
+        })
+        .await
+        .map_err(|e| AptPrepError::Unexpected(e.into()))?
+    }
+
+    fn path_of_key(&self, key: &str) -> PathBuf {
+        let (algorithm, hex) = key.split_once('/').unwrap_or(("", key));
+        self.root
+            .join(PACKAGES_DIR)
+            .join(algorithm)
+            .join(hex.get(..2).unwrap_or(hex))
+            .join(hex)
+    }
+
+    /// Records that `lockfile_path` uses the entries with the given keys, replacing what was
+    /// recorded for it before.
+    pub fn record_lockfile_use(
+        &self,
+        lockfile_path: &Path,
+        keys: impl IntoIterator<Item = String>,
+    ) -> Result<(), AptPrepError> {
+        let lockfile = std::fs::canonicalize(lockfile_path)?;
+        let mut entries = keys.into_iter().collect::<Vec<_>>();
+        entries.sort();
+        entries.dedup();
+
+        let record = LockfileUse {
+            lockfile,
+            last_used: Utc::now(),
+            entries,
+        };
+        let directory = self.root.join(LOCKFILES_DIR);
+        std::fs::create_dir_all(&directory)?;
+        let name = format!(
+            "{:x}.json",
+            Sha256::digest(record.lockfile.as_os_str().as_encoded_bytes())
+        );
+        std::fs::write(directory.join(name), serde_json::to_vec_pretty(&record)?)?;
+        Ok(())
+    }
+
+    /// Removes entries that no lockfile used within `max_age` references, then the least
+    /// recently used entries until the cache fits into `max_size`.
+    pub fn gc(&self, options: GcOptions) -> Result<GcReport, AptPrepError> {
+        let mut report = GcReport::default();
+        let cutoff = Utc::now() - options.max_age;
+
+        let mut last_used_by_key: HashMap<String, DateTime<Utc>> = HashMap::new();
+        for (record_path, record) in self.lockfile_uses()? {
+            if record.last_used < cutoff {
+                if !options.dry_run {
+                    std::fs::remove_file(&record_path)?;
+                }
+                report.expired_lockfiles.push(record.lockfile);
+                continue;
+            }
+            for key in record.entries {
+                let last_used = last_used_by_key.entry(key).or_insert(record.last_used);
+                *last_used = (*last_used).max(record.last_used);
+            }
+        }
+
+        let mut referenced = Vec::new();
+        for entry in self.entries()? {
+            match last_used_by_key.get(&entry.key) {
+                Some(last_used) => referenced.push((*last_used, entry)),
+                None => self.evict(&entry, options.dry_run, &mut report)?,
+            }
+        }
+
+        // Most recently used first, so that the size limit evicts from the end
+        referenced.sort_by(|(a, a_entry), (b, b_entry)| {
+            b.cmp(a).then_with(|| a_entry.key.cmp(&b_entry.key))
+        });
+        for (_, entry) in referenced {
+            if options
+                .max_size
+                .is_some_and(|max_size| report.kept_bytes + entry.size > max_size)
+            {
+                self.evict(&entry, options.dry_run, &mut report)?;
+            } else {
+                report.kept_entries += 1;
+                report.kept_bytes += entry.size;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn evict(
+        &self,
+        entry: &CacheEntry,
+        dry_run: bool,
+        report: &mut GcReport,
+    ) -> Result<(), AptPrepError> {
+        if !dry_run {
+            // Entries in use by a download are kept
+            let lock_path = lock_path(&entry.path);
+            let lock_file = open_lock_file(&lock_path)?;
+            match lock_file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    tracing::debug!("Keeping {}, it is in use", entry.key);
+                    report.kept_entries += 1;
+                    report.kept_bytes += entry.size;
+                    return Ok(());
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+            tracing::debug!("Evicting {}", entry.key);
+            std::fs::remove_file(&entry.path)?;
+            // Waiting downloads notice that the file they locked is gone. Elsewhere open files
+            // cannot be removed, so the lock file stays.
+            if cfg!(unix) {
+                std::fs::remove_file(&lock_path)?;
+            }
+            drop(lock_file);
+        }
+        report.removed_entries += 1;
+        report.removed_bytes += entry.size;
+        Ok(())
+    }
+
+    fn lockfile_uses(&self) -> Result<Vec<(PathBuf, LockfileUse)>, AptPrepError> {
+        let mut records = Vec::new();
+        for path in read_dir_if_exists(&self.root.join(LOCKFILES_DIR))? {
+            let content = std::fs::read(&path)?;
+            match serde_json::from_slice::<LockfileUse>(&content) {
+                Ok(record) => records.push((path, record)),
+                Err(e) => tracing::warn!("Ignoring unreadable {}: {}", path.display(), e),
+            }
+        }
+        Ok(records)
+    }
+
+    /// All entries below the packages directory, without partial downloads and lock files.
+    fn entries(&self) -> Result<Vec<CacheEntry>, AptPrepError> {
+        let mut entries = Vec::new();
+        for algorithm_dir in read_dir_if_exists(&self.root.join(PACKAGES_DIR))? {
+            let Some(algorithm) = algorithm_dir.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            for prefix_dir in read_dir_if_exists(&algorithm_dir)? {
+                for path in read_dir_if_exists(&prefix_dir)? {
+                    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                        continue;
+                    };
+                    if path.extension().is_some_and(|extension| {
+                        AUXILIARY_EXTENSIONS
+                            .iter()
+                            .any(|auxiliary| extension == *auxiliary)
+                    }) {
+                        continue;
+                    }
+                    entries.push(CacheEntry {
+                        key: format!("{}/{}", algorithm, name),
+                        size: std::fs::metadata(&path)?.len(),
+                        path,
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn lock_path(entry_path: &Path) -> PathBuf {
+    let mut path = entry_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+fn open_lock_file(path: &Path) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+}
+
+/// Whether `file` is still the file at `path`, i.e. was not removed after it was opened.
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let opened = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(current) => Ok(opened.dev() == current.dev() && opened.ino() == current.ino()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(unix))]
+fn is_current(_file: &File, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+fn read_dir_if_exists(directory: &Path) -> Result<Vec<PathBuf>, AptPrepError> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debian_packaging::checksum::AnyChecksumType;
+
+    #[test]
+    fn test_entry_path() {
+        let digest = AnyContentDigest::from_hex_digest(
+            AnyChecksumType::Sha256,
+            "3a4f0c5b8f3e3a1e2d6f0a4b9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d",
+        )
+        .unwrap();
+        let cache = PackageCache::new("/var/cache/aptprep");
+
+        assert_eq!(
+            PackageCache::key(&digest),
+            "sha256/3a4f0c5b8f3e3a1e2d6f0a4b9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d"
+        );
+        assert_eq!(
+            cache.entry_path(&digest),
+            Path::new("/var/cache/aptprep/packages/sha256/3a")
+                .join("3a4f0c5b8f3e3a1e2d6f0a4b9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d")
+        );
+    }
+}
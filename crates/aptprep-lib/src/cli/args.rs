@@ -1,6 +1,13 @@
+use crate::audit::Severity;
+use crate::config::{OutputLayout, SnapshotTimestamp};
+use crate::download::EventsFormat;
+use crate::export::SbomFormat;
+use crate::lockfile::{DiffFormat, PackageFilter, PackageKind, QueryFormat, ReportFormat};
+use crate::utils::parse_size;
 use clap::{ArgAction, Parser, Subcommand};
 use tracing::Level;
 use tracing_subscriber;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
 #[derive(Debug, Clone)]
 pub enum Command {
@@ -8,6 +15,9 @@ pub enum Command {
         config_path: String,
         lockfile_path: String,
         target_architectures: Vec<String>,
+        snapshot_date: Option<SnapshotTimestamp>,
+        bump_snapshot: Option<SnapshotTimestamp>,
+        check: bool,
     },
     Download {
         config_path: Option<String>,
@@ -17,11 +27,88 @@ pub enum Command {
         max_retries: usize,
         download_parallelism: usize,
         checking_parallelism: usize,
+        require_signature: Option<String>,
+        signature_path: Option<String>,
+        events: Option<EventsFormat>,
+        limit_rate: Option<u64>,
+        limit_rate_per_host: Option<u64>,
+        limit_burst: Option<u64>,
+        cache: bool,
+        cache_dir: Option<String>,
+        layout: Option<OutputLayout>,
+        prune: bool,
+        quarantine_dir: Option<String>,
+        full_verify: bool,
     },
     GeneratePackagesFileFromLockfile {
         config_path: Option<String>,
         lockfile_path: String,
         output_path: Option<String>,
+        layout: Option<OutputLayout>,
+    },
+    LockfileDiff {
+        old_lockfile_path: String,
+        new_lockfile_path: String,
+        format: DiffFormat,
+        output_path: Option<String>,
+    },
+    ExportSbom {
+        lockfile_path: String,
+        format: SbomFormat,
+        output_path: Option<String>,
+    },
+    LockfileQuery {
+        lockfile_path: String,
+        filter: PackageFilter,
+        format: QueryFormat,
+        output_path: Option<String>,
+    },
+    LockfileSize {
+        lockfile_path: String,
+        top: usize,
+        format: ReportFormat,
+        output_path: Option<String>,
+    },
+    Audit {
+        lockfile_path: String,
+        advisory_paths: Vec<String>,
+        release: Option<String>,
+        fail_on: Severity,
+        format: ReportFormat,
+        output_path: Option<String>,
+    },
+    Licenses {
+        config_path: Option<String>,
+        lockfile_path: String,
+        package_dir: Option<String>,
+        output_path: Option<String>,
+        layout: Option<OutputLayout>,
+    },
+    Sign {
+        lockfile_path: String,
+        key_path: String,
+        output_path: Option<String>,
+    },
+    CacheGc {
+        cache_dir: Option<String>,
+        max_age_days: u32,
+        max_size: Option<u64>,
+        dry_run: bool,
+    },
+    Prune {
+        config_path: Option<String>,
+        lockfile_path: String,
+        output_dir: Option<String>,
+        layout: Option<OutputLayout>,
+        dry_run: bool,
+        quarantine_dir: Option<String>,
+    },
+    Verify {
+        config_path: Option<String>,
+        lockfile_path: String,
+        dir: Option<String>,
+        layout: Option<OutputLayout>,
+        parallelism: usize,
     },
 }
 
@@ -82,6 +169,29 @@ enum CliCommand {
             value_delimiter = ','
         )]
         target_architectures: Vec<String>,
+
+        #[arg(
+            long = "snapshot-date",
+            value_name = "TIMESTAMP",
+            help = "Timestamp for snapshot repositories that have none in the config or lockfile"
+        )]
+        snapshot_date: Option<SnapshotTimestamp>,
+
+        #[arg(
+            long = "bump-snapshot",
+            value_name = "TIMESTAMP",
+            help = "Moves all snapshot repositories not pinned in the config forward to this timestamp",
+            conflicts_with = "snapshot_date"
+        )]
+        bump_snapshot: Option<SnapshotTimestamp>,
+
+        #[arg(
+            long = "check",
+            visible_alias = "frozen",
+            help = "Resolves and compares the result with the existing lockfile without writing it. Exits with code 2 if they differ",
+            conflicts_with = "bump_snapshot"
+        )]
+        check: bool,
     },
 
     /// Read lockfile and download all required packages
@@ -142,6 +252,92 @@ enum CliCommand {
             default_value_t = 128
         )]
         checking_parallelism: usize,
+
+        #[arg(
+            long = "require-signature",
+            value_name = "KEYRING",
+            help = "Refuses to download unless the lockfile has a valid signature from a key in this keyring"
+        )]
+        require_signature: Option<String>,
+
+        #[arg(
+            long = "signature",
+            value_name = "FILE",
+            help = "Detached lockfile signature checked by --require-signature (default: <lockfile>.asc)",
+            requires = "require_signature"
+        )]
+        signature: Option<String>,
+
+        #[arg(
+            long = "events",
+            value_name = "FORMAT",
+            help = "Writes machine-readable download events to standard output and logs to standard error: jsonl"
+        )]
+        events: Option<EventsFormat>,
+
+        #[arg(
+            long = "limit-rate",
+            value_name = "SIZE",
+            help = "Caps the combined download bandwidth in bytes per second, e.g. 10M",
+            value_parser = parse_size
+        )]
+        limit_rate: Option<u64>,
+
+        #[arg(
+            long = "limit-rate-per-host",
+            value_name = "SIZE",
+            help = "Caps the download bandwidth per host in bytes per second, e.g. 2M",
+            value_parser = parse_size
+        )]
+        limit_rate_per_host: Option<u64>,
+
+        #[arg(
+            long = "limit-burst",
+            value_name = "SIZE",
+            help = "Bytes that may be transferred at once above the rate limits (default: one second worth, at least 1M)",
+            value_parser = parse_size
+        )]
+        limit_burst: Option<u64>,
+
+        #[arg(
+            long = "cache",
+            help = "Downloads into the shared package cache at $XDG_CACHE_HOME/aptprep and links the packages from there"
+        )]
+        cache: bool,
+
+        #[arg(
+            long = "cache-dir",
+            value_name = "DIR",
+            help = "Uses this directory as the shared package cache (implies --cache)"
+        )]
+        cache_dir: Option<String>,
+
+        #[arg(
+            long = "layout",
+            value_name = "LAYOUT",
+            help = "Arrangement of the packages in the output directory: flat or pool (default: <config.output.layout>, else flat)"
+        )]
+        layout: Option<OutputLayout>,
+
+        #[arg(
+            long = "prune",
+            help = "Deletes files in the output directory that the lockfile does not reference after downloading"
+        )]
+        prune: bool,
+
+        #[arg(
+            long = "quarantine",
+            value_name = "DIR",
+            help = "Moves pruned files to this directory instead of deleting them",
+            requires = "prune"
+        )]
+        quarantine: Option<String>,
+
+        #[arg(
+            long = "full-verify",
+            help = "Hashes every existing package, even those unchanged since they were last verified"
+        )]
+        full_verify: bool,
     },
 
     /// Read lockfile and generate a Packages index file
@@ -174,6 +370,472 @@ enum CliCommand {
             help = "Sets the output Packages file path (default: <config.output.path>/Packages)"
         )]
         output: Option<String>,
+
+        #[arg(
+            long = "layout",
+            value_name = "LAYOUT",
+            help = "Arrangement of the packages the Filename fields point to: flat or pool (default: <config.output.layout>, else flat)"
+        )]
+        layout: Option<OutputLayout>,
+    },
+
+    /// Inspect lockfiles
+    Lockfile {
+        #[command(subcommand)]
+        command: LockfileCommand,
+    },
+
+    /// Export the lockfile in other formats
+    Export {
+        #[command(subcommand)]
+        command: ExportCommand,
+    },
+
+    /// Check locked packages against local vulnerability advisory data
+    Audit {
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'a',
+            long = "advisories",
+            value_name = "PATH",
+            help = "Debian Security Tracker JSON export, OSV file or directory of OSV files (repeatable)",
+            action = ArgAction::Append,
+            required = true
+        )]
+        advisories: Vec<String>,
+
+        #[arg(
+            long = "release",
+            value_name = "RELEASE",
+            help = "Release to match advisories against, e.g. bookworm, or 12 for OSV data (default: release codename of each package)"
+        )]
+        release: Option<String>,
+
+        #[arg(
+            long = "fail-on",
+            value_name = "SEVERITY",
+            help = "Exits with code 3 if a finding has this severity or higher: negligible, low, medium, high or critical",
+            default_value_t = Severity::Low
+        )]
+        fail_on: Severity,
+
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_name = "FORMAT",
+            help = "Output format: text or json",
+            default_value_t = ReportFormat::Text
+        )]
+        format: ReportFormat,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Writes the report to a file instead of standard output"
+        )]
+        output: Option<String>,
+    },
+
+    /// Extract copyright and license information from downloaded packages
+    Licenses {
+        #[arg(
+            short = 'c',
+            long = "config",
+            value_name = "FILE",
+            help = "Optional config file for package directory fallback"
+        )]
+        config: Option<String>,
+
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'd',
+            long = "package-dir",
+            value_name = "DIR",
+            help = "Directory with the downloaded packages (default: <config.output.path>)"
+        )]
+        package_dir: Option<String>,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Writes the license report to a file instead of standard output"
+        )]
+        output: Option<String>,
+
+        #[arg(
+            long = "layout",
+            value_name = "LAYOUT",
+            help = "Arrangement of the packages in the package directory: flat or pool (default: <config.output.layout>, else flat)"
+        )]
+        layout: Option<OutputLayout>,
+    },
+
+    /// Manage the shared package cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Delete files in the output directory that the lockfile does not reference
+    Prune {
+        #[arg(
+            short = 'c',
+            long = "config",
+            value_name = "FILE",
+            help = "Optional config file for output-dir fallback and lockfile hash validation"
+        )]
+        config: Option<String>,
+
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'o',
+            long = "output-dir",
+            value_name = "DIR",
+            help = "Overrides the output directory to prune"
+        )]
+        output_dir: Option<String>,
+
+        #[arg(
+            long = "layout",
+            value_name = "LAYOUT",
+            help = "Arrangement of the packages in the output directory: flat or pool (default: <config.output.layout>, else flat)"
+        )]
+        layout: Option<OutputLayout>,
+
+        #[arg(
+            long = "dry-run",
+            help = "Lists the files that would be pruned without touching them"
+        )]
+        dry_run: bool,
+
+        #[arg(
+            long = "quarantine",
+            value_name = "DIR",
+            help = "Moves pruned files to this directory instead of deleting them"
+        )]
+        quarantine: Option<String>,
+    },
+
+    /// Check a downloaded bundle against the lockfile without network access
+    Verify {
+        #[arg(
+            short = 'c',
+            long = "config",
+            value_name = "FILE",
+            help = "Optional config file for directory and layout fallback"
+        )]
+        config: Option<String>,
+
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'd',
+            long = "dir",
+            value_name = "DIR",
+            help = "Directory with the packages and the Packages index (default: <config.output.path>)"
+        )]
+        dir: Option<String>,
+
+        #[arg(
+            long = "layout",
+            value_name = "LAYOUT",
+            help = "Arrangement of the packages in the directory: flat or pool (default: <config.output.layout>, else flat)"
+        )]
+        layout: Option<OutputLayout>,
+
+        #[arg(
+            long = "parallelism",
+            value_name = "N",
+            help = "Maximum number of files hashed at the same time",
+            default_value_t = 8
+        )]
+        parallelism: usize,
+    },
+
+    /// Write a detached OpenPGP signature over the lockfile
+    Sign {
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the lockfile to sign",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'k',
+            long = "key",
+            value_name = "FILE",
+            help = "Armored or binary OpenPGP secret key. A passphrase is read from APTPREP_SIGNING_PASSPHRASE"
+        )]
+        key: String,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Sets the signature path (default: <lockfile>.asc)"
+        )]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+    /// Remove cached packages no recently used lockfile needs and enforce a size limit
+    Gc {
+        #[arg(
+            long = "cache-dir",
+            value_name = "DIR",
+            help = "Sets the cache directory (default: $XDG_CACHE_HOME/aptprep)"
+        )]
+        cache_dir: Option<String>,
+
+        #[arg(
+            long = "max-age",
+            value_name = "DAYS",
+            help = "Keeps the packages of lockfiles downloaded within this many days",
+            default_value_t = 30
+        )]
+        max_age: u32,
+
+        #[arg(
+            long = "max-size",
+            value_name = "SIZE",
+            help = "Evicts the least recently used packages until the cache is at most this large, e.g. 20G",
+            value_parser = parse_size
+        )]
+        max_size: Option<u64>,
+
+        #[arg(
+            long = "dry-run",
+            help = "Reports what would be removed without removing anything"
+        )]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ExportCommand {
+    /// Write a software bill of materials for the locked packages
+    Sbom {
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_name = "FORMAT",
+            help = "SBOM format: cyclonedx or spdx",
+            default_value_t = SbomFormat::CycloneDx
+        )]
+        format: SbomFormat,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Writes the SBOM to a file instead of standard output"
+        )]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LockfileCommand {
+    /// Compare two lockfiles package by package
+    Diff {
+        #[arg(value_name = "OLD", help = "Lockfile to compare against")]
+        old: String,
+
+        #[arg(value_name = "NEW", help = "Lockfile with the changes")]
+        new: String,
+
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_name = "FORMAT",
+            help = "Output format: text, markdown or json",
+            default_value_t = DiffFormat::Text
+        )]
+        format: DiffFormat,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Writes the diff to a file instead of standard output"
+        )]
+        output: Option<String>,
+    },
+
+    /// List lockfile packages matching filters
+    Query {
+        #[arg(
+            value_name = "PATTERN",
+            help = "Glob over package names, e.g. 'lib*-dev'"
+        )]
+        name: Option<String>,
+
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            short = 'a',
+            long = "architecture",
+            value_name = "ARCH",
+            help = "Only packages of these architectures (repeat or use comma-separated values)",
+            action = ArgAction::Append,
+            value_delimiter = ','
+        )]
+        architectures: Vec<String>,
+
+        #[arg(
+            long = "section",
+            value_name = "SECTION",
+            help = "Only packages in this section"
+        )]
+        section: Option<String>,
+
+        #[arg(
+            long = "priority",
+            value_name = "PRIORITY",
+            help = "Only packages with this priority"
+        )]
+        priority: Option<String>,
+
+        #[arg(
+            long = "min-size",
+            value_name = "SIZE",
+            help = "Only packages at least this large, e.g. 10M",
+            value_parser = parse_size
+        )]
+        min_size: Option<u64>,
+
+        #[arg(
+            long = "max-size",
+            value_name = "SIZE",
+            help = "Only packages at most this large, e.g. 512K",
+            value_parser = parse_size
+        )]
+        max_size: Option<u64>,
+
+        #[arg(
+            long = "repository",
+            value_name = "URL",
+            help = "Only packages from this repository source_url, distribution URL or source key"
+        )]
+        repository: Option<String>,
+
+        #[arg(
+            long = "direct",
+            help = "Only required packages",
+            conflicts_with = "transitive"
+        )]
+        direct: bool,
+
+        #[arg(long = "transitive", help = "Only packages pulled in as dependencies")]
+        transitive: bool,
+
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_name = "FORMAT",
+            help = "Output format: table, json, csv or apt (name=version lines)",
+            default_value_t = QueryFormat::Table
+        )]
+        format: QueryFormat,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Writes the result to a file instead of standard output"
+        )]
+        output: Option<String>,
+    },
+
+    /// Report download sizes and how much each required package contributes
+    Size {
+        #[arg(
+            short = 'l',
+            long = "lockfile",
+            value_name = "FILE",
+            help = "Sets the input lockfile path",
+            default_value = "aptprep.lock"
+        )]
+        lockfile: String,
+
+        #[arg(
+            long = "top",
+            value_name = "N",
+            help = "Number of largest packages to list",
+            default_value_t = 20
+        )]
+        top: usize,
+
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_name = "FORMAT",
+            help = "Output format: text or json",
+            default_value_t = ReportFormat::Text
+        )]
+        format: ReportFormat,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_name = "FILE",
+            help = "Writes the report to a file instead of standard output"
+        )]
+        output: Option<String>,
     },
 }
 
@@ -186,8 +848,17 @@ pub fn parse_args() -> Args {
         _ => Level::TRACE,
     };
 
+    // Keep standard output free for the download events
+    let writer = match &cli.command {
+        CliCommand::Download {
+            events: Some(_), ..
+        } => BoxMakeWriter::new(std::io::stderr),
+        _ => BoxMakeWriter::new(std::io::stdout),
+    };
+
     tracing_subscriber::fmt()
         .with_max_level(log_level)
+        .with_writer(writer)
         .with_env_filter(
             tracing_subscriber::EnvFilter::builder()
                 .with_default_directive(log_level.into())
@@ -201,10 +872,16 @@ pub fn parse_args() -> Args {
             config,
             lockfile,
             target_architectures,
+            snapshot_date,
+            bump_snapshot,
+            check,
         } => Command::Lock {
             config_path: config,
             lockfile_path: lockfile,
             target_architectures,
+            snapshot_date,
+            bump_snapshot,
+            check,
         },
         CliCommand::Download {
             config,
@@ -214,6 +891,18 @@ pub fn parse_args() -> Args {
             max_retries,
             download_parallelism,
             checking_parallelism,
+            require_signature,
+            signature,
+            events,
+            limit_rate,
+            limit_rate_per_host,
+            limit_burst,
+            cache,
+            cache_dir,
+            layout,
+            prune,
+            quarantine,
+            full_verify,
         } => Command::Download {
             config_path: config,
             lockfile_path: lockfile,
@@ -222,15 +911,183 @@ pub fn parse_args() -> Args {
             max_retries,
             download_parallelism,
             checking_parallelism,
+            require_signature,
+            signature_path: signature,
+            events,
+            limit_rate,
+            limit_rate_per_host,
+            limit_burst,
+            cache,
+            cache_dir,
+            layout,
+            prune,
+            quarantine_dir: quarantine,
+            full_verify,
         },
         CliCommand::GeneratePackagesFileFromLockfile {
             config,
             lockfile,
             output,
+            layout,
         } => Command::GeneratePackagesFileFromLockfile {
             config_path: config,
             lockfile_path: lockfile,
             output_path: output,
+            layout,
+        },
+        CliCommand::Lockfile {
+            command:
+                LockfileCommand::Diff {
+                    old,
+                    new,
+                    format,
+                    output,
+                },
+        } => Command::LockfileDiff {
+            old_lockfile_path: old,
+            new_lockfile_path: new,
+            format,
+            output_path: output,
+        },
+        CliCommand::Lockfile {
+            command:
+                LockfileCommand::Query {
+                    name,
+                    lockfile,
+                    architectures,
+                    section,
+                    priority,
+                    min_size,
+                    max_size,
+                    repository,
+                    direct,
+                    transitive,
+                    format,
+                    output,
+                },
+        } => Command::LockfileQuery {
+            lockfile_path: lockfile,
+            filter: PackageFilter {
+                name,
+                architectures,
+                section,
+                priority,
+                min_size,
+                max_size,
+                repository,
+                kind: match (direct, transitive) {
+                    (true, _) => Some(PackageKind::Direct),
+                    (_, true) => Some(PackageKind::Transitive),
+                    _ => None,
+                },
+            },
+            format,
+            output_path: output,
+        },
+        CliCommand::Lockfile {
+            command:
+                LockfileCommand::Size {
+                    lockfile,
+                    top,
+                    format,
+                    output,
+                },
+        } => Command::LockfileSize {
+            lockfile_path: lockfile,
+            top,
+            format,
+            output_path: output,
+        },
+        CliCommand::Export {
+            command:
+                ExportCommand::Sbom {
+                    lockfile,
+                    format,
+                    output,
+                },
+        } => Command::ExportSbom {
+            lockfile_path: lockfile,
+            format,
+            output_path: output,
+        },
+        CliCommand::Audit {
+            lockfile,
+            advisories,
+            release,
+            fail_on,
+            format,
+            output,
+        } => Command::Audit {
+            lockfile_path: lockfile,
+            advisory_paths: advisories,
+            release,
+            fail_on,
+            format,
+            output_path: output,
+        },
+        CliCommand::Licenses {
+            config,
+            lockfile,
+            package_dir,
+            output,
+            layout,
+        } => Command::Licenses {
+            config_path: config,
+            lockfile_path: lockfile,
+            package_dir,
+            output_path: output,
+            layout,
+        },
+        CliCommand::Cache {
+            command:
+                CacheCommand::Gc {
+                    cache_dir,
+                    max_age,
+                    max_size,
+                    dry_run,
+                },
+        } => Command::CacheGc {
+            cache_dir,
+            max_age_days: max_age,
+            max_size,
+            dry_run,
+        },
+        CliCommand::Sign {
+            lockfile,
+            key,
+            output,
+        } => Command::Sign {
+            lockfile_path: lockfile,
+            key_path: key,
+            output_path: output,
+        },
+        CliCommand::Prune {
+            config,
+            lockfile,
+            output_dir,
+            layout,
+            dry_run,
+            quarantine,
+        } => Command::Prune {
+            config_path: config,
+            lockfile_path: lockfile,
+            output_dir,
+            layout,
+            dry_run,
+            quarantine_dir: quarantine,
+        },
+        CliCommand::Verify {
+            config,
+            lockfile,
+            dir,
+            layout,
+            parallelism,
+        } => Command::Verify {
+            config_path: config,
+            lockfile_path: lockfile,
+            dir,
+            layout,
+            parallelism,
         },
     };
 
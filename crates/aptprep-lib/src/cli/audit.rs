@@ -0,0 +1,34 @@
+use crate::audit::AuditReport;
+use crate::cli::AuditParams;
+use crate::error::AptPrepError;
+
+pub async fn run_audit(params: AuditParams) -> Result<(), AptPrepError> {
+    let AuditParams {
+        lockfile,
+        advisories,
+        release,
+        fail_on,
+        format,
+        output_path,
+    } = params;
+
+    let report = AuditReport::run(&lockfile, &advisories, release.as_deref())?;
+    let rendered = report.render(format)?;
+
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(&output_path, rendered)?;
+            tracing::info!("Audit report written to {}", output_path.display());
+        }
+        None => print!("{}", rendered),
+    }
+
+    let count = report.count_at_least(fail_on);
+    if count > 0 {
+        return Err(AptPrepError::VulnerabilitiesFound {
+            count,
+            threshold: fail_on.to_string(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use crate::cli::CacheGcParams;
+use crate::error::AptPrepError;
+use crate::utils::format_size;
+
+pub async fn run_cache_gc(params: CacheGcParams) -> Result<(), AptPrepError> {
+    let CacheGcParams { cache, options } = params;
+
+    tracing::info!("Collecting garbage in {}", cache.root().display());
+    let report = cache.gc(options)?;
+
+    for lockfile in &report.expired_lockfiles {
+        tracing::info!("Lockfile {} has not been used recently", lockfile.display());
+    }
+    let verb = if options.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    tracing::info!(
+        "{} {} packages ({}), keeping {} packages ({})",
+        verb,
+        report.removed_entries,
+        format_size(report.removed_bytes),
+        report.kept_entries,
+        format_size(report.kept_bytes)
+    );
+    Ok(())
+}
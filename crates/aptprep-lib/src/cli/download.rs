@@ -1,79 +1,88 @@
+use crate::cache::PackageCache;
 use crate::cli::DownloadParams;
-use crate::download::{DownloadItem, download_and_check_all};
+use crate::cli::prune::prune_stale_files;
+use crate::download::{DownloadItem, DownloadSource, download_and_check_all};
 use crate::error::AptPrepError;
 use crate::output::generate_packages_file_from_lockfile;
-use debian_packaging::checksum::AnyChecksumType;
-use debian_packaging::checksum::AnyContentDigest;
+use std::collections::HashSet;
 use tracing;
 
 pub async fn run_download(params: DownloadParams) -> Result<(), AptPrepError> {
     let DownloadParams {
         lockfile,
+        lockfile_path,
         output_dir,
+        layout,
+        prune,
         options,
     } = params;
 
+    // Fails on packages that would overwrite each other before anything is downloaded
+    let package_paths = lockfile.package_paths(layout)?;
+
     // Create download items from lockfile
     let mut download_items = Vec::new();
+    let mut seen_paths = HashSet::new();
     tracing::info!("Processing {} packages", lockfile.packages.len());
 
-    for package in lockfile.packages.values() {
-        // Parse the digest
-        let checksum_type = match package.digest.algorithm.as_str() {
-            "MD5Sum" => AnyChecksumType::Md5,
-            "SHA1" => AnyChecksumType::Sha1,
-            "SHA256" => AnyChecksumType::Sha256,
-            "SHA384" => AnyChecksumType::Sha384,
-            "SHA512" => AnyChecksumType::Sha512,
-            _ => {
-                return Err(AptPrepError::PackageVerification {
-                    package: "unknown".to_string(),
-                    expected: "supported digest algorithm".to_string(),
-                    actual: package.digest.algorithm.clone(),
-                });
-            }
-        };
-
-        let digest = AnyContentDigest::from_hex_digest(checksum_type, &package.digest.value)?;
+    for (package_key, package) in &lockfile.packages {
+        // Identical files locked more than once are downloaded once
+        let output_path = &package_paths[package_key];
+        if !seen_paths.insert(output_path) {
+            continue;
+        }
 
-        // Extract filename from download URL
-        let filename =
-            package
-                .download_url
-                .split('/')
-                .next_back()
-                .ok_or_else(|| AptPrepError::Download {
-                    message: format!("Invalid download URL: {}", package.download_url),
-                })?;
+        let digest = package.digest.content_digest()?;
 
-        // Parse the download URL to separate base and relative path
-        let url =
-            reqwest::Url::parse(&package.download_url).map_err(|e| AptPrepError::Download {
-                message: format!("Invalid download URL {}: {}", package.download_url, e),
-            })?;
-
-        let mut base_url_url = url.clone();
-        base_url_url.set_path("");
-        base_url_url.set_query(None);
-        base_url_url.set_fragment(None);
-        let base_url = base_url_url.as_str().trim_end_matches('/').to_string();
-        let rel_path = url.path().to_string();
+        // Parse the download URLs to separate base and relative path
+        let parse_download_url = |download_url: &str| {
+            DownloadSource::from_url(download_url).map_err(|e| AptPrepError::Download {
+                message: format!("Invalid download URL {}: {}", download_url, e),
+            })
+        };
+        let DownloadSource { base_url, rel_path } = parse_download_url(&package.download_url)?;
+        let mirrors = package
+            .mirror_urls
+            .iter()
+            .map(|mirror_url| parse_download_url(mirror_url))
+            .collect::<Result<Vec<_>, _>>()?;
 
         download_items.push(DownloadItem {
             base_url,
             rel_path,
+            mirrors,
             size: Some(package.size),
             digest,
-            output_path: Some(filename.to_string()),
+            output_path: Some(output_path.clone()),
         });
     }
 
+    if let Some(cache) = &options.cache {
+        tracing::info!("Using package cache {}", cache.root().display());
+        cache.record_lockfile_use(
+            &lockfile_path,
+            download_items
+                .iter()
+                .map(|item| PackageCache::key(&item.digest)),
+        )?;
+    }
+
     tracing::info!("Downloading {} packages...", download_items.len());
     download_and_check_all(download_items, output_dir.clone(), options).await?;
 
     // Generate Packages file from lockfile
     tracing::info!("Generating Packages file...");
-    generate_packages_file_from_lockfile(&lockfile, &output_dir.join("Packages"))?;
+    generate_packages_file_from_lockfile(&lockfile, &output_dir.join("Packages"), layout)?;
+
+    if let Some(prune_options) = prune {
+        prune_stale_files(
+            &lockfile,
+            &lockfile_path,
+            &output_dir,
+            layout,
+            &prune_options,
+        )?;
+    }
 
     tracing::info!("Download completed successfully");
     Ok(())
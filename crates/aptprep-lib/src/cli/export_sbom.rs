@@ -0,0 +1,22 @@
+use crate::cli::ExportSbomParams;
+use crate::error::AptPrepError;
+use crate::export::generate_sbom;
+
+pub async fn run_export_sbom(params: ExportSbomParams) -> Result<(), AptPrepError> {
+    let ExportSbomParams {
+        lockfile,
+        format,
+        output_path,
+    } = params;
+
+    let sbom = generate_sbom(&lockfile, format)?;
+
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(&output_path, sbom)?;
+            tracing::info!("{} SBOM written to {}", format, output_path.display());
+        }
+        None => println!("{}", sbom),
+    }
+    Ok(())
+}
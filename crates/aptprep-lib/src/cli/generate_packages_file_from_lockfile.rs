@@ -8,11 +8,12 @@ pub async fn run_generate_packages_file_from_lockfile(
     let GeneratePackagesFileFromLockfileParams {
         lockfile,
         output_path,
+        layout,
     } = params;
 
     tracing::info!("Generating Packages file from lockfile...");
 
-    let final_output_path = generate_packages_file_from_lockfile(&lockfile, &output_path)?;
+    let final_output_path = generate_packages_file_from_lockfile(&lockfile, &output_path, layout)?;
 
     tracing::info!(
         "Packages file generated successfully at {:?}",
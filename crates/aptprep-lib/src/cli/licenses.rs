@@ -0,0 +1,32 @@
+use crate::cli::LicensesParams;
+use crate::error::AptPrepError;
+use crate::licenses::collect_licenses;
+
+pub async fn run_licenses(params: LicensesParams) -> Result<(), AptPrepError> {
+    let LicensesParams {
+        lockfile,
+        package_dir,
+        layout,
+        output_path,
+    } = params;
+
+    tracing::info!(
+        "Reading copyright files of {} packages from {}",
+        lockfile.packages.len(),
+        package_dir.display()
+    );
+    let report = collect_licenses(&lockfile, &package_dir, layout)?;
+    for (status, count) in report.status_counts() {
+        tracing::info!("{:?}: {} packages", status, count);
+    }
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(&output_path, json)?;
+            tracing::info!("License report written to {}", output_path.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
@@ -1,24 +1,41 @@
 use crate::cli::LockParams;
 use crate::dependency::resolve_dependencies;
 use crate::error::AptPrepError;
-use crate::lockfile::Lockfile;
+use crate::lockfile::{DiffFormat, Lockfile, LockfileDiff};
 use crate::repository::collect_binary_packages;
+use std::path::Path;
 use tracing;
 
 pub async fn run_lock(params: LockParams) -> Result<(), AptPrepError> {
     let LockParams {
         app_config,
-        config_hash,
+        config_hashes,
         lockfile_path,
         target_architectures,
+        check,
     } = params;
 
     // Collect binary packages from repositories
     tracing::info!("Collecting binary packages from repositories...");
-    let binary_packages = collect_binary_packages(&app_config).await?;
+    let collected = collect_binary_packages(&app_config).await?;
+    let binary_packages = &collected.binary_packages_by_arch;
+
+    // Fail before resolution if a target architecture only has `all` packages to offer
+    if let Some(architecture) = target_architectures
+        .iter()
+        .find(|architecture| !binary_packages.contains_key(architecture.as_str()))
+    {
+        return Err(AptPrepError::LockfileValidation {
+            details: format!(
+                "No repository provides a package index for target architecture '{}'. Check the architectures and architecture_map settings.",
+                architecture
+            ),
+        });
+    }
 
     // Create lockfile
-    let mut lockfile = Lockfile::new(config_hash, app_config.packages.clone());
+    let mut lockfile = Lockfile::new(config_hashes, app_config.packages.clone());
+    lockfile.snapshots = app_config.snapshot_timestamps();
 
     // Resolve dependencies for each architecture
     tracing::info!("Resolving requirements...");
@@ -26,9 +43,13 @@ pub async fn run_lock(params: LockParams) -> Result<(), AptPrepError> {
         tracing::info!("Resolving requirements for {}", architecture);
 
         let resolved_packages =
-            resolve_dependencies(&binary_packages, &app_config.packages, &architecture)?;
+            resolve_dependencies(binary_packages, &app_config.packages, &architecture)?;
+
+        lockfile.add_packages(architecture, &resolved_packages)?;
+    }
 
-        lockfile.add_packages(architecture, &resolved_packages, &binary_packages)?;
+    if check {
+        return check_lockfile(&lockfile, &lockfile_path);
     }
 
     // Save lockfile
@@ -41,3 +62,48 @@ pub async fn run_lock(params: LockParams) -> Result<(), AptPrepError> {
     );
     Ok(())
 }
+
+/// Compares a freshly resolved lockfile with the one on disk, printing any differences.
+fn check_lockfile(resolved: &Lockfile, lockfile_path: &Path) -> Result<(), AptPrepError> {
+    let existing = Lockfile::load_from_file(lockfile_path)?;
+
+    // Output settings do not affect the resolution, so only the other sections count
+    let changed_sections = match (&existing.config_hashes, &resolved.config_hashes) {
+        (Some(existing_hashes), Some(resolved_hashes)) => {
+            existing_hashes.changed_resolution_sections(resolved_hashes)
+        }
+        // Lockfiles migrated from version 1 have no section hashes to compare
+        _ => vec!["configuration"],
+    };
+    let up_to_date = changed_sections.is_empty()
+        && existing.required_packages == resolved.required_packages
+        && existing.snapshots == resolved.snapshots
+        && existing.packages == resolved.packages;
+    if up_to_date {
+        tracing::info!("Lockfile {} is up to date", lockfile_path.display());
+        return Ok(());
+    }
+
+    if !changed_sections.is_empty() {
+        println!("Changed: {}", changed_sections.join(" and "));
+    }
+    if existing.snapshots != resolved.snapshots {
+        println!("Snapshot timestamps differ");
+    }
+    let diff = LockfileDiff::between(&existing, resolved)?;
+    if !diff.is_empty() {
+        print!("{}", diff.render(DiffFormat::Text)?);
+    }
+    // Entries with the same version whose download location, digest or dependencies changed
+    for (package_key, entry) in &existing.packages {
+        if let Some(resolved_entry) = resolved.packages.get(package_key)
+            && resolved_entry != entry
+        {
+            println!("Changed entry: {}", package_key);
+        }
+    }
+
+    Err(AptPrepError::LockfileDrift {
+        path: lockfile_path.to_path_buf(),
+    })
+}
@@ -0,0 +1,24 @@
+use crate::cli::LockfileDiffParams;
+use crate::error::AptPrepError;
+use crate::lockfile::LockfileDiff;
+
+pub async fn run_lockfile_diff(params: LockfileDiffParams) -> Result<(), AptPrepError> {
+    let LockfileDiffParams {
+        old_lockfile,
+        new_lockfile,
+        format,
+        output_path,
+    } = params;
+
+    let diff = LockfileDiff::between(&old_lockfile, &new_lockfile)?;
+    let rendered = diff.render(format)?;
+
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(&output_path, rendered)?;
+            tracing::info!("Lockfile diff written to {}", output_path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
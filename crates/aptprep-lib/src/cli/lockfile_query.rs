@@ -0,0 +1,28 @@
+use crate::cli::LockfileQueryParams;
+use crate::error::AptPrepError;
+use crate::lockfile::LockfileQuery;
+
+pub async fn run_lockfile_query(params: LockfileQueryParams) -> Result<(), AptPrepError> {
+    let LockfileQueryParams {
+        lockfile,
+        filter,
+        format,
+        output_path,
+    } = params;
+
+    let query = LockfileQuery::run(&lockfile, &filter)?;
+    let rendered = query.render(format)?;
+
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(&output_path, rendered)?;
+            tracing::info!(
+                "{} matching packages written to {}",
+                query.packages.len(),
+                output_path.display()
+            );
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use crate::cli::LockfileSizeParams;
+use crate::error::AptPrepError;
+use crate::lockfile::SizeReport;
+
+pub async fn run_lockfile_size(params: LockfileSizeParams) -> Result<(), AptPrepError> {
+    let LockfileSizeParams {
+        lockfile,
+        top,
+        format,
+        output_path,
+    } = params;
+
+    let report = SizeReport::of(&lockfile, top);
+    let rendered = report.render(format)?;
+
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(&output_path, rendered)?;
+            tracing::info!("Size report written to {}", output_path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
@@ -1,13 +1,37 @@
 mod args;
+mod audit;
+mod cache_gc;
 mod download;
+mod export_sbom;
 mod generate_packages_file_from_lockfile;
+mod licenses;
 mod lock;
+mod lockfile_diff;
+mod lockfile_query;
+mod lockfile_size;
 mod params;
+mod prune;
 mod resolved_command;
+mod sign;
+mod verify;
 
 pub use args::{Command, parse_args};
+pub use audit::run_audit;
+pub use cache_gc::run_cache_gc;
 pub use download::run_download;
+pub use export_sbom::run_export_sbom;
 pub use generate_packages_file_from_lockfile::run_generate_packages_file_from_lockfile;
+pub use licenses::run_licenses;
 pub use lock::run_lock;
-pub use params::{DownloadParams, GeneratePackagesFileFromLockfileParams, LockParams};
+pub use lockfile_diff::run_lockfile_diff;
+pub use lockfile_query::run_lockfile_query;
+pub use lockfile_size::run_lockfile_size;
+pub use params::{
+    AuditParams, CacheGcParams, DownloadParams, ExportSbomParams,
+    GeneratePackagesFileFromLockfileParams, LicensesParams, LockParams, LockfileDiffParams,
+    LockfileQueryParams, LockfileSizeParams, PruneParams, SignParams, VerifyParams,
+};
+pub use prune::run_prune;
 pub use resolved_command::{ResolvedCommand, resolve_command};
+pub use sign::run_sign;
+pub use verify::run_verify;
@@ -1,20 +1,30 @@
-use crate::config::Config;
+use crate::audit::{Advisory, Severity};
+use crate::cache::{GcOptions, PackageCache};
+use crate::config::{Config, ConfigHashes, OutputLayout};
 use crate::download::DownloadAndCheckOptions;
-use crate::lockfile::Lockfile;
+use crate::export::SbomFormat;
+use crate::lockfile::{DiffFormat, Lockfile, PackageFilter, QueryFormat, ReportFormat};
+use crate::output::PruneOptions;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct LockParams {
     pub app_config: Config,
-    pub config_hash: String,
+    pub config_hashes: ConfigHashes,
     pub lockfile_path: PathBuf,
     pub target_architectures: Vec<String>,
+    /// Compare with the existing lockfile instead of writing it
+    pub check: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct DownloadParams {
     pub lockfile: Lockfile,
+    pub lockfile_path: PathBuf,
     pub output_dir: PathBuf,
+    pub layout: OutputLayout,
+    /// Prunes stale files after downloading
+    pub prune: Option<PruneOptions>,
     pub options: DownloadAndCheckOptions,
 }
 
@@ -22,4 +32,85 @@ pub struct DownloadParams {
 pub struct GeneratePackagesFileFromLockfileParams {
     pub lockfile: Lockfile,
     pub output_path: PathBuf,
+    pub layout: OutputLayout,
+}
+
+#[derive(Debug, Clone)]
+pub struct LockfileDiffParams {
+    pub old_lockfile: Lockfile,
+    pub new_lockfile: Lockfile,
+    pub format: DiffFormat,
+    pub output_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LockfileQueryParams {
+    pub lockfile: Lockfile,
+    pub filter: PackageFilter,
+    pub format: QueryFormat,
+    pub output_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LockfileSizeParams {
+    pub lockfile: Lockfile,
+    pub top: usize,
+    pub format: ReportFormat,
+    pub output_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportSbomParams {
+    pub lockfile: Lockfile,
+    pub format: SbomFormat,
+    pub output_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditParams {
+    pub lockfile: Lockfile,
+    pub advisories: Vec<Advisory>,
+    pub release: Option<String>,
+    pub fail_on: Severity,
+    pub format: ReportFormat,
+    pub output_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LicensesParams {
+    pub lockfile: Lockfile,
+    pub package_dir: PathBuf,
+    pub layout: OutputLayout,
+    pub output_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignParams {
+    pub lockfile: Lockfile,
+    pub secret_key_path: PathBuf,
+    pub signature_path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheGcParams {
+    pub cache: PackageCache,
+    pub options: GcOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct PruneParams {
+    pub lockfile: Lockfile,
+    pub lockfile_path: PathBuf,
+    pub output_dir: PathBuf,
+    pub layout: OutputLayout,
+    pub options: PruneOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyParams {
+    pub lockfile: Lockfile,
+    pub lockfile_path: PathBuf,
+    pub dir: PathBuf,
+    pub layout: OutputLayout,
+    pub parallelism: usize,
 }
@@ -0,0 +1,75 @@
+use crate::cli::PruneParams;
+use crate::config::OutputLayout;
+use crate::error::AptPrepError;
+use crate::lockfile::{Lockfile, default_signature_path};
+use crate::output::{PruneOptions, prune_output_dir, referenced_files};
+use crate::utils::format_size;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+pub async fn run_prune(params: PruneParams) -> Result<(), AptPrepError> {
+    let PruneParams {
+        lockfile,
+        lockfile_path,
+        output_dir,
+        layout,
+        options,
+    } = params;
+
+    prune_stale_files(&lockfile, &lockfile_path, &output_dir, layout, &options)
+}
+
+/// Prunes the files in `output_dir` that `lockfile` does not reference.
+pub(super) fn prune_stale_files(
+    lockfile: &Lockfile,
+    lockfile_path: &Path,
+    output_dir: &Path,
+    layout: OutputLayout,
+    options: &PruneOptions,
+) -> Result<(), AptPrepError> {
+    let referenced = files_to_keep(lockfile, lockfile_path, output_dir, layout)?;
+
+    tracing::info!("Pruning stale files in {}", output_dir.display());
+    let report = prune_output_dir(output_dir, &referenced, options)?;
+
+    let verb = match (&options.quarantine_dir, options.dry_run) {
+        (_, true) => "Would prune",
+        (Some(_), false) => "Quarantined",
+        (None, false) => "Deleted",
+    };
+    for path in &report.pruned_files {
+        tracing::info!("{} {}", verb, path.display());
+    }
+    tracing::info!(
+        "{} {} files ({}), keeping {} files",
+        verb,
+        report.pruned_files.len(),
+        format_size(report.pruned_bytes),
+        report.kept_files
+    );
+    Ok(())
+}
+
+/// Files in `output_dir` that belong to the bundle of `lockfile`. The lockfile and its
+/// signature belong to it if they are stored in the output directory.
+pub(super) fn files_to_keep(
+    lockfile: &Lockfile,
+    lockfile_path: &Path,
+    output_dir: &Path,
+    layout: OutputLayout,
+) -> Result<BTreeSet<PathBuf>, AptPrepError> {
+    let mut referenced = referenced_files(lockfile, layout)?;
+    let output_dir_canonical = std::fs::canonicalize(output_dir)?;
+    for own_file in [
+        lockfile_path.to_path_buf(),
+        default_signature_path(lockfile_path),
+    ] {
+        let Ok(own_file) = std::fs::canonicalize(own_file) else {
+            continue;
+        };
+        if let Ok(relative_path) = own_file.strip_prefix(&output_dir_canonical) {
+            referenced.insert(relative_path.to_path_buf());
+        }
+    }
+    Ok(referenced)
+}
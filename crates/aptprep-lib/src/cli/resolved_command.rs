@@ -1,16 +1,39 @@
+use crate::audit::load_advisories;
+use crate::cache::{GcOptions, PackageCache};
 use crate::cli::args::Command;
-use crate::cli::params::{DownloadParams, GeneratePackagesFileFromLockfileParams, LockParams};
-use crate::config::{hash_config_file, load_config};
-use crate::download::DownloadAndCheckOptions;
+use crate::cli::params::{
+    AuditParams, CacheGcParams, DownloadParams, ExportSbomParams,
+    GeneratePackagesFileFromLockfileParams, LicensesParams, LockParams, LockfileDiffParams,
+    LockfileQueryParams, LockfileSizeParams, PruneParams, SignParams, VerifyParams,
+};
+use crate::config::{
+    Config, ConfigHashes, OutputLayout, SnapshotDef, SnapshotTimestamp, load_config,
+};
+use crate::download::{BandwidthLimit, DownloadAndCheckOptions};
 use crate::error::AptPrepError;
-use crate::lockfile::Lockfile;
+use crate::lockfile::{Lockfile, default_signature_path, verify_lockfile_signature};
+use crate::output::PruneOptions;
+use chrono::TimeDelta;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum ResolvedCommand {
     Lock(LockParams),
     Download(DownloadParams),
     GeneratePackagesFileFromLockfile(GeneratePackagesFileFromLockfileParams),
+    LockfileDiff(LockfileDiffParams),
+    LockfileQuery(LockfileQueryParams),
+    LockfileSize(LockfileSizeParams),
+    ExportSbom(ExportSbomParams),
+    Audit(AuditParams),
+    Licenses(LicensesParams),
+    Sign(SignParams),
+    CacheGc(CacheGcParams),
+    Prune(PruneParams),
+    Verify(VerifyParams),
 }
 
 pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError> {
@@ -19,8 +42,13 @@ pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError
             config_path,
             lockfile_path,
             target_architectures,
+            snapshot_date,
+            bump_snapshot,
+            check,
         } => {
-            let app_config = load_config(&config_path)?;
+            let mut app_config = load_config(&config_path)?;
+            // Hash the config as written, before snapshot timestamps are filled in
+            let config_hashes = ConfigHashes::of(&app_config)?;
 
             if app_config.source_repositories.is_empty() {
                 return Err(AptPrepError::LockfileValidation {
@@ -42,13 +70,33 @@ pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError
                 });
             }
 
-            let config_hash = hash_config_file(Path::new(&config_path))?;
+            if let Some(architecture) = resolved_target_architectures.iter().find(|architecture| {
+                !app_config
+                    .source_repositories
+                    .iter()
+                    .any(|repository| repository.architectures.contains(architecture))
+            }) {
+                return Err(AptPrepError::CliArgumentValidation {
+                    details: format!(
+                        "Target architecture '{}' is not listed in the architectures of any source repository.",
+                        architecture
+                    ),
+                });
+            }
+
+            resolve_snapshot_timestamps(
+                &mut app_config,
+                Path::new(&lockfile_path),
+                snapshot_date,
+                bump_snapshot,
+            )?;
 
             Ok(ResolvedCommand::Lock(LockParams {
                 app_config,
-                config_hash,
+                config_hashes,
                 lockfile_path: PathBuf::from(lockfile_path),
                 target_architectures: resolved_target_architectures,
+                check,
             }))
         }
         Command::Download {
@@ -59,6 +107,18 @@ pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError
             max_retries,
             download_parallelism,
             checking_parallelism,
+            require_signature,
+            signature_path,
+            events,
+            limit_rate,
+            limit_rate_per_host,
+            limit_burst,
+            cache,
+            cache_dir,
+            layout,
+            prune,
+            quarantine_dir,
+            full_verify,
         } => {
             for (name, value) in [
                 ("max-concurrency-per-host", max_concurrency_per_host),
@@ -73,44 +133,63 @@ pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError
                 }
             }
 
+            let bandwidth_limit = |rate: Option<u64>, name: &str| {
+                rate.map(|rate| BandwidthLimit::new(rate, limit_burst))
+                    .transpose()
+                    .map_err(|reason| AptPrepError::CliArgumentValidation {
+                        details: format!("Invalid {name}: {reason}"),
+                    })
+            };
+            let bandwidth_limit_per_host =
+                bandwidth_limit(limit_rate_per_host, "limit-rate-per-host")?;
+            let bandwidth_limit = bandwidth_limit(limit_rate, "limit-rate")?;
+
+            let cache = match cache_dir {
+                Some(cache_dir) => Some(PackageCache::new(cache_dir)),
+                None if cache => Some(default_cache()?),
+                None => None,
+            };
+
             let lockfile = Lockfile::load_from_file(Path::new(&lockfile_path))?;
 
-            let resolved_output_dir = match config_path {
-                Some(config_path) => {
-                    let app_config = load_config(&config_path)?;
-                    let config_hash = hash_config_file(Path::new(&config_path))?;
-                    if lockfile.config_hash != config_hash {
-                        return Err(AptPrepError::LockfileValidation {
-                            details: "Configuration hash does not match lockfile. Please regenerate the lockfile with 'aptprep lock'.".to_string(),
-                        });
-                    }
+            if let Some(keyring_path) = require_signature {
+                let signature_path = signature_path
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| default_signature_path(Path::new(&lockfile_path)));
+                let fingerprint = verify_lockfile_signature(
+                    &lockfile,
+                    &signature_path,
+                    Path::new(&keyring_path),
+                )?;
+                tracing::info!("Lockfile signed by {}", fingerprint);
+            }
 
-                    output_dir
-                        .map(PathBuf::from)
-                        .or_else(|| app_config.output.path.clone())
-                        .ok_or_else(|| AptPrepError::CliArgumentValidation {
-                            details: "No output directory provided. Configure output.path or pass --output-dir."
-                                .to_string(),
-                        })?
-                }
-                None => {
-                    output_dir
-                        .map(PathBuf::from)
-                        .ok_or_else(|| AptPrepError::CliArgumentValidation {
-                            details: "No output directory provided. Pass --output-dir or provide --config with output.path."
-                                .to_string(),
-                        })?
-                }
-            };
+            let (resolved_output_dir, resolved_layout) =
+                resolve_output(config_path, &lockfile, output_dir, layout)?;
 
             Ok(ResolvedCommand::Download(DownloadParams {
                 lockfile,
+                lockfile_path: PathBuf::from(lockfile_path),
                 output_dir: resolved_output_dir,
+                layout: resolved_layout,
+                prune: prune.then(|| PruneOptions {
+                    dry_run: false,
+                    quarantine_dir: quarantine_dir.map(PathBuf::from),
+                }),
                 options: DownloadAndCheckOptions {
                     max_concurrency_per_host,
                     max_retries,
                     download_parallelism,
                     checking_parallelism,
+                    events,
+                    progress: std::io::stderr().is_terminal(),
+                    bandwidth_limit,
+                    bandwidth_limit_per_host,
+                    cache,
+                    // The key stays on this machine, so copied manifests are not trusted
+                    manifest_key_path: PackageCache::default_root()
+                        .map(|root| root.join("manifest.key")),
+                    full_verify,
                 },
             }))
         }
@@ -118,13 +197,17 @@ pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError
             config_path,
             lockfile_path,
             output_path,
+            layout,
         } => {
             let lockfile = Lockfile::load_from_file(Path::new(&lockfile_path))?;
+            let app_config = config_path.as_deref().map(load_config).transpose()?;
+            let layout = layout
+                .or_else(|| app_config.as_ref().map(|config| config.output.layout))
+                .unwrap_or_default();
 
             let output_path = if let Some(output_path) = output_path {
                 PathBuf::from(output_path)
-            } else if let Some(config_path) = config_path {
-                let app_config = load_config(&config_path)?;
+            } else if let Some(app_config) = app_config {
                 app_config
                     .output
                     .path
@@ -145,8 +228,385 @@ pub fn resolve_command(command: Command) -> Result<ResolvedCommand, AptPrepError
                 GeneratePackagesFileFromLockfileParams {
                     lockfile,
                     output_path,
+                    layout,
                 },
             ))
         }
+        Command::LockfileDiff {
+            old_lockfile_path,
+            new_lockfile_path,
+            format,
+            output_path,
+        } => Ok(ResolvedCommand::LockfileDiff(LockfileDiffParams {
+            old_lockfile: Lockfile::load_from_file(Path::new(&old_lockfile_path))?,
+            new_lockfile: Lockfile::load_from_file(Path::new(&new_lockfile_path))?,
+            format,
+            output_path: output_path.map(PathBuf::from),
+        })),
+        Command::LockfileQuery {
+            lockfile_path,
+            filter,
+            format,
+            output_path,
+        } => Ok(ResolvedCommand::LockfileQuery(LockfileQueryParams {
+            lockfile: Lockfile::load_from_file(Path::new(&lockfile_path))?,
+            filter,
+            format,
+            output_path: output_path.map(PathBuf::from),
+        })),
+        Command::LockfileSize {
+            lockfile_path,
+            top,
+            format,
+            output_path,
+        } => Ok(ResolvedCommand::LockfileSize(LockfileSizeParams {
+            lockfile: Lockfile::load_from_file(Path::new(&lockfile_path))?,
+            top,
+            format,
+            output_path: output_path.map(PathBuf::from),
+        })),
+        Command::ExportSbom {
+            lockfile_path,
+            format,
+            output_path,
+        } => Ok(ResolvedCommand::ExportSbom(ExportSbomParams {
+            lockfile: Lockfile::load_from_file(Path::new(&lockfile_path))?,
+            format,
+            output_path: output_path.map(PathBuf::from),
+        })),
+        Command::Audit {
+            lockfile_path,
+            advisory_paths,
+            release,
+            fail_on,
+            format,
+            output_path,
+        } => {
+            let lockfile = Lockfile::load_from_file(Path::new(&lockfile_path))?;
+            let mut advisories = Vec::new();
+            for advisory_path in &advisory_paths {
+                advisories.extend(load_advisories(Path::new(advisory_path))?);
+            }
+            tracing::info!(
+                "Loaded {} advisories from {}",
+                advisories.len(),
+                advisory_paths.join(", ")
+            );
+
+            Ok(ResolvedCommand::Audit(AuditParams {
+                lockfile,
+                advisories,
+                release,
+                fail_on,
+                format,
+                output_path: output_path.map(PathBuf::from),
+            }))
+        }
+        Command::Licenses {
+            config_path,
+            lockfile_path,
+            package_dir,
+            output_path,
+            layout,
+        } => {
+            let lockfile = Lockfile::load_from_file(Path::new(&lockfile_path))?;
+            let app_config = config_path.as_deref().map(load_config).transpose()?;
+            let layout = layout
+                .or_else(|| app_config.as_ref().map(|config| config.output.layout))
+                .unwrap_or_default();
+
+            let package_dir = if let Some(package_dir) = package_dir {
+                PathBuf::from(package_dir)
+            } else if let Some(app_config) = app_config {
+                app_config.output.path.ok_or_else(|| {
+                    AptPrepError::CliArgumentValidation {
+                        details: "No package directory provided. Pass --package-dir or configure output.path."
+                            .to_string(),
+                    }
+                })?
+            } else {
+                return Err(AptPrepError::CliArgumentValidation {
+                    details: "No package directory provided. Pass --package-dir or provide --config with output.path."
+                        .to_string(),
+                });
+            };
+
+            Ok(ResolvedCommand::Licenses(LicensesParams {
+                lockfile,
+                package_dir,
+                layout,
+                output_path: output_path.map(PathBuf::from),
+            }))
+        }
+        Command::Sign {
+            lockfile_path,
+            key_path,
+            output_path,
+        } => {
+            let lockfile_path = PathBuf::from(lockfile_path);
+            let lockfile = Lockfile::load_from_file(&lockfile_path)?;
+
+            Ok(ResolvedCommand::Sign(SignParams {
+                lockfile,
+                secret_key_path: PathBuf::from(key_path),
+                signature_path: output_path
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| default_signature_path(&lockfile_path)),
+            }))
+        }
+        Command::Prune {
+            config_path,
+            lockfile_path,
+            output_dir,
+            layout,
+            dry_run,
+            quarantine_dir,
+        } => {
+            let lockfile = Lockfile::load_from_file(Path::new(&lockfile_path))?;
+            let (output_dir, layout) = resolve_output(config_path, &lockfile, output_dir, layout)?;
+
+            Ok(ResolvedCommand::Prune(PruneParams {
+                lockfile,
+                lockfile_path: PathBuf::from(lockfile_path),
+                output_dir,
+                layout,
+                options: PruneOptions {
+                    dry_run,
+                    quarantine_dir: quarantine_dir.map(PathBuf::from),
+                },
+            }))
+        }
+        Command::Verify {
+            config_path,
+            lockfile_path,
+            dir,
+            layout,
+            parallelism,
+        } => {
+            if parallelism == 0 {
+                return Err(AptPrepError::CliArgumentValidation {
+                    details: "parallelism must be greater than 0.".to_string(),
+                });
+            }
+            let lockfile = Lockfile::load_from_file(Path::new(&lockfile_path))?;
+            let app_config = config_path.as_deref().map(load_config).transpose()?;
+            let layout = layout
+                .or_else(|| app_config.as_ref().map(|config| config.output.layout))
+                .unwrap_or_default();
+
+            let dir = if let Some(dir) = dir {
+                PathBuf::from(dir)
+            } else if let Some(app_config) = app_config {
+                app_config
+                    .output
+                    .path
+                    .ok_or_else(|| AptPrepError::CliArgumentValidation {
+                        details: "No directory provided. Pass --dir or configure output.path."
+                            .to_string(),
+                    })?
+            } else {
+                return Err(AptPrepError::CliArgumentValidation {
+                    details:
+                        "No directory provided. Pass --dir or provide --config with output.path."
+                            .to_string(),
+                });
+            };
+
+            Ok(ResolvedCommand::Verify(VerifyParams {
+                lockfile,
+                lockfile_path: PathBuf::from(lockfile_path),
+                dir,
+                layout,
+                parallelism,
+            }))
+        }
+        Command::CacheGc {
+            cache_dir,
+            max_age_days,
+            max_size,
+            dry_run,
+        } => {
+            let cache = match cache_dir {
+                Some(cache_dir) => PackageCache::new(cache_dir),
+                None => default_cache()?,
+            };
+
+            Ok(ResolvedCommand::CacheGc(CacheGcParams {
+                cache,
+                options: GcOptions {
+                    max_age: TimeDelta::days(i64::from(max_age_days)),
+                    max_size,
+                    dry_run,
+                },
+            }))
+        }
+    }
+}
+
+/// Output directory and layout from the command line, falling back to the `output` section of
+/// the config. The lockfile must match the config if one is given.
+fn resolve_output(
+    config_path: Option<String>,
+    lockfile: &Lockfile,
+    output_dir: Option<String>,
+    layout: Option<OutputLayout>,
+) -> Result<(PathBuf, OutputLayout), AptPrepError> {
+    match config_path {
+        Some(config_path) => {
+            let app_config = load_config(&config_path)?;
+            lockfile.check_config(&app_config, Path::new(&config_path))?;
+
+            let output_dir = output_dir
+                .map(PathBuf::from)
+                .or_else(|| app_config.output.path.clone())
+                .ok_or_else(|| AptPrepError::CliArgumentValidation {
+                    details:
+                        "No output directory provided. Configure output.path or pass --output-dir."
+                            .to_string(),
+                })?;
+            Ok((output_dir, layout.unwrap_or(app_config.output.layout)))
+        }
+        None => {
+            let output_dir = output_dir.map(PathBuf::from).ok_or_else(|| {
+                AptPrepError::CliArgumentValidation {
+                    details: "No output directory provided. Pass --output-dir or provide --config with output.path."
+                        .to_string(),
+                }
+            })?;
+            Ok((output_dir, layout.unwrap_or_default()))
+        }
+    }
+}
+
+fn default_cache() -> Result<PackageCache, AptPrepError> {
+    PackageCache::default_root()
+        .map(PackageCache::new)
+        .ok_or_else(|| AptPrepError::CliArgumentValidation {
+            details: "Cannot determine the cache directory. Pass --cache-dir.".to_string(),
+        })
+}
+
+/// Fills in the timestamp of every snapshot repository.
+///
+/// Timestamps pinned in the config always win. Unpinned repositories take `--bump-snapshot`,
+/// then the timestamp recorded in the existing lockfile, then `--snapshot-date`. A bump that
+/// applies to no repository is an error rather than a silent no-op.
+fn resolve_snapshot_timestamps(
+    app_config: &mut Config,
+    lockfile_path: &Path,
+    snapshot_date: Option<SnapshotTimestamp>,
+    bump_snapshot: Option<SnapshotTimestamp>,
+) -> Result<(), AptPrepError> {
+    let has_unpinned_snapshots = app_config
+        .source_repositories
+        .iter()
+        .any(|repository| matches!(repository.snapshot, Some(SnapshotDef { timestamp: None })));
+
+    let recorded_snapshots = if has_unpinned_snapshots && lockfile_path.exists() {
+        Lockfile::load_from_file(lockfile_path)?.snapshots
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut bumped_any = false;
+    for repository in app_config.source_repositories.iter_mut() {
+        let Some(snapshot) = &repository.snapshot else {
+            continue;
+        };
+        let recorded = recorded_snapshots.get(&repository.source_url).copied();
+
+        let timestamp = match (snapshot.timestamp, bump_snapshot) {
+            (Some(pinned), Some(_)) => {
+                tracing::warn!(
+                    "Snapshot repository {} is pinned to {} in the config, not bumping",
+                    repository.source_url,
+                    pinned
+                );
+                pinned
+            }
+            (Some(pinned), None) => pinned,
+            (None, Some(bumped)) => {
+                if let Some(recorded) = recorded
+                    && recorded > bumped
+                {
+                    return Err(AptPrepError::Snapshot {
+                        repository: repository.source_url.clone(),
+                        details: format!(
+                            "Refusing to move snapshot back from {} to {}",
+                            recorded, bumped
+                        ),
+                    });
+                }
+                bumped_any = true;
+                bumped
+            }
+            (None, None) => recorded.or(snapshot_date).ok_or_else(|| {
+                AptPrepError::Snapshot {
+                    repository: repository.source_url.clone(),
+                    details: "No snapshot timestamp configured or recorded in the lockfile. Set snapshot.timestamp or pass --snapshot-date.".to_string(),
+                }
+            })?,
+        };
+
+        Arc::make_mut(repository).snapshot = Some(SnapshotDef {
+            timestamp: Some(timestamp),
+        });
+    }
+
+    if let Some(bumped) = bump_snapshot
+        && !bumped_any
+    {
+        return Err(AptPrepError::CliArgumentValidation {
+            details: format!(
+                "--bump-snapshot {} applies to no repository, because none is an unpinned snapshot repository. Remove snapshot.timestamp from the config or change it there instead.",
+                bumped
+            ),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(snapshot: &str) -> Config {
+        serde_json::from_str(&format!(
+            r#"{{
+                "source_repositories": [
+                    {{"source_url": "https://snapshot.ubuntu.com/ubuntu", "snapshot": {}, "architectures": ["amd64"], "distributions": ["noble"]}}
+                ],
+                "packages": ["hello"],
+                "output": {{"target_architectures": ["amd64"]}}
+            }}"#,
+            snapshot
+        ))
+        .unwrap()
+    }
+
+    fn resolved_timestamp(config: &Config) -> Option<SnapshotTimestamp> {
+        config.source_repositories[0]
+            .snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.timestamp)
+    }
+
+    #[test]
+    fn test_bump_snapshot_moves_unpinned_repositories() {
+        let mut config = config("{}");
+        let bumped = "20251201T000000Z".parse().unwrap();
+        resolve_snapshot_timestamps(&mut config, Path::new("missing.lock"), None, Some(bumped))
+            .unwrap();
+        assert_eq!(resolved_timestamp(&config), Some(bumped));
+    }
+
+    #[test]
+    fn test_bump_snapshot_fails_when_every_snapshot_is_pinned() {
+        let mut config = config(r#"{"timestamp": "20251029T140000Z"}"#);
+        let bumped = "20251201T000000Z".parse().unwrap();
+        let error =
+            resolve_snapshot_timestamps(&mut config, Path::new("missing.lock"), None, Some(bumped))
+                .unwrap_err();
+        assert!(matches!(error, AptPrepError::CliArgumentValidation { .. }));
     }
 }
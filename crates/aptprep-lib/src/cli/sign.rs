@@ -0,0 +1,21 @@
+use crate::cli::SignParams;
+use crate::error::AptPrepError;
+use crate::lockfile::sign_lockfile;
+
+/// Environment variable holding the passphrase of the signing key.
+const PASSPHRASE_VARIABLE: &str = "APTPREP_SIGNING_PASSPHRASE";
+
+pub async fn run_sign(params: SignParams) -> Result<(), AptPrepError> {
+    let SignParams {
+        lockfile,
+        secret_key_path,
+        signature_path,
+    } = params;
+
+    let passphrase = std::env::var(PASSPHRASE_VARIABLE).unwrap_or_default();
+    let signature = sign_lockfile(&lockfile, &secret_key_path, passphrase)?;
+
+    std::fs::write(&signature_path, signature)?;
+    tracing::info!("Lockfile signature written to {}", signature_path.display());
+    Ok(())
+}
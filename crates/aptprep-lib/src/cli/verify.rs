@@ -0,0 +1,42 @@
+use crate::cli::VerifyParams;
+use crate::cli::prune::files_to_keep;
+use crate::error::AptPrepError;
+use crate::verification::verify_bundle;
+
+pub async fn run_verify(params: VerifyParams) -> Result<(), AptPrepError> {
+    let VerifyParams {
+        lockfile,
+        lockfile_path,
+        dir,
+        layout,
+        parallelism,
+    } = params;
+
+    tracing::info!(
+        "Verifying {} packages in {}",
+        lockfile.packages.len(),
+        dir.display()
+    );
+    let allowed = files_to_keep(&lockfile, &lockfile_path, &dir, layout)?;
+    let report = verify_bundle(&lockfile, &dir, layout, &allowed, parallelism).await?;
+
+    for path in &report.missing {
+        tracing::error!("Missing: {}", path.display());
+    }
+    for file in &report.corrupt {
+        tracing::error!("Corrupt: {}: {}", file.path.display(), file.reason);
+    }
+    for path in &report.extra {
+        tracing::warn!("Extra: {}", path.display());
+    }
+    tracing::info!(
+        "{} files verified, {} missing, {} corrupt, {} extra",
+        report.verified,
+        report.missing.len(),
+        report.corrupt.len(),
+        report.extra.len()
+    );
+
+    report.into_result()?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use super::{Config, SourceRepository};
+use crate::error::AptPrepError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Hashes of the configuration sections, computed over a canonical form of the deserialized
+/// config. Formatting, comments and the order of repositories, packages and architectures
+/// do not affect them.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConfigHashes {
+    pub repositories: String,
+    pub packages: String,
+    pub output: String,
+}
+
+impl ConfigHashes {
+    pub fn of(config: &Config) -> Result<Self, AptPrepError> {
+        let mut repositories = config
+            .source_repositories
+            .iter()
+            .map(|repository| canonical_repository(repository))
+            .collect::<Result<Vec<_>, _>>()?;
+        repositories.sort();
+
+        let mut packages = config
+            .packages
+            .iter()
+            .map(|package| package.trim())
+            .collect::<Vec<_>>();
+        packages.sort();
+        packages.dedup();
+
+        let mut output = config.output.clone();
+        output.target_architectures.sort();
+        output.target_architectures.dedup();
+
+        Ok(Self {
+            repositories: hash_json(&repositories)?,
+            packages: hash_json(&packages)?,
+            output: hash_json(&output)?,
+        })
+    }
+
+    /// Names of the sections that differ between `self` and `other`, excluding `output`,
+    /// which does not affect dependency resolution.
+    pub fn changed_resolution_sections(&self, other: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.repositories != other.repositories {
+            changed.push("source_repositories");
+        }
+        if self.packages != other.packages {
+            changed.push("packages");
+        }
+        changed
+    }
+}
+
+/// SHA-256 of the raw config file, which version 1 lockfiles recorded as `config_hash`.
+pub fn hash_config_file(config_path: &Path) -> Result<String, AptPrepError> {
+    Ok(format!("{:x}", Sha256::digest(std::fs::read(config_path)?)))
+}
+
+fn canonical_repository(repository: &SourceRepository) -> Result<String, AptPrepError> {
+    let mut repository = repository.clone();
+    repository.architectures.sort();
+    Ok(serde_json::to_string(&repository)?)
+}
+
+fn hash_json<T: Serialize>(value: &T) -> Result<String, AptPrepError> {
+    Ok(format!("{:x}", Sha256::digest(serde_json::to_vec(value)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: &str) -> Config {
+        serde_json::from_str(json).unwrap()
+    }
+
+    const CONFIG: &str = r#"{
+        "source_repositories": [
+            {"source_url": "https://a.example.com", "architectures": ["amd64", "arm64"], "distributions": ["noble"]},
+            {"source_url": "https://b.example.com", "architectures": ["amd64"], "distributions": ["noble"]}
+        ],
+        "packages": ["hello", "curl"],
+        "output": {"path": "./packages", "target_architectures": ["amd64"]}
+    }"#;
+
+    #[test]
+    fn test_order_does_not_change_hashes() {
+        let reordered = config(
+            r#"{
+            "source_repositories": [
+                {"source_url": "https://b.example.com", "architectures": ["amd64"], "distributions": ["noble"]},
+                {"source_url": "https://a.example.com", "architectures": ["arm64", "amd64"], "distributions": ["noble"]}
+            ],
+            "packages": ["curl", "hello"],
+            "output": {"path": "./packages", "target_architectures": ["amd64"]}
+        }"#,
+        );
+        assert_eq!(
+            ConfigHashes::of(&config(CONFIG)).unwrap(),
+            ConfigHashes::of(&reordered).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_output_changes_do_not_affect_resolution() {
+        let moved = config(&CONFIG.replace("./packages", "./elsewhere"));
+        let original = ConfigHashes::of(&config(CONFIG)).unwrap();
+        let changed = ConfigHashes::of(&moved).unwrap();
+        assert_ne!(original.output, changed.output);
+        assert!(original.changed_resolution_sections(&changed).is_empty());
+    }
+
+    #[test]
+    fn test_package_changes_are_reported() {
+        let changed = config(&CONFIG.replace("\"curl\"", "\"wget\""));
+        let original = ConfigHashes::of(&config(CONFIG)).unwrap();
+        assert_eq!(
+            original.changed_resolution_sections(&ConfigHashes::of(&changed).unwrap()),
+            vec!["packages"]
+        );
+    }
+}
@@ -7,5 +7,7 @@ pub fn load_config(config_path: &str) -> Result<Config, AptPrepError> {
         .add_source(config::File::with_name(config_path))
         .build()?;
 
-    config_builder.try_deserialize().map_err(Into::into)
+    let config: Config = config_builder.try_deserialize()?;
+    config.validate()?;
+    Ok(config)
 }
@@ -1,20 +1,188 @@
+use super::release_checks::ReleaseChecks;
+use super::snapshot::SnapshotTimestamp;
+use crate::error::AptPrepError;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use url::Url;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct SourceRepository {
     pub source_url: String,
+    /// Marks the repository as a snapshot archive; `source_url` is then the archive base URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotDef>,
+    /// Fallback URLs serving the same repository, tried in order when `source_url` fails
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// Debian architecture names to fetch, e.g. `amd64`
     pub architectures: Vec<String>,
+    /// Names the repository uses instead of the Debian ones, e.g. `amd64: x86_64`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub architecture_map: BTreeMap<String, String>,
     pub distributions: Vec<DistributionDef>,
+    #[serde(default)]
+    pub release_checks: ReleaseChecks,
+    /// OpenPGP keyring (armored or binary) that Release files must be signed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_by: Option<PathBuf>,
+}
+
+/// Snapshot archive settings. Without a timestamp, the one recorded in the lockfile is used.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotDef {
+    pub timestamp: Option<SnapshotTimestamp>,
+}
+
+/// Architecture names used by non-Debian tooling, with their Debian equivalents.
+const NON_DEBIAN_ARCHITECTURE_NAMES: [(&str, &str); 7] = [
+    ("x86_64", "amd64"),
+    ("aarch64", "arm64"),
+    ("sbsa", "arm64"),
+    ("i686", "i386"),
+    ("ppc64le", "ppc64el"),
+    ("armv7l", "armhf"),
+    ("armhfp", "armhf"),
+];
+
+impl SourceRepository {
+    /// URLs of the repository root in order of preference, starting with `source_url`.
+    /// Snapshot repositories have their timestamp applied to every mirror.
+    pub fn repository_urls(&self) -> Result<Vec<String>, AptPrepError> {
+        std::iter::once(&self.source_url)
+            .chain(self.mirrors.iter())
+            .map(|url| self.apply_snapshot(url))
+            .collect()
+    }
+
+    /// Name the repository uses for a Debian architecture.
+    pub fn repository_architecture<'a>(&'a self, debian_architecture: &'a str) -> &'a str {
+        self.architecture_map
+            .get(debian_architecture)
+            .map(String::as_str)
+            .unwrap_or(debian_architecture)
+    }
+
+    /// Debian architecture for a name used by the repository.
+    pub fn debian_architecture<'a>(&'a self, repository_architecture: &'a str) -> &'a str {
+        self.architecture_map
+            .iter()
+            .find(|(_, name)| name.as_str() == repository_architecture)
+            .map(|(debian_architecture, _)| debian_architecture.as_str())
+            .unwrap_or(repository_architecture)
+    }
+
+    /// Whether packages or indices of the given repository architecture should be read.
+    pub fn serves_architecture(&self, repository_architecture: &str) -> bool {
+        repository_architecture == "all"
+            || self.architectures.iter().any(|architecture| {
+                self.repository_architecture(architecture) == repository_architecture
+            })
+    }
+
+    fn validate(&self) -> Result<(), AptPrepError> {
+        let invalid = |details: String| AptPrepError::RepositoryConfig {
+            repository: self.source_url.clone(),
+            details,
+        };
+
+        if self.architectures.is_empty() {
+            return Err(invalid("No architectures configured".to_string()));
+        }
+        let mut seen = BTreeSet::new();
+        for architecture in &self.architectures {
+            if !seen.insert(architecture.as_str()) {
+                return Err(invalid(format!(
+                    "Architecture '{}' is listed more than once",
+                    architecture
+                )));
+            }
+            if architecture == "all" {
+                return Err(invalid(
+                    "Architecture 'all' is always included and must not be listed".to_string(),
+                ));
+            }
+            if let Some((_, debian_name)) = NON_DEBIAN_ARCHITECTURE_NAMES
+                .iter()
+                .find(|(name, _)| name == architecture)
+            {
+                return Err(invalid(format!(
+                    "'{0}' is not a Debian architecture name. List '{1}' instead and add \
+                     '{1}: {0}' to architecture_map",
+                    architecture, debian_name
+                )));
+            }
+        }
+
+        let mut repository_names = BTreeSet::new();
+        for (debian_architecture, repository_architecture) in &self.architecture_map {
+            if !self.architectures.contains(debian_architecture) {
+                return Err(invalid(format!(
+                    "architecture_map maps '{}', which is not listed in architectures",
+                    debian_architecture
+                )));
+            }
+            if repository_architecture.is_empty() || repository_architecture == "all" {
+                return Err(invalid(format!(
+                    "architecture_map maps '{}' to the invalid name '{}'",
+                    debian_architecture, repository_architecture
+                )));
+            }
+            if !repository_names.insert(repository_architecture.as_str()) {
+                return Err(invalid(format!(
+                    "architecture_map maps more than one architecture to '{}'",
+                    repository_architecture
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_snapshot(&self, url: &str) -> Result<String, AptPrepError> {
+        match &self.snapshot {
+            None => Ok(url.to_string()),
+            Some(SnapshotDef {
+                timestamp: Some(timestamp),
+            }) => Ok(format!("{}/{}", url.trim_end_matches('/'), timestamp)),
+            Some(SnapshotDef { timestamp: None }) => Err(AptPrepError::Snapshot {
+                repository: self.source_url.clone(),
+                details: "No snapshot timestamp configured. Set snapshot.timestamp or pass --snapshot-date.".to_string(),
+            }),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, untagged)]
 pub enum DistributionDef {
     Simple(String),
-    Advanced { distribution_path: String },
+    Advanced {
+        distribution_path: String,
+    },
+    /// Flat repository keeping `Release` and `Packages` in `flat_path` (usually `./`)
+    Flat {
+        flat_path: String,
+    },
+}
+
+impl DistributionDef {
+    /// URL that the `Filename` fields of this distribution are relative to.
+    pub fn base_url(&self, repository_url: &str) -> Result<Url, url::ParseError> {
+        let mut root = Url::parse(repository_url)?;
+        if !root.path().ends_with('/') {
+            root.set_path(&format!("{}/", root.path()));
+        }
+        match self {
+            // `Filename` paths in flat repositories are relative to the repository URL
+            DistributionDef::Simple(_) | DistributionDef::Flat { .. } => Ok(root),
+            DistributionDef::Advanced { distribution_path } => root.join(distribution_path),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -26,9 +194,130 @@ pub struct Config {
     pub output: OutputConfig,
 }
 
+impl Config {
+    /// Checks settings that cannot be expressed through deserialization alone.
+    pub fn validate(&self) -> Result<(), AptPrepError> {
+        self.source_repositories
+            .iter()
+            .try_for_each(|repository| repository.validate())
+    }
+
+    /// Timestamps of all snapshot repositories, keyed by their base URL.
+    pub fn snapshot_timestamps(&self) -> BTreeMap<String, SnapshotTimestamp> {
+        self.source_repositories
+            .iter()
+            .filter_map(|repository| {
+                let timestamp = repository.snapshot.as_ref()?.timestamp?;
+                Some((repository.source_url.clone(), timestamp))
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct OutputConfig {
     pub path: Option<PathBuf>,
     pub target_architectures: Vec<String>,
+    #[serde(skip_serializing_if = "OutputLayout::is_flat")]
+    pub layout: OutputLayout,
+}
+
+/// How downloaded packages are arranged in the output directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLayout {
+    /// All packages next to each other, by file name
+    #[default]
+    Flat,
+    /// `pool/<component>/<prefix>/<source>/`, like a Debian archive
+    Pool,
+}
+
+impl OutputLayout {
+    fn is_flat(&self) -> bool {
+        *self == Self::Flat
+    }
+}
+
+impl FromStr for OutputLayout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "flat" => Ok(Self::Flat),
+            "pool" => Ok(Self::Pool),
+            _ => Err(format!(
+                "Unknown output layout '{value}'. Expected flat or pool"
+            )),
+        }
+    }
+}
+
+impl Display for OutputLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Flat => write!(f, "flat"),
+            Self::Pool => write!(f, "pool"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(architectures: &[&str], architecture_map: &[(&str, &str)]) -> SourceRepository {
+        SourceRepository {
+            source_url: "https://example.com/repo".to_string(),
+            snapshot: None,
+            mirrors: Vec::new(),
+            architectures: architectures.iter().map(|a| a.to_string()).collect(),
+            architecture_map: architecture_map
+                .iter()
+                .map(|(debian, name)| (debian.to_string(), name.to_string()))
+                .collect(),
+            distributions: Vec::new(),
+            release_checks: Default::default(),
+            signed_by: None,
+        }
+    }
+
+    #[test]
+    fn test_architecture_map_translates_both_ways() {
+        let repository = repository(
+            &["amd64", "arm64"],
+            &[("amd64", "x86_64"), ("arm64", "sbsa")],
+        );
+        assert!(repository.validate().is_ok());
+        assert_eq!(repository.repository_architecture("arm64"), "sbsa");
+        assert_eq!(repository.debian_architecture("x86_64"), "amd64");
+        assert_eq!(repository.debian_architecture("amd64"), "amd64");
+        assert!(repository.serves_architecture("sbsa"));
+        assert!(repository.serves_architecture("all"));
+        assert!(!repository.serves_architecture("arm64"));
+    }
+
+    #[test]
+    fn test_non_debian_architecture_names_are_rejected() {
+        assert!(repository(&["x86_64"], &[]).validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_architecture_maps_are_rejected() {
+        assert!(
+            repository(&["amd64"], &[("arm64", "sbsa")])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            repository(
+                &["amd64", "arm64"],
+                &[("amd64", "x86_64"), ("arm64", "x86_64")]
+            )
+            .validate()
+            .is_err()
+        );
+        assert!(repository(&["amd64", "amd64"], &[]).validate().is_err());
+    }
 }
@@ -0,0 +1,119 @@
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Freshness requirements for the Release files of a repository.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReleaseChecks {
+    /// Reject Release files whose `Valid-Until` has passed. Disable for archives that are expired on purpose.
+    pub check_valid_until: bool,
+    /// Reject Release files whose `Date` is older than this, e.g. `7d` or `36h`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<MaxAge>,
+}
+
+impl Default for ReleaseChecks {
+    fn default() -> Self {
+        Self {
+            check_valid_until: true,
+            max_age: None,
+        }
+    }
+}
+
+/// Duration written as an integer followed by a unit: `s`, `m`, `h`, `d` or `w`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MaxAge {
+    amount: i64,
+    unit: char,
+}
+
+impl MaxAge {
+    pub fn as_time_delta(&self) -> TimeDelta {
+        match self.unit {
+            's' => TimeDelta::seconds(self.amount),
+            'm' => TimeDelta::minutes(self.amount),
+            'h' => TimeDelta::hours(self.amount),
+            'd' => TimeDelta::days(self.amount),
+            'w' => TimeDelta::weeks(self.amount),
+            _ => unreachable!("unit is validated when parsing"),
+        }
+    }
+}
+
+impl FromStr for MaxAge {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let invalid =
+            || format!("Invalid max age '{value}'. Expected e.g. 90s, 30m, 12h, 7d or 2w");
+        let unit = value.chars().last().ok_or_else(invalid)?;
+        if !matches!(unit, 's' | 'm' | 'h' | 'd' | 'w') {
+            return Err(invalid());
+        }
+        let amount: i64 = value[..value.len() - 1].parse().map_err(|_| invalid())?;
+        if amount <= 0 {
+            return Err(invalid());
+        }
+        Ok(Self { amount, unit })
+    }
+}
+
+impl TryFrom<String> for MaxAge {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<MaxAge> for String {
+    fn from(value: MaxAge) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for MaxAge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.amount, self.unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_max_age_units() {
+        assert_eq!(
+            "90s".parse::<MaxAge>().unwrap().as_time_delta(),
+            TimeDelta::seconds(90)
+        );
+        assert_eq!(
+            "12h".parse::<MaxAge>().unwrap().as_time_delta(),
+            TimeDelta::hours(12)
+        );
+        assert_eq!(
+            "2w".parse::<MaxAge>().unwrap().as_time_delta(),
+            TimeDelta::days(14)
+        );
+    }
+
+    #[test]
+    fn test_parse_max_age_rejects_invalid_values() {
+        assert!("".parse::<MaxAge>().is_err());
+        assert!("7".parse::<MaxAge>().is_err());
+        assert!("d".parse::<MaxAge>().is_err());
+        assert!("0d".parse::<MaxAge>().is_err());
+        assert!("7y".parse::<MaxAge>().is_err());
+    }
+
+    #[test]
+    fn test_max_age_round_trips() {
+        assert_eq!("7d".parse::<MaxAge>().unwrap().to_string(), "7d");
+    }
+}
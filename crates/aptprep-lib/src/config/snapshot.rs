@@ -0,0 +1,100 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Point in time of a snapshot archive such as snapshot.debian.org or snapshot.ubuntu.com.
+///
+/// Serialized in the `YYYYMMDDTHHMMSSZ` form used by both services in their URLs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SnapshotTimestamp(DateTime<Utc>);
+
+impl SnapshotTimestamp {
+    const URL_FORMAT: &'static str = "%Y%m%dT%H%M%SZ";
+
+    pub fn as_datetime(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+}
+
+impl From<DateTime<Utc>> for SnapshotTimestamp {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self(value)
+    }
+}
+
+impl FromStr for SnapshotTimestamp {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(value, Self::URL_FORMAT) {
+            return Ok(Self(datetime.and_utc()));
+        }
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+            return Ok(Self(datetime.with_timezone(&Utc)));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(Self(date.and_time(NaiveTime::MIN).and_utc()));
+        }
+        Err(format!(
+            "Invalid snapshot timestamp '{value}'. Expected YYYYMMDDTHHMMSSZ, RFC 3339 or YYYY-MM-DD"
+        ))
+    }
+}
+
+impl TryFrom<String> for SnapshotTimestamp {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SnapshotTimestamp> for String {
+    fn from(value: SnapshotTimestamp) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for SnapshotTimestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format(Self::URL_FORMAT))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_format_round_trips() {
+        let timestamp: SnapshotTimestamp = "20251029T140000Z".parse().unwrap();
+        assert_eq!(timestamp.to_string(), "20251029T140000Z");
+    }
+
+    #[test]
+    fn test_parse_rfc3339_is_normalized_to_utc() {
+        let timestamp: SnapshotTimestamp = "2025-10-29T16:00:00+02:00".parse().unwrap();
+        assert_eq!(timestamp.to_string(), "20251029T140000Z");
+    }
+
+    #[test]
+    fn test_parse_date_only_uses_midnight() {
+        let timestamp: SnapshotTimestamp = "2025-10-29".parse().unwrap();
+        assert_eq!(timestamp.to_string(), "20251029T000000Z");
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!("yesterday".parse::<SnapshotTimestamp>().is_err());
+    }
+
+    #[test]
+    fn test_ordering_follows_time() {
+        let earlier: SnapshotTimestamp = "20251029T140000Z".parse().unwrap();
+        let later: SnapshotTimestamp = "20251101T000000Z".parse().unwrap();
+        assert!(earlier < later);
+    }
+}
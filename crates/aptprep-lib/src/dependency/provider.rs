@@ -1,7 +1,7 @@
 use super::resolver::DependencyResolutionError;
 use super::types::{AptDependencyGraphElement, AptVersion, DummyPackageKey};
+use crate::repository::BinaryPackage;
 use crate::utils::arch_matches;
-use debian_packaging::binary_package_control::BinaryPackageControlFile;
 use debian_packaging::dependency::{
     DependencyVariants, DependencyVersionConstraint, SingleDependency, VersionRelationship,
 };
@@ -41,7 +41,7 @@ pub struct AptPackage {
 #[derive(Clone, Debug)]
 pub struct DependenciesByVersionEntry {
     pub dependencies: Map<AptDependencyGraphElement, Ranges<AptVersion>>,
-    pub control_file: Option<Arc<BinaryPackageControlFile<'static>>>,
+    pub package: Option<BinaryPackage>,
 }
 
 impl Display for AptPackage {
@@ -289,23 +289,27 @@ impl DependencyProvider for AptDependencyProvider {
 }
 impl AptDependencyProvider {
     pub fn new(
-        packages: impl Iterator<Item = Arc<BinaryPackageControlFile<'static>>>,
+        packages: impl Iterator<Item = BinaryPackage>,
         arch: &str,
     ) -> Result<Self, DependencyResolutionError> {
         let mut dummy_id = 0;
         let mut binary_packages: HashMap<Arc<str>, AptPackage> = HashMap::new();
         let mut dummy_packages: HashMap<DummyPackageKey, DummyPackageData> = HashMap::new();
-        let binary_packages_by_package_name: HashMap<
-            Arc<str>,
-            Vec<Arc<BinaryPackageControlFile<'_>>>,
-        > = packages.into_iter().into_group_map_by(|x| {
-            Arc::from(x.package().expect("Package name not found").to_string())
-        });
+        let binary_packages_by_package_name: HashMap<Arc<str>, Vec<BinaryPackage>> =
+            packages.into_iter().into_group_map_by(|x| {
+                Arc::from(
+                    x.control_file
+                        .package()
+                        .expect("Package name not found")
+                        .to_string(),
+                )
+            });
         let provided_by = Self::collect_virtual_packages(&binary_packages_by_package_name, arch);
-        for (package_name, control_files) in binary_packages_by_package_name.iter() {
+        for (package_name, packages) in binary_packages_by_package_name.iter() {
             let mut dependencies_by_version: BTreeMap<AptVersion, DependenciesByVersionEntry> =
                 BTreeMap::new();
-            'control: for control in control_files {
+            'control: for package in packages {
+                let control = &package.control_file;
                 let fields = control
                     .package_dependency_fields()
                     .expect("Failed to read package");
@@ -384,7 +388,7 @@ impl AptDependencyProvider {
                                         PackageVersion::parse(&format!("{}:1.0.0", j)).unwrap(),
                                     ),
                                     DependenciesByVersionEntry {
-                                        control_file: None,
+                                        package: None,
                                         dependencies: virtual_res,
                                     },
                                 );
@@ -412,7 +416,7 @@ impl AptDependencyProvider {
                     version,
                     DependenciesByVersionEntry {
                         dependencies: current_package_dependencies,
-                        control_file: Some(control.clone()),
+                        package: Some(package.clone()),
                     },
                 );
             }
@@ -435,7 +439,7 @@ impl AptDependencyProvider {
     }
 
     fn collect_solutions<'b>(
-        binary_packages_by_package_name: &'b HashMap<Arc<str>, Vec<Arc<BinaryPackageControlFile>>>,
+        binary_packages_by_package_name: &'b HashMap<Arc<str>, Vec<BinaryPackage>>,
         provided_by: &'b ProvidedByMap,
         dependency_variants: &DependencyVariants,
         arch: &str,
@@ -449,7 +453,7 @@ impl AptDependencyProvider {
                     return solutions;
                 }
 
-                if let Some(_control_files) =
+                if let Some(_packages) =
                     binary_packages_by_package_name.get(dependency.package.as_str())
                 {
                     // Real binary package
@@ -495,12 +499,13 @@ impl AptDependencyProvider {
     }
 
     fn collect_virtual_packages(
-        grouped_packages: &HashMap<Arc<str>, Vec<Arc<BinaryPackageControlFile>>>,
+        grouped_packages: &HashMap<Arc<str>, Vec<BinaryPackage>>,
         arch: &str,
     ) -> ProvidedByMap {
         let mut provided_by = HashMap::new();
-        for (package_name, control_files) in grouped_packages.iter() {
-            for control in control_files {
+        for (package_name, packages) in grouped_packages.iter() {
+            for package in packages {
+                let control = &package.control_file;
                 let fields = control
                     .package_dependency_fields()
                     .expect("Failed to read package");
@@ -526,13 +531,13 @@ impl AptDependencyProvider {
         }
         provided_by
     }
-    pub fn get_control(
+    pub fn get_package(
         &self,
         package_name: &str,
         apt_version: &AptVersion,
-    ) -> Option<&Arc<BinaryPackageControlFile<'static>>> {
+    ) -> Option<&BinaryPackage> {
         let package = self.binary_packages.get(package_name)?;
         let deps = package.dependencies_by_version.get(apt_version)?;
-        deps.control_file.as_ref()
+        deps.package.as_ref()
     }
 }
@@ -1,12 +1,11 @@
 use super::provider::AptDependencyProvider;
 use super::types::{AptDependencyGraphElement, AptVersion, RequestedPackages};
 use crate::repository::types::{BinaryPackage, iterate_all_relevant_packages};
-use debian_packaging::binary_package_control::BinaryPackageControlFile;
 use debian_packaging::error::DebianError;
 use debian_packaging::package_version::PackageVersion;
 use eyre::WrapErr;
 use pubgrub::{DefaultStringReporter, PubGrubError, Reporter, resolve};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
 use tracing;
@@ -23,15 +22,16 @@ pub enum DependencyResolutionError {
     Unexpected(#[from] eyre::Report),
 }
 
+/// Resolves the required packages for an architecture, returning the exact packages chosen
+/// together with the repository each one came from.
 pub fn resolve_dependencies(
     binary_packages: &HashMap<String, Vec<BinaryPackage>>,
     required_packages: &[Arc<str>],
     architecture: &str,
-) -> Result<BTreeSet<Arc<BinaryPackageControlFile<'static>>>, DependencyResolutionError> {
+) -> Result<Vec<BinaryPackage>, DependencyResolutionError> {
     tracing::info!("Loading packages for {}", &architecture);
     let dependency_provider = AptDependencyProvider::new(
-        iterate_all_relevant_packages(binary_packages, &architecture.to_string())
-            .map(|v| v.control_file.clone()),
+        iterate_all_relevant_packages(binary_packages, &architecture.to_string()).cloned(),
         architecture,
     )
     .wrap_err("Failed to prepare for pubgrub dependency resolution")?;
@@ -69,13 +69,13 @@ pub fn resolve_dependencies(
     };
 
     // tracing::info!("Resolved: {:?}", resolved);
-    let mut collected_packages = BTreeSet::new();
+    let mut collected_packages = Vec::new();
 
     for (package, version) in resolved {
         match package {
             AptDependencyGraphElement::AptPackage(package_name) => {
-                if let Some(package) = dependency_provider.get_control(&package_name, &version) {
-                    collected_packages.insert(package.clone());
+                if let Some(package) = dependency_provider.get_package(&package_name, &version) {
+                    collected_packages.push(package.clone());
                 } else {
                     tracing::warn!(
                         "Package {} with version {} not found",
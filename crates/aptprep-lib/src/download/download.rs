@@ -1,17 +1,22 @@
-use super::types::{DownloadAndCheckOptions, DownloadItem};
+use super::manifest::VerificationManifest;
+use super::progress::{ProgressReporter, ProgressTimer};
+use super::types::{BandwidthLimit, DownloadAndCheckOptions, DownloadItem, DownloadSource};
 use crate::verification::content_digest_hasher::ContentDigestVerifier;
-use debian_packaging::checksum::AnyContentDigest;
 use eyre::{Result, WrapErr, eyre};
 use futures::stream::{FuturesUnordered, StreamExt};
-use md5::Md5;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use opendal::Operator;
 use opendal::layers::{ConcurrentLimitLayer, RetryLayer};
 use opendal::services::Http;
-use sha1::Sha1;
-use sha2::{Digest as Sha2Digest, Sha256, Sha384, Sha512};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How often the overall progress is logged when it is not drawn on a terminal.
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(10);
 
 fn build_http_operator(
     base_url: &str,
@@ -29,25 +34,419 @@ fn build_http_operator(
     Ok(op)
 }
 
+/// Bandwidth budget shared by all downloads, or by those from one host.
+///
+/// OpenDAL's `ThrottleLayer` only throttles writes, so downloads are throttled here as their
+/// chunks arrive.
+struct SharedBandwidth {
+    limiter: DefaultDirectRateLimiter,
+    burst: NonZeroU32,
+}
+
+impl SharedBandwidth {
+    fn new(limit: BandwidthLimit) -> Self {
+        Self {
+            limiter: RateLimiter::direct(
+                Quota::per_second(limit.bytes_per_second).allow_burst(limit.burst),
+            ),
+            burst: limit.burst,
+        }
+    }
+
+    /// Waits until `bytes` fit into the budget, taking them in portions no larger than the
+    /// burst.
+    async fn consume(&self, mut bytes: usize) {
+        while let Some(portion) = NonZeroU32::new(bytes.min(self.burst.get() as usize) as u32) {
+            // Cannot fail, the portion never exceeds the burst
+            let _ = self.limiter.until_n_ready(portion).await;
+            bytes -= portion.get() as usize;
+        }
+    }
+}
+
+/// Path a download is written to until its digest has been verified.
+fn part_path(output_path: &Path) -> PathBuf {
+    let mut path = output_path.as_os_str().to_owned();
+    path.push(".part");
+    PathBuf::from(path)
+}
+
+/// Feeds the bytes of a file, e.g. an interrupted download, into `hasher` and returns how many
+/// there are. A missing file counts as empty.
+async fn hash_existing_file(path: &Path, hasher: &mut ContentDigestVerifier) -> Result<u64> {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => {
+            return Err(e).wrap_err_with(|| format!("Failed to open {}", path.display()));
+        }
+    };
+    let mut reader = tokio::io::BufReader::new(file);
+    let mut buffer = vec![0u8; 65536];
+    let mut length = 0;
+
+    loop {
+        let bytes_read = tokio::io::AsyncReadExt::read(&mut reader, &mut buffer)
+            .await
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        if bytes_read == 0 {
+            break;
+        }
+        tokio::task::block_in_place(|| hasher.update(&buffer[..bytes_read]));
+        length += bytes_read as u64;
+    }
+    Ok(length)
+}
+
+/// State shared by all transfers.
+#[derive(Clone, Copy)]
+struct TransferContext<'a> {
+    reporter: &'a ProgressReporter,
+    bandwidth: Option<&'a SharedBandwidth>,
+    /// Budgets by base URL, i.e. by host
+    host_bandwidth: &'a HashMap<String, SharedBandwidth>,
+}
+
+/// Appends the missing bytes of `source` to the part file of `target_path`, resuming with a
+/// range read where a previous attempt stopped. Returns the hasher over the whole file, the
+/// resume offset and the length of the file. The file is synced to disk before returning.
+///
+/// Progress is reported for `output_path`, which differs from `target_path` for downloads into
+/// the cache.
+async fn transfer_to_part_file(
+    op: &Operator,
+    source: &DownloadSource,
+    item: &DownloadItem,
+    target_path: &Path,
+    output_path: &Path,
+    context: TransferContext<'_>,
+) -> Result<(ContentDigestVerifier, u64, u64)> {
+    let part_path = &part_path(target_path);
+    let DownloadSource { base_url, rel_path } = source;
+    let TransferContext {
+        reporter,
+        bandwidth,
+        host_bandwidth,
+    } = context;
+    let host_bandwidth = host_bandwidth.get(base_url);
+    let DownloadItem { digest, size, .. } = item;
+    let size = *size;
+
+    let mut hasher = ContentDigestVerifier::new(digest.clone());
+    let mut offset = hash_existing_file(part_path, &mut hasher).await?;
+    if size.is_some_and(|size| offset > size) {
+        tracing::info!(output = %part_path.display(), "Partial file is larger than expected, restarting");
+        hasher = ContentDigestVerifier::new(digest.clone());
+        offset = 0;
+    }
+    // Nothing left to fetch, the caller only has to verify
+    if offset > 0 && size == Some(offset) {
+        return Ok((hasher, offset, offset));
+    }
+
+    let open_stream = |offset: u64| async move {
+        op.reader(rel_path)
+            .await
+            .wrap_err_with(|| format!("Failed to create reader for {}{}", base_url, rel_path))?
+            .into_stream(offset..)
+            .await
+            .wrap_err_with(|| format!("Failed to create reader for {}{}", base_url, rel_path))
+    };
+    let mut reader = match open_stream(offset).await {
+        Ok(reader) => reader,
+        Err(err) if offset > 0 => {
+            warn!(base = %base_url, path = %rel_path, "Range request failed, restarting from the beginning: {:#}", err);
+            hasher = ContentDigestVerifier::new(digest.clone());
+            offset = 0;
+            open_stream(0).await?
+        }
+        Err(err) => return Err(err),
+    };
+    if offset > 0 {
+        info!(base = %base_url, path = %rel_path, offset, "Resuming download");
+    }
+    reporter.start(
+        output_path,
+        format!("{}{}", base_url, rel_path),
+        size,
+        offset,
+    );
+
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(part_path)
+        .await
+        .wrap_err_with(|| format!("Failed to create output file: {}", part_path.display()))?;
+    let mut writer = tokio::io::BufWriter::new(file);
+
+    let mut length = offset;
+    let mut timer = ProgressTimer::new();
+    while let Some(reader_res) = reader.next().await {
+        let buffer = reader_res
+            .wrap_err_with(|| format!("Failed to read from {}{}", base_url, rel_path))?
+            .to_bytes();
+        for bandwidth in [bandwidth, host_bandwidth].into_iter().flatten() {
+            bandwidth.consume(buffer.len()).await;
+        }
+
+        length += buffer.len() as u64;
+        // A server that ignores the range sends the whole file again. Stop instead of writing
+        // all of it; the next attempt starts over.
+        if let Some(size) = size.filter(|size| length > *size) {
+            drop(writer);
+            tokio::fs::remove_file(part_path).await.wrap_err_with(|| {
+                format!("Failed to delete partial file: {}", part_path.display())
+            })?;
+            return Err(eyre!(
+                "Received more than the expected {} bytes from {}{}",
+                size,
+                base_url,
+                rel_path
+            ));
+        }
+
+        // Update the appropriate hasher based on digest type
+        tokio::task::block_in_place(|| hasher.update(&buffer));
+
+        tokio::io::AsyncWriteExt::write_all(&mut writer, &buffer)
+            .await
+            .wrap_err_with(|| format!("Failed to write to {}", part_path.display()))?;
+        reporter.advance(output_path, buffer.len() as u64, length, size, timer.due());
+    }
+
+    // Finalize the write
+    tokio::io::AsyncWriteExt::flush(&mut writer)
+        .await
+        .wrap_err_with(|| format!("Failed to flush {}", part_path.display()))?;
+    writer
+        .into_inner()
+        .sync_all()
+        .await
+        .wrap_err_with(|| format!("Failed to sync {}", part_path.display()))?;
+
+    Ok((hasher, offset, length))
+}
+
+/// Downloads and verifies a file to `target_path`, returning its length.
+async fn download_from_source(
+    op: &Operator,
+    source: &DownloadSource,
+    item: &DownloadItem,
+    target_path: &Path,
+    output_path: &Path,
+    context: TransferContext<'_>,
+) -> Result<u64> {
+    let DownloadSource { base_url, rel_path } = source;
+    let size = item.size;
+    tracing::debug!(base = %base_url, path = %rel_path, output = %target_path.display(), expected_digest = item.digest.digest_hex(), "Downloading");
+
+    // Interrupted transfers stay in the part file and are resumed by the next attempt. Only
+    // bytes that fail verification are thrown away. The file gets its real name once it is
+    // complete, synced and verified, so a crash never leaves a truncated package behind.
+    let part_path = part_path(target_path);
+    let length = loop {
+        let (hasher, resumed_from, length) =
+            transfer_to_part_file(op, source, item, target_path, output_path, context).await?;
+        if let Some(size) = size.filter(|size| length < *size) {
+            return Err(eyre!(
+                "Short response from {}{}: expected {} bytes, got {}",
+                base_url,
+                rel_path,
+                size,
+                length
+            ));
+        }
+        match hasher.verify() {
+            Ok(()) => break length,
+            Err(err) => {
+                tokio::fs::remove_file(&part_path).await.wrap_err_with(|| {
+                    format!("Failed to delete partial file: {}", part_path.display())
+                })?;
+                if resumed_from == 0 {
+                    return Err(err)
+                        .wrap_err_with(|| format!("Failed to verify {}", target_path.display()));
+                }
+                warn!(base = %base_url, path = %rel_path, "Resumed download failed verification, restarting from the beginning");
+            }
+        }
+    };
+
+    tokio::fs::rename(&part_path, target_path)
+        .await
+        .wrap_err_with(|| format!("Failed to move {} into place", part_path.display()))?;
+    Ok(length)
+}
+
+/// Hashes an existing file and returns its length if it has the expected digest. Files of the
+/// wrong size or with the wrong digest are deleted.
+async fn check_existing_file(path: &Path, item: &DownloadItem) -> Result<Option<u64>> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).wrap_err_with(|| format!("Failed to inspect {}", path.display()));
+        }
+    };
+
+    // A file of the wrong size cannot have the right digest, so don't hash it
+    if item.size.is_some_and(|size| metadata.len() != size) {
+        tracing::info!(output = %path.display(), "File exists with incorrect size, deleting");
+    } else {
+        let mut hasher = ContentDigestVerifier::new(item.digest.clone());
+        let length = hash_existing_file(path, &mut hasher).await?;
+        if hasher.verify().is_ok() {
+            tracing::debug!(output = %path.display(), "File exists with matching digest, skipping download");
+            return Ok(Some(length));
+        }
+        tracing::info!(output = %path.display(), "File exists with incorrect digest, deleting");
+    }
+
+    tokio::fs::remove_file(path)
+        .await
+        .wrap_err_with(|| format!("Failed to delete {}", path.display()))?;
+    Ok(None)
+}
+
+/// Puts a verified cache entry at `output_path` as a hardlink, or as a copy when the cache is
+/// on another file system. On Linux the copy uses `copy_file_range`, which shares the data
+/// blocks on file systems with reflink support.
+async fn link_from_cache(entry_path: &Path, output_path: &Path) -> Result<()> {
+    let part_path = part_path(output_path);
+    match tokio::fs::remove_file(&part_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e).wrap_err_with(|| {
+                format!("Failed to delete partial file: {}", part_path.display())
+            });
+        }
+    }
+
+    if let Err(e) = tokio::fs::hard_link(entry_path, &part_path).await {
+        tracing::debug!(entry = %entry_path.display(), "Hardlinking failed, copying: {}", e);
+        tokio::fs::copy(entry_path, &part_path)
+            .await
+            .wrap_err_with(|| format!("Failed to copy {}", entry_path.display()))?;
+        tokio::fs::File::open(&part_path)
+            .await
+            .wrap_err_with(|| format!("Failed to open {}", part_path.display()))?
+            .sync_all()
+            .await
+            .wrap_err_with(|| format!("Failed to sync {}", part_path.display()))?;
+    }
+
+    tokio::fs::rename(&part_path, output_path)
+        .await
+        .wrap_err_with(|| format!("Failed to move {} into place", part_path.display()))
+}
+
+/// Deletes part files in the output directories that belong to none of the items, e.g. those
+/// of packages dropped from the lockfile. Part files of pending items are kept for resuming.
+fn remove_stale_part_files(output_paths: &[PathBuf]) -> Result<()> {
+    let part_paths = output_paths
+        .iter()
+        .map(|output_path| part_path(output_path))
+        .collect::<HashSet<_>>();
+    let directories = output_paths
+        .iter()
+        .filter_map(|output_path| output_path.parent())
+        .collect::<HashSet<_>>();
+
+    for directory in directories {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(e).wrap_err_with(|| {
+                    format!("Failed to list directory: {}", directory.display())
+                });
+            }
+        };
+        for entry in entries {
+            let path = entry
+                .wrap_err_with(|| format!("Failed to list directory: {}", directory.display()))?
+                .path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "part")
+                && !part_paths.contains(&path)
+            {
+                tracing::info!(path = %path.display(), "Removing stale partial download");
+                std::fs::remove_file(&path).wrap_err_with(|| {
+                    format!("Failed to delete partial file: {}", path.display())
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
 pub async fn download_and_check_all(
     items: Vec<DownloadItem>,
-    output_dir: impl AsRef<std::path::Path>,
+    output_dir: impl AsRef<Path>,
     options: DownloadAndCheckOptions,
 ) -> Result<()> {
     // Build per-base operator so multiple items from the same repo reuse the same HTTP client.
     let mut per_base: HashMap<String, Operator> = HashMap::new();
     tracing::info!("Creating operators...");
-    for it in &items {
-        let key = it.base_url.as_str().to_string();
-        if let std::collections::hash_map::Entry::Vacant(e) = per_base.entry(key) {
+    for source in items.iter().flat_map(|it| it.sources()) {
+        if let std::collections::hash_map::Entry::Vacant(e) = per_base.entry(source.base_url) {
             let op = build_http_operator(
-                &it.base_url,
+                e.key(),
                 options.max_concurrency_per_host,
                 options.max_retries,
             )?;
             e.insert(op);
         }
     }
+    let per_base = Arc::new(per_base);
+    let bandwidth = options
+        .bandwidth_limit
+        .map(|limit| Arc::new(SharedBandwidth::new(limit)));
+    let host_bandwidth = Arc::new(match options.bandwidth_limit_per_host {
+        Some(limit) => per_base
+            .keys()
+            .map(|base_url| (base_url.clone(), SharedBandwidth::new(limit)))
+            .collect(),
+        None => HashMap::new(),
+    });
+
+    let output_paths = items
+        .iter()
+        .map(|it| it.output_path_in(output_dir.as_ref()))
+        .collect::<Vec<_>>();
+    remove_stale_part_files(&output_paths)?;
+
+    let manifest = Arc::new(VerificationManifest::open(
+        output_dir.as_ref(),
+        options.manifest_key_path.as_deref(),
+        !options.full_verify,
+    ));
+
+    let reporter = Arc::new(ProgressReporter::new(
+        options.events,
+        items.len() as u64,
+        items.iter().filter_map(|it| it.size).sum(),
+    ));
+    let progress = options.progress;
+    let display = tokio::spawn({
+        let reporter = reporter.clone();
+        async move {
+            if progress {
+                reporter.draw().await;
+            } else {
+                let mut interval = tokio::time::interval(PROGRESS_LOG_INTERVAL);
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    info!("{}", reporter.status_line());
+                }
+            }
+        }
+    });
     tracing::info!("Starting...");
 
     // Use a bounded unordered stream for parallel downloads across bases.
@@ -58,143 +457,119 @@ pub async fn download_and_check_all(
     let mut futs = FuturesUnordered::new();
     for it in items {
         let key = it.base_url.as_str().to_string();
-        let op = per_base
-            .get(&key)
-            .expect("operator must be present")
-            .clone();
+        let per_base = per_base.clone();
         let output_dir = output_dir.as_ref().to_path_buf();
         let download_semaphore = download_semaphore.clone();
         let checking_semaphore = checking_semaphore.clone();
+        let reporter = reporter.clone();
+        let bandwidth = bandwidth.clone();
+        let host_bandwidth = host_bandwidth.clone();
+        let cache = options.cache.clone();
+        let manifest = manifest.clone();
         futs.push(async move {
-            let permit = checking_semaphore.acquire_owned().await?;
             let rel = it.rel_path.clone();
+            let output_path = it.output_path_in(&output_dir);
+            // Where the last attempt downloaded from, if the item fails during a download
+            let mut failed_url = None;
+            let result: Result<()> = async {
+                let permit = checking_semaphore.acquire_owned().await?;
+                let relative_path = output_path
+                    .strip_prefix(&output_dir)
+                    .unwrap_or(&output_path)
+                    .to_string_lossy()
+                    .into_owned();
+                tracing::trace!(base = %key, path = %rel, output = %output_path.display(), "Checking");
 
-            // Determine output path
-            let output_path = match &it.output_path {
-                Some(custom_path) => output_dir.join(custom_path),
-                None => output_dir.join(&rel),
-            };
-            tracing::trace!(base = %key, path = %rel, output = %output_path.display(), "Checking");
-
-            // Ensure parent directory exists
-            if let Some(parent) = output_path.parent() {
-                std::fs::create_dir_all(parent)
-                    .wrap_err_with(|| format!("Failed to create directory: {}", parent.display()))?;
-            }
+                // Files that are unchanged since they were last verified are not hashed again
+                if let Some(length) = manifest.unchanged_length(&output_path, &relative_path, &it.digest) {
+                    tracing::debug!(output = %output_path.display(), "File unchanged since last verification, skipping download");
+                    reporter.verified(&output_path, length, true);
+                    return Ok(());
+                }
 
-            // Check if file already exists
-            if output_path.exists() {
-                // Verify the digest of the existing file using streaming to avoid loading the entire file into memory
-                let mut sha1_hasher = Sha1::new();
-                let mut sha256_hasher = Sha256::new();
-                let mut sha384_hasher = Sha384::new();
-                let mut sha512_hasher = Sha512::new();
-                let mut md5_hasher = Md5::new();
-
-                let file = tokio::fs::File::open(&output_path)
-                    .await
-                    .wrap_err_with(|| format!("Failed to open existing file: {}", output_path.display()))?;
-                let mut reader = tokio::io::BufReader::new(file);
-                let mut buffer = vec![0u8; 65536]; // 64KB buffer for reading chunks
+                // Ensure parent directory exists
+                if let Some(parent) = output_path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .wrap_err_with(|| format!("Failed to create directory: {}", parent.display()))?;
+                }
 
-                loop {
-                    let bytes_read = tokio::io::AsyncReadExt::read(&mut reader, &mut buffer)
-                        .await
-                        .wrap_err_with(|| format!("Failed to read from existing file: {}", output_path.display()))?;
+                if let Some(length) = check_existing_file(&output_path, &it).await? {
+                    manifest.record(&output_path, &relative_path, &it.digest);
+                    reporter.verified(&output_path, length, true);
+                    return Ok(());
+                }
 
-                    if bytes_read == 0 {
-                        break; // End of file
+                // With a cache, downloads go into the cache and are linked to the output from there
+                let cache_entry = cache.as_ref().map(|cache| cache.entry_path(&it.digest));
+                // Held until the entry is linked, so that concurrent runs wait for each other
+                // instead of writing the same part file
+                let _entry_lock = match &cache {
+                    Some(cache) => Some(cache.lock_entry(&it.digest).await?),
+                    None => None,
+                };
+                if let Some(entry_path) = &cache_entry {
+                    if let Some(length) = check_existing_file(entry_path, &it).await? {
+                        link_from_cache(entry_path, &output_path).await?;
+                        manifest.record(&output_path, &relative_path, &it.digest);
+                        tracing::debug!(base = %key, path = %rel, output = %output_path.display(), "Taken from cache");
+                        reporter.verified(&output_path, length, true);
+                        return Ok(());
+                    }
+                    if let Some(parent) = entry_path.parent() {
+                        std::fs::create_dir_all(parent).wrap_err_with(|| {
+                            format!("Failed to create directory: {}", parent.display())
+                        })?;
                     }
-
-                    // Only update the hasher for the expected digest type
-                    tokio::task::block_in_place(|| match &it.digest {
-                        AnyContentDigest::Sha1(_) => sha1_hasher.update(&buffer[..bytes_read]),
-                        AnyContentDigest::Sha256(_) => sha256_hasher.update(&buffer[..bytes_read]),
-                        AnyContentDigest::Sha384(_) => sha384_hasher.update(&buffer[..bytes_read]),
-                        AnyContentDigest::Sha512(_) => sha512_hasher.update(&buffer[..bytes_read]),
-                        AnyContentDigest::Md5(_) => md5_hasher.update(&buffer[..bytes_read]),
-                    });
                 }
+                let target_path = cache_entry.as_deref().unwrap_or(&output_path);
+                drop(permit);
 
-                let existing_digest_valid = match &it.digest {
-                    AnyContentDigest::Sha1(expected) => {
-                        let calculated = sha1_hasher.finalize();
-                        calculated.as_slice() == expected.as_slice()
-                    },
-                    AnyContentDigest::Sha256(expected) => {
-                        let calculated = sha256_hasher.finalize();
-                        calculated.as_slice() == expected.as_slice()
-                    },
-                    AnyContentDigest::Sha384(expected) => {
-                        let calculated = sha384_hasher.finalize();
-                        calculated.as_slice() == expected.as_slice()
-                    },
-                    AnyContentDigest::Sha512(expected) => {
-                        let calculated = sha512_hasher.finalize();
-                        calculated.as_slice() == expected.as_slice()
-                    },
-                    AnyContentDigest::Md5(expected) => {
-                        let calculated = md5_hasher.finalize();
-                        calculated.as_slice() == expected.as_slice()
-                    },
+                let _permit = download_semaphore.acquire_owned().await?;
+                let context = TransferContext {
+                    reporter: &reporter,
+                    bandwidth: bandwidth.as_deref(),
+                    host_bandwidth: &host_bandwidth,
                 };
 
-                if existing_digest_valid {
-                    tracing::debug!(base = %key, path = %rel, output = %output_path.display(), "File exists with matching digest, skipping download");
-                    return Ok(());
-                } else {
-                    // Delete the file with mismatched digest
-                    tracing::info!(base = %key, path = %rel, output = %output_path.display(), "File exists with incorrect digest, deleting");
-                    tokio::fs::remove_file(&output_path)
-                        .await
-                        .wrap_err_with(|| format!("Failed to delete file with incorrect digest: {}", output_path.display()))?;
+                // Try the primary location first, then rotate through the mirrors. The digest
+                // check makes it irrelevant which of them ends up serving the file.
+                let mut last_error = None;
+                let source_count = it.mirrors.len() + 1;
+                for (index, source) in it.sources().enumerate() {
+                    let op = per_base
+                        .get(&source.base_url)
+                        .expect("operator must be present");
+                    match download_from_source(op, &source, &it, target_path, &output_path, context).await {
+                        Ok(length) => {
+                            if cache_entry.is_some() {
+                                link_from_cache(target_path, &output_path).await?;
+                            }
+                            manifest.record(&output_path, &relative_path, &it.digest);
+                            debug!(base = %source.base_url, path = %source.rel_path, output = %output_path.display(), "Downloaded and verified");
+                            reporter.verified(&output_path, length, false);
+                            return Ok(());
+                        }
+                        Err(err) => {
+                            warn!(base = %source.base_url, path = %source.rel_path, "Download attempt failed: {:#}", err);
+                            let url = format!("{}{}", source.base_url, source.rel_path);
+                            if index + 1 < source_count {
+                                reporter.failed(&output_path, Some(url), &err, true);
+                            } else {
+                                failed_url = Some(url);
+                            }
+                            last_error = Some(err);
+                        }
+                    }
                 }
+                Err(last_error.expect("a download item has at least one source"))
             }
-            drop(permit);
-
-            let _permit = download_semaphore.acquire_owned().await?;
-            tracing::info!(base = %key, path = %rel, output = %output_path.display(), expected_digest = it.digest.digest_hex(), "Downloading");
-
-            let mut hasher = ContentDigestVerifier::new(it.digest.clone());
-
-            // Stream the file to disk while calculating hash
-            let mut reader = op.reader(&rel)
-                .await
-                .wrap_err_with(|| format!("Failed to create reader for {}{}", key, rel))?
-                .into_stream(..)
-                .await
-                .wrap_err_with(|| format!("Failed to create reader for {}{}", key, rel))?;
-
-            let file = tokio::fs::File::create(&output_path)
-                .await
-                .wrap_err_with(|| format!("Failed to create output file: {}", output_path.display()))?;
-            let mut writer = tokio::io::BufWriter::new(file);
-
-
-            loop {
-                let Some(reader_res) = reader.next().await else {
-                    break;
-                };
-                let buffer = reader_res.wrap_err_with(|| format!("Failed to read from {}{}", key, rel))?.to_bytes();
-
-                // Update the appropriate hasher based on digest type
-                tokio::task::block_in_place(|| hasher.update(&buffer));
-
-                tokio::io::AsyncWriteExt::write_all(&mut writer, &buffer)
-                    .await
-                    .wrap_err_with(|| format!("Failed to write to {}", output_path.display()))?;
+            .await;
 
+            // Every failure of the item is reported, not only those of download attempts
+            if let Err(err) = &result {
+                reporter.failed(&output_path, failed_url, err, false);
             }
-
-            // Finalize the write
-            tokio::io::AsyncWriteExt::flush(&mut writer)
-                .await
-                .wrap_err_with(|| format!("Failed to flush {}", output_path.display()))?;
-
-            // Verify the hash
-            hasher.verify().wrap_err_with(|| format!("Failed to verify {}", output_path.display()))?;
-            info!(base = %key, path = %rel, output = %output_path.display(), "Downloaded and verified");
-            Ok::<(), eyre::Report>(())
+            result
         });
     }
 
@@ -208,6 +583,17 @@ pub async fn download_and_check_all(
         }
     }
 
+    if let Err(err) = manifest.save() {
+        warn!("Failed to save the verification manifest: {:#}", err);
+    }
+
+    display.abort();
+    if progress {
+        reporter.finish_display();
+    } else {
+        info!("{}", reporter.status_line());
+    }
+
     if failures.is_empty() {
         Ok(())
     } else {
@@ -0,0 +1,274 @@
+use crate::cache::PackageCache;
+use debian_packaging::checksum::AnyContentDigest;
+use eyre::{Result, WrapErr, eyre};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Name of the manifest in the output directory.
+pub const MANIFEST_FILE_NAME: &str = ".aptprep-manifest.json";
+
+const MANIFEST_VERSION: u32 = 1;
+const KEY_LENGTH: usize = 32;
+
+type ManifestMac = Hmac<Sha256>;
+
+/// Identity of a file at the time its digest was verified.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ManifestEntry {
+    size: u64,
+    mtime_secs: i64,
+    mtime_nanos: u32,
+    inode: u64,
+    /// Verified digest as a cache key, `<algorithm>/<hex digest>`
+    digest: String,
+}
+
+impl ManifestEntry {
+    fn new(metadata: &Metadata, digest: &AnyContentDigest) -> Option<Self> {
+        let mtime = metadata.modified().ok()?;
+        let (mtime_secs, mtime_nanos) = match mtime.duration_since(UNIX_EPOCH) {
+            Ok(duration) => (
+                i64::try_from(duration.as_secs()).ok()?,
+                duration.subsec_nanos(),
+            ),
+            Err(e) => (
+                -i64::try_from(e.duration().as_secs()).ok()?,
+                e.duration().subsec_nanos(),
+            ),
+        };
+        Some(Self {
+            size: metadata.len(),
+            mtime_secs,
+            mtime_nanos,
+            inode: inode(metadata),
+            digest: PackageCache::key(digest),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn inode(metadata: &Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &Metadata) -> u64 {
+    0
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    version: u32,
+    entries: BTreeMap<String, ManifestEntry>,
+    /// HMAC-SHA256 over the version and the entries with the key of the local machine
+    mac: String,
+}
+
+/// Files in the output directory whose digests have been verified, so that unchanged files are
+/// not hashed again.
+///
+/// The manifest is authenticated with a key that never leaves the local machine. A manifest
+/// that was edited, or copied from elsewhere, is ignored and every file is hashed.
+pub(super) struct VerificationManifest {
+    path: PathBuf,
+    key: Option<[u8; KEY_LENGTH]>,
+    previous: BTreeMap<String, ManifestEntry>,
+    current: Mutex<BTreeMap<String, ManifestEntry>>,
+}
+
+impl VerificationManifest {
+    /// Reads the manifest of `output_dir`. Without a key the manifest is neither read nor
+    /// written. With `trust_entries` unset, every file is hashed and the manifest rebuilt.
+    pub(super) fn open(output_dir: &Path, key_path: Option<&Path>, trust_entries: bool) -> Self {
+        let key = key_path.and_then(|key_path| match load_or_create_key(key_path) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                tracing::warn!("Not using a verification manifest: {:#}", e);
+                None
+            }
+        });
+        let path = output_dir.join(MANIFEST_FILE_NAME);
+        let previous = match &key {
+            Some(key) if trust_entries => read_manifest(&path, key).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring {}: {:#}", path.display(), e);
+                BTreeMap::new()
+            }),
+            _ => BTreeMap::new(),
+        };
+        Self {
+            path,
+            key,
+            previous,
+            current: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Length of the file at `path` if it is unchanged since it was verified to have `digest`.
+    pub(super) fn unchanged_length(
+        &self,
+        path: &Path,
+        relative_path: &str,
+        digest: &AnyContentDigest,
+    ) -> Option<u64> {
+        let previous = self.previous.get(relative_path)?;
+        let metadata = std::fs::metadata(path).ok()?;
+        let entry = ManifestEntry::new(&metadata, digest)?;
+        if &entry != previous {
+            return None;
+        }
+        let size = entry.size;
+        self.current
+            .lock()
+            .unwrap()
+            .insert(relative_path.to_string(), entry);
+        Some(size)
+    }
+
+    /// Records that the file at `path` has just been verified to have `digest`.
+    pub(super) fn record(&self, path: &Path, relative_path: &str, digest: &AnyContentDigest) {
+        if self.key.is_none() {
+            return;
+        }
+        let Some(entry) = std::fs::metadata(path)
+            .ok()
+            .and_then(|metadata| ManifestEntry::new(&metadata, digest))
+        else {
+            return;
+        };
+        self.current
+            .lock()
+            .unwrap()
+            .insert(relative_path.to_string(), entry);
+    }
+
+    /// Replaces the manifest with the files verified in this run.
+    pub(super) fn save(&self) -> Result<()> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        let entries = self.current.lock().unwrap().clone();
+        let manifest = ManifestFile {
+            version: MANIFEST_VERSION,
+            mac: hex::encode(compute_mac(key, &entries)?.finalize().into_bytes()),
+            entries,
+        };
+
+        let mut part_path = self.path.as_os_str().to_owned();
+        part_path.push(".part");
+        let part_path = PathBuf::from(part_path);
+        let mut file = std::fs::File::create(&part_path)
+            .wrap_err_with(|| format!("Failed to create {}", part_path.display()))?;
+        file.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        file.sync_all()?;
+        std::fs::rename(&part_path, &self.path)
+            .wrap_err_with(|| format!("Failed to move {} into place", part_path.display()))?;
+        Ok(())
+    }
+}
+
+fn compute_mac(
+    key: &[u8; KEY_LENGTH],
+    entries: &BTreeMap<String, ManifestEntry>,
+) -> Result<ManifestMac> {
+    let mut mac = ManifestMac::new_from_slice(key).map_err(|e| eyre!("{}", e))?;
+    mac.update(&MANIFEST_VERSION.to_be_bytes());
+    mac.update(&serde_json::to_vec(entries)?);
+    Ok(mac)
+}
+
+fn read_manifest(path: &Path, key: &[u8; KEY_LENGTH]) -> Result<BTreeMap<String, ManifestEntry>> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let manifest: ManifestFile = serde_json::from_slice(&content)?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(eyre!("Unsupported manifest version {}", manifest.version));
+    }
+    let mac = hex::decode(&manifest.mac).wrap_err("Invalid manifest MAC")?;
+    compute_mac(key, &manifest.entries)?
+        .verify_slice(&mac)
+        .map_err(|_| eyre!("Manifest MAC does not match, it was modified or written elsewhere"))?;
+    Ok(manifest.entries)
+}
+
+/// Reads the manifest key, creating a random one readable only by the current user if there is
+/// none yet.
+fn load_or_create_key(path: &Path) -> Result<[u8; KEY_LENGTH]> {
+    match std::fs::read(path) {
+        Ok(content) => {
+            return content
+                .try_into()
+                .map_err(|_| eyre!("{} is not a manifest key", path.display()));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+    }
+
+    let mut key = [0u8; KEY_LENGTH];
+    getrandom::getrandom(&mut key).map_err(|e| eyre!("Failed to generate a key: {}", e))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(path) {
+        Ok(mut file) => {
+            file.write_all(&key)?;
+            file.sync_all()?;
+            Ok(key)
+        }
+        // Another process created the key in the meantime
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => load_or_create_key(path),
+        Err(e) => Err(e).wrap_err_with(|| format!("Failed to create {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debian_packaging::checksum::AnyChecksumType;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = temp_dir.path().join("output");
+        let key_path = temp_dir.path().join("manifest.key");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let package_path = output_dir.join("hello_2.10-3_amd64.deb");
+        std::fs::write(&package_path, b"hello").unwrap();
+        let digest = AnyContentDigest::from_hex_digest(
+            AnyChecksumType::Sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        )
+        .unwrap();
+
+        let manifest = VerificationManifest::open(&output_dir, Some(&key_path), true);
+        manifest.record(&package_path, "hello_2.10-3_amd64.deb", &digest);
+        manifest.save().unwrap();
+
+        let manifest = VerificationManifest::open(&output_dir, Some(&key_path), true);
+        assert_eq!(
+            manifest.unchanged_length(&package_path, "hello_2.10-3_amd64.deb", &digest),
+            Some(5)
+        );
+
+        // A different key means the manifest cannot be trusted
+        let other_key_path = temp_dir.path().join("other.key");
+        let manifest = VerificationManifest::open(&output_dir, Some(&other_key_path), true);
+        assert_eq!(
+            manifest.unchanged_length(&package_path, "hello_2.10-3_amd64.deb", &digest),
+            None
+        );
+    }
+}
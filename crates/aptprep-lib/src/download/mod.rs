@@ -1,6 +1,10 @@
 #[allow(clippy::module_inception)]
 mod download;
+mod manifest;
+mod progress;
 mod types;
 
 pub use download::download_and_check_all;
-pub use types::{DownloadAndCheckOptions, DownloadItem};
+pub use manifest::MANIFEST_FILE_NAME;
+pub use progress::{DownloadEvent, EventsFormat};
+pub use types::{BandwidthLimit, DownloadAndCheckOptions, DownloadItem, DownloadSource};
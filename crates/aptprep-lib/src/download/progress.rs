@@ -0,0 +1,306 @@
+use crate::utils::format_size;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often per-item progress events are emitted and the progress line is redrawn.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Format of the machine-readable download events written to standard output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventsFormat {
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl FromStr for EventsFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(format!("Unknown events format '{value}'. Expected jsonl")),
+        }
+    }
+}
+
+impl Display for EventsFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// What happened to a single download item.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DownloadEvent<'a> {
+    /// A transfer from `url` begins, continuing a partial file from `offset`
+    Start {
+        output: &'a Path,
+        url: String,
+        size: Option<u64>,
+        offset: u64,
+    },
+    /// `downloaded` bytes of the file are on disk
+    Progress {
+        output: &'a Path,
+        downloaded: u64,
+        size: Option<u64>,
+    },
+    /// The file is in place with the expected digest. `reused` files were already present.
+    Verified {
+        output: &'a Path,
+        size: u64,
+        reused: bool,
+    },
+    /// An attempt failed. Further attempts follow from the next mirror if `will_retry` is set.
+    Failed {
+        output: &'a Path,
+        url: Option<String>,
+        error: String,
+        will_retry: bool,
+    },
+}
+
+/// Collects the progress of all downloads for the progress line and the event stream.
+pub(super) struct ProgressReporter {
+    events: Option<EventsFormat>,
+    total_files: u64,
+    total_bytes: u64,
+    started: Instant,
+    finished_files: AtomicU64,
+    failed_files: AtomicU64,
+    finished_bytes: AtomicU64,
+    /// Bytes received over the network, for the throughput
+    transferred_bytes: AtomicU64,
+    /// Bytes on disk of the files being downloaded
+    in_flight: Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl ProgressReporter {
+    pub(super) fn new(events: Option<EventsFormat>, total_files: u64, total_bytes: u64) -> Self {
+        Self {
+            events,
+            total_files,
+            total_bytes,
+            started: Instant::now(),
+            finished_files: AtomicU64::new(0),
+            failed_files: AtomicU64::new(0),
+            finished_bytes: AtomicU64::new(0),
+            transferred_bytes: AtomicU64::new(0),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn start(&self, output: &Path, url: String, size: Option<u64>, offset: u64) {
+        self.set_in_flight(output, offset);
+        self.emit(&DownloadEvent::Start {
+            output,
+            url,
+            size,
+            offset,
+        });
+    }
+
+    /// Records `received` new bytes, bringing the file to `downloaded` bytes. Emits a progress
+    /// event when `report` is set.
+    pub(super) fn advance(
+        &self,
+        output: &Path,
+        received: u64,
+        downloaded: u64,
+        size: Option<u64>,
+        report: bool,
+    ) {
+        self.transferred_bytes
+            .fetch_add(received, Ordering::Relaxed);
+        self.set_in_flight(output, downloaded);
+        if report {
+            self.emit(&DownloadEvent::Progress {
+                output,
+                downloaded,
+                size,
+            });
+        }
+    }
+
+    pub(super) fn verified(&self, output: &Path, size: u64, reused: bool) {
+        self.in_flight.lock().unwrap().remove(output);
+        self.finished_files.fetch_add(1, Ordering::Relaxed);
+        self.finished_bytes.fetch_add(size, Ordering::Relaxed);
+        self.emit(&DownloadEvent::Verified {
+            output,
+            size,
+            reused,
+        });
+    }
+
+    pub(super) fn failed(
+        &self,
+        output: &Path,
+        url: Option<String>,
+        error: &eyre::Report,
+        will_retry: bool,
+    ) {
+        if !will_retry {
+            self.in_flight.lock().unwrap().remove(output);
+            self.failed_files.fetch_add(1, Ordering::Relaxed);
+        }
+        self.emit(&DownloadEvent::Failed {
+            output,
+            url,
+            error: format!("{:#}", error),
+            will_retry,
+        });
+    }
+
+    /// One-line summary of the overall progress: files, bytes, throughput and remaining time.
+    pub(super) fn status_line(&self) -> String {
+        let finished_files = self.finished_files.load(Ordering::Relaxed);
+        let failed_files = self.failed_files.load(Ordering::Relaxed);
+        let done_bytes = self.finished_bytes.load(Ordering::Relaxed)
+            + self.in_flight.lock().unwrap().values().sum::<u64>();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let throughput = if elapsed > 0.0 {
+            self.transferred_bytes.load(Ordering::Relaxed) as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        let mut line = format!(
+            "{}/{} files, {}/{}, {}/s",
+            finished_files,
+            self.total_files,
+            format_size(done_bytes),
+            format_size(self.total_bytes),
+            format_size(throughput as u64)
+        );
+        if failed_files > 0 {
+            line.push_str(&format!(", {} failed", failed_files));
+        }
+        let remaining_bytes = self.total_bytes.saturating_sub(done_bytes);
+        if throughput >= 1.0 && remaining_bytes > 0 {
+            line.push_str(&format!(
+                ", ETA {}",
+                format_duration(Duration::from_secs_f64(remaining_bytes as f64 / throughput))
+            ));
+        }
+        line
+    }
+
+    /// Redraws the progress line on standard error until the task is aborted.
+    pub(super) async fn draw(&self) {
+        let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K{}", self.status_line());
+            let _ = stderr.flush();
+        }
+    }
+
+    /// Replaces the progress line with the final status.
+    pub(super) fn finish_display(&self) {
+        eprintln!("\r\x1b[2K{}", self.status_line());
+    }
+
+    fn set_in_flight(&self, output: &Path, downloaded: u64) {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .insert(output.to_path_buf(), downloaded);
+    }
+
+    fn emit(&self, event: &DownloadEvent<'_>) {
+        if let Some(EventsFormat::Jsonl) = self.events {
+            let Ok(line) = serde_json::to_string(event) else {
+                return;
+            };
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", line);
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// Tracks when the last progress event of a single transfer was emitted.
+pub(super) struct ProgressTimer {
+    last: Instant,
+}
+
+impl ProgressTimer {
+    pub(super) fn new() -> Self {
+        Self {
+            last: Instant::now(),
+        }
+    }
+
+    /// Whether a progress event is due, restarting the interval if so.
+    pub(super) fn due(&mut self) -> bool {
+        if self.last.elapsed() >= PROGRESS_INTERVAL {
+            self.last = Instant::now();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization() {
+        let event = DownloadEvent::Verified {
+            output: Path::new("out/hello_2.10-3_amd64.deb"),
+            size: 56132,
+            reused: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"verified","output":"out/hello_2.10-3_amd64.deb","size":56132,"reused":false}"#
+        );
+    }
+
+    #[test]
+    fn test_status_line() {
+        let reporter = ProgressReporter::new(None, 2, 3 * 1024);
+        reporter.verified(Path::new("a.deb"), 1024, true);
+        reporter.start(
+            Path::new("b.deb"),
+            "https://example.com/b.deb".into(),
+            None,
+            512,
+        );
+
+        let line = reporter.status_line();
+        assert!(line.starts_with("1/2 files, 1.5 KiB/3.0 KiB, "), "{line}");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_duration(Duration::from_secs(7260)), "2h01m");
+    }
+}
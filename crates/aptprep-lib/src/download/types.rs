@@ -1,20 +1,118 @@
+use super::progress::EventsFormat;
+use crate::cache::PackageCache;
 use debian_packaging::checksum::AnyContentDigest;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug)]
 pub struct DownloadItem {
     pub base_url: String,
     pub rel_path: String,
+    pub mirrors: Vec<DownloadSource>, // Fallback locations of the same file, tried in order
     pub size: Option<u64>,
     pub digest: AnyContentDigest,
     pub output_path: Option<String>, // Optional custom output path, relative to output_dir
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadSource {
+    pub base_url: String,
+    pub rel_path: String,
+}
+
+impl DownloadSource {
+    /// Splits a download URL into the host part used to pick an operator and the path below it.
+    pub fn from_url(download_url: &str) -> Result<Self, url::ParseError> {
+        let url = reqwest::Url::parse(download_url)?;
+
+        let mut base_url = url.clone();
+        base_url.set_path("");
+        base_url.set_query(None);
+        base_url.set_fragment(None);
+
+        Ok(Self {
+            base_url: base_url.as_str().trim_end_matches('/').to_string(),
+            rel_path: url.path().to_string(),
+        })
+    }
+}
+
+impl DownloadItem {
+    /// Where the item is stored below `output_dir`.
+    pub fn output_path_in(&self, output_dir: &Path) -> PathBuf {
+        match &self.output_path {
+            Some(custom_path) => output_dir.join(custom_path),
+            None => output_dir.join(&self.rel_path),
+        }
+    }
+
+    /// Primary location followed by the mirrors.
+    pub fn sources(&self) -> impl Iterator<Item = DownloadSource> + '_ {
+        std::iter::once(DownloadSource {
+            base_url: self.base_url.clone(),
+            rel_path: self.rel_path.clone(),
+        })
+        .chain(self.mirrors.iter().cloned())
+    }
+}
+
+/// A bandwidth cap in bytes per second that allows bursts of up to `burst` bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BandwidthLimit {
+    pub bytes_per_second: NonZeroU32,
+    pub burst: NonZeroU32,
+}
+
+impl BandwidthLimit {
+    /// Smallest allowed burst. Chunks larger than the burst wait for the budget in several
+    /// portions, and HTTP response chunks can be several hundred KiB.
+    pub const MIN_BURST: u32 = 1024 * 1024;
+
+    /// Validates a rate and an optional burst, which defaults to one second worth of data but
+    /// at least [`Self::MIN_BURST`].
+    pub fn new(bytes_per_second: u64, burst: Option<u64>) -> Result<Self, String> {
+        let to_non_zero = |value: u64, name: &str| {
+            u32::try_from(value)
+                .ok()
+                .and_then(NonZeroU32::new)
+                .ok_or_else(|| format!("{name} must be between 1 byte and 4 GiB"))
+        };
+        let bytes_per_second = to_non_zero(bytes_per_second, "Bandwidth limit")?;
+        let burst = match burst {
+            Some(burst) if burst < u64::from(Self::MIN_BURST) => {
+                return Err("Burst size must be at least 1 MiB".to_string());
+            }
+            Some(burst) => to_non_zero(burst, "Burst size")?,
+            None => bytes_per_second.max(NonZeroU32::new(Self::MIN_BURST).unwrap()),
+        };
+        Ok(Self {
+            bytes_per_second,
+            burst,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct DownloadAndCheckOptions {
     pub max_concurrency_per_host: usize,
     pub max_retries: usize,
     pub download_parallelism: usize,
     pub checking_parallelism: usize,
+    /// Machine-readable events per item on standard output
+    pub events: Option<EventsFormat>,
+    /// Draw an overall progress line on standard error
+    pub progress: bool,
+    /// Cap on the combined bandwidth of all downloads
+    pub bandwidth_limit: Option<BandwidthLimit>,
+    /// Cap on the bandwidth used per host
+    pub bandwidth_limit_per_host: Option<BandwidthLimit>,
+    /// Shared cache the packages are downloaded into and linked from
+    pub cache: Option<PackageCache>,
+    /// Key that authenticates the verification manifest. Without one, every existing file is
+    /// hashed.
+    pub manifest_key_path: Option<PathBuf>,
+    /// Hash every existing file even if the manifest says it is unchanged
+    pub full_verify: bool,
 }
 
 impl Default for DownloadAndCheckOptions {
@@ -24,6 +122,30 @@ impl Default for DownloadAndCheckOptions {
             max_retries: 5,
             download_parallelism: 16,
             checking_parallelism: 128,
+            events: None,
+            progress: false,
+            bandwidth_limit: None,
+            bandwidth_limit_per_host: None,
+            cache: None,
+            manifest_key_path: None,
+            full_verify: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_limit_burst() {
+        let limit = BandwidthLimit::new(10 * 1024 * 1024, None).unwrap();
+        assert_eq!(limit.burst, limit.bytes_per_second);
+
+        let limit = BandwidthLimit::new(100 * 1024, None).unwrap();
+        assert_eq!(limit.burst.get(), BandwidthLimit::MIN_BURST);
+
+        assert!(BandwidthLimit::new(0, None).is_err());
+        assert!(BandwidthLimit::new(100 * 1024, Some(64 * 1024)).is_err());
+    }
+}
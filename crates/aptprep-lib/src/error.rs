@@ -21,6 +21,24 @@ pub enum AptPrepError {
     #[error("Lockfile validation failed: {details}")]
     LockfileValidation { details: String },
 
+    #[error("Lockfile {path} does not match the configuration")]
+    LockfileDrift { path: PathBuf },
+
+    #[error("Failed to read advisory data from {path}: {reason}")]
+    AdvisoryData { path: PathBuf, reason: String },
+
+    #[error("Found {count} vulnerabilities with severity {threshold} or higher")]
+    VulnerabilitiesFound { count: usize, threshold: String },
+
+    #[error(
+        "Bundle verification failed: {missing} missing, {corrupt} corrupt and {extra} extra files"
+    )]
+    BundleVerification {
+        missing: usize,
+        corrupt: usize,
+        extra: usize,
+    },
+
     #[error("CLI argument validation failed: {details}")]
     CliArgumentValidation { details: String },
 
@@ -37,6 +55,21 @@ pub enum AptPrepError {
     #[error("Download directory creation failed at {path}: {reason}")]
     DownloadDirectoryCreation { path: PathBuf, reason: String },
 
+    #[error("Source repository {repository} is misconfigured: {details}")]
+    RepositoryConfig { repository: String, details: String },
+
+    #[error("Snapshot repository {repository} is misconfigured: {details}")]
+    Snapshot { repository: String, details: String },
+
+    #[error("Release file of {distribution} is not fresh: {details}")]
+    ReleaseFreshness {
+        distribution: String,
+        details: String,
+    },
+
+    #[error("Signature verification failed for {location}: {reason}")]
+    Signature { location: String, reason: String },
+
     #[error("Repository access failed for {repository}: {reason}")]
     RepositoryAccess { repository: String, reason: String },
 
@@ -50,8 +83,12 @@ pub enum AptPrepError {
     #[error("Package validation failed for {package}: {details}")]
     PackageValidation { package: String, details: String },
 
-    #[error("Failed to hash configuration file {path}: {reason}")]
-    ConfigFileHash { path: PathBuf, reason: String },
+    #[error("Output path collision at {path}: {first} and {second} are different files")]
+    OutputPathCollision {
+        path: String,
+        first: String,
+        second: String,
+    },
 
     #[error("JSON serialization/deserialization error: {0}")]
     Json(#[from] serde_json::Error),
@@ -65,3 +102,19 @@ pub enum AptPrepError {
     #[error("Unexpected error: {0}")]
     Unexpected(#[from] eyre::Report),
 }
+
+impl AptPrepError {
+    /// Process exit code for the error. Outcomes that CI scripts need to tell apart from
+    /// ordinary failures get their own codes.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::LockfileDrift { .. } => 2,
+            Self::VulnerabilitiesFound { .. } => 3,
+            // The most serious problem decides when there are several
+            Self::BundleVerification { corrupt, .. } if *corrupt > 0 => 5,
+            Self::BundleVerification { missing, .. } if *missing > 0 => 4,
+            Self::BundleVerification { .. } => 6,
+            _ => 1,
+        }
+    }
+}
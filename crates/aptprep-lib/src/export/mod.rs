@@ -0,0 +1,3 @@
+mod sbom;
+
+pub use sbom::{SbomFormat, generate_sbom};
@@ -0,0 +1,574 @@
+use crate::error::AptPrepError;
+use crate::lockfile::{Lockfile, LockfilePackageEntry};
+use chrono::Utc;
+use debian_packaging::control::ControlFile;
+use reqwest::Url;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Format of an exported software bill of materials.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "cyclonedx" => Ok(Self::CycloneDx),
+            "spdx" => Ok(Self::Spdx),
+            _ => Err(format!(
+                "Unknown SBOM format '{value}'. Expected cyclonedx or spdx"
+            )),
+        }
+    }
+}
+
+impl Display for SbomFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CycloneDx => write!(f, "cyclonedx"),
+            Self::Spdx => write!(f, "spdx"),
+        }
+    }
+}
+
+/// Renders the packages of a lockfile as a JSON SBOM.
+pub fn generate_sbom(lockfile: &Lockfile, format: SbomFormat) -> Result<String, AptPrepError> {
+    let packages = lockfile
+        .packages
+        .iter()
+        .map(|(package_key, entry)| SbomPackage::new(lockfile, package_key, entry))
+        .collect::<Result<Vec<_>, _>>()?;
+    let document_id = document_uuid(lockfile)?;
+
+    let json = match format {
+        SbomFormat::CycloneDx => serde_json::to_string_pretty(&cyclonedx(&packages, &document_id))?,
+        SbomFormat::Spdx => serde_json::to_string_pretty(&spdx(&packages, &document_id))?,
+    };
+    Ok(json)
+}
+
+/// Package details shared by both formats.
+struct SbomPackage<'a> {
+    key: &'a str,
+    entry: &'a LockfilePackageEntry,
+    purl: String,
+    maintainer: Option<Maintainer>,
+    description: Option<String>,
+    homepage: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Maintainer {
+    name: String,
+    email: Option<String>,
+}
+
+impl<'a> SbomPackage<'a> {
+    fn new(
+        lockfile: &Lockfile,
+        key: &'a str,
+        entry: &'a LockfilePackageEntry,
+    ) -> Result<Self, AptPrepError> {
+        let control_file = ControlFile::parse_str(&entry.control_file)?;
+        let paragraph = control_file.paragraphs().next();
+        let field = |name: &str| paragraph.and_then(|paragraph| paragraph.field_str(name));
+
+        let source = entry
+            .source
+            .as_ref()
+            .and_then(|source| lockfile.sources.get(source));
+        let origin_url = source
+            .and_then(|source| source.repository_url.as_deref())
+            .unwrap_or(&entry.download_url);
+
+        Ok(Self {
+            key,
+            entry,
+            purl: package_url(
+                &purl_namespace(origin_url),
+                entry,
+                source.and_then(|source| source.suite.as_deref()),
+            ),
+            maintainer: field("Maintainer").map(parse_maintainer),
+            description: field("Description")
+                .and_then(|description| description.lines().next())
+                .map(str::to_string),
+            homepage: field("Homepage").map(str::to_string),
+        })
+    }
+}
+
+/// Builds a `pkg:deb` package URL as described by the purl specification.
+fn package_url(namespace: &str, entry: &LockfilePackageEntry, distro: Option<&str>) -> String {
+    let mut purl = format!(
+        "pkg:deb/{}/{}@{}?arch={}",
+        encode_purl_component(namespace),
+        encode_purl_component(&entry.name),
+        encode_purl_component(&entry.version),
+        encode_purl_component(&entry.architecture)
+    );
+    if let Some(distro) = distro {
+        purl.push_str("&distro=");
+        purl.push_str(&encode_purl_component(distro));
+    }
+    purl
+}
+
+fn encode_purl_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Vendor namespace of a repository: `debian` and `ubuntu` for their archives, otherwise the
+/// second-level domain of the host, e.g. `nvidia` or `docker`.
+fn purl_namespace(url: &str) -> String {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .unwrap_or_default();
+    if host == "ubuntu.com" || host.ends_with(".ubuntu.com") {
+        "ubuntu".to_string()
+    } else if host == "debian.org" || host.ends_with(".debian.org") {
+        "debian".to_string()
+    } else {
+        host.rsplit('.')
+            .nth(1)
+            .filter(|label| !label.is_empty())
+            .unwrap_or("deb")
+            .to_string()
+    }
+}
+
+/// Splits a `Name <email>` maintainer field.
+fn parse_maintainer(value: &str) -> Maintainer {
+    match value.split_once('<') {
+        Some((name, email)) => Maintainer {
+            name: name.trim().to_string(),
+            email: Some(email.trim_end().trim_end_matches('>').trim().to_string()),
+        },
+        None => Maintainer {
+            name: value.trim().to_string(),
+            email: None,
+        },
+    }
+}
+
+/// Stable document identifier derived from the lockfile contents, formatted as a UUID.
+fn document_uuid(lockfile: &Lockfile) -> Result<String, AptPrepError> {
+    let digest = Sha256::digest(serde_json::to_vec(lockfile)?);
+    let mut bytes: [u8; 16] = digest[..16].try_into().expect("digest is 32 bytes long");
+    // Mark as a name-based (version 5 style) RFC 4122 UUID
+    bytes[6] = (bytes[6] & 0x0f) | 0x50;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+fn tool_version() -> String {
+    format!("aptprep-{}", env!("CARGO_PKG_VERSION"))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxBom<'a> {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    serial_number: String,
+    version: u32,
+    metadata: CycloneDxMetadata,
+    components: Vec<CycloneDxComponent<'a>>,
+    dependencies: Vec<CycloneDxDependency<'a>>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxMetadata {
+    timestamp: String,
+    tools: CycloneDxTools,
+}
+
+#[derive(Serialize)]
+struct CycloneDxTools {
+    components: Vec<CycloneDxTool>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxTool {
+    #[serde(rename = "type")]
+    component_type: &'static str,
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+struct CycloneDxComponent<'a> {
+    #[serde(rename = "type")]
+    component_type: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: &'a str,
+    name: &'a str,
+    version: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supplier: Option<CycloneDxSupplier<'a>>,
+    purl: &'a str,
+    hashes: Vec<CycloneDxHash<'a>>,
+    #[serde(rename = "externalReferences", skip_serializing_if = "Vec::is_empty")]
+    external_references: Vec<CycloneDxExternalReference<'a>>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxSupplier<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contact: Vec<CycloneDxContact<'a>>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxContact<'a> {
+    name: &'a str,
+    email: &'a str,
+}
+
+#[derive(Serialize)]
+struct CycloneDxHash<'a> {
+    alg: &'static str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct CycloneDxExternalReference<'a> {
+    #[serde(rename = "type")]
+    reference_type: &'static str,
+    url: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxDependency<'a> {
+    #[serde(rename = "ref")]
+    reference: &'a str,
+    depends_on: &'a [String],
+}
+
+fn cyclonedx<'a>(packages: &'a [SbomPackage<'a>], document_id: &str) -> CycloneDxBom<'a> {
+    CycloneDxBom {
+        bom_format: "CycloneDX",
+        spec_version: "1.5",
+        serial_number: format!("urn:uuid:{}", document_id),
+        version: 1,
+        metadata: CycloneDxMetadata {
+            timestamp: Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            tools: CycloneDxTools {
+                components: vec![CycloneDxTool {
+                    component_type: "application",
+                    name: "aptprep",
+                    version: env!("CARGO_PKG_VERSION"),
+                }],
+            },
+        },
+        components: packages
+            .iter()
+            .map(|package| CycloneDxComponent {
+                component_type: "library",
+                bom_ref: package.key,
+                name: &package.entry.name,
+                version: &package.entry.version,
+                description: package.description.as_deref(),
+                supplier: package
+                    .maintainer
+                    .as_ref()
+                    .map(|maintainer| CycloneDxSupplier {
+                        name: &maintainer.name,
+                        contact: maintainer
+                            .email
+                            .as_deref()
+                            .map(|email| CycloneDxContact {
+                                name: &maintainer.name,
+                                email,
+                            })
+                            .into_iter()
+                            .collect(),
+                    }),
+                purl: &package.purl,
+                hashes: digest_algorithm(&package.entry.digest.algorithm)
+                    .map(|(alg, _)| CycloneDxHash {
+                        alg,
+                        content: &package.entry.digest.value,
+                    })
+                    .into_iter()
+                    .collect(),
+                external_references: std::iter::once(CycloneDxExternalReference {
+                    reference_type: "distribution",
+                    url: &package.entry.download_url,
+                })
+                .chain(
+                    package
+                        .homepage
+                        .as_deref()
+                        .map(|url| CycloneDxExternalReference {
+                            reference_type: "website",
+                            url,
+                        }),
+                )
+                .collect(),
+            })
+            .collect(),
+        dependencies: packages
+            .iter()
+            .map(|package| CycloneDxDependency {
+                reference: package.key,
+                depends_on: &package.entry.dependencies,
+            })
+            .collect(),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxDocument<'a> {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: &'static str,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage<'a>>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage<'a> {
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    name: &'a str,
+    version_info: &'a str,
+    supplier: String,
+    download_location: &'a str,
+    files_analyzed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    checksums: Vec<SpdxChecksum<'a>>,
+    license_concluded: &'static str,
+    license_declared: &'static str,
+    copyright_text: &'static str,
+    external_refs: Vec<SpdxExternalRef<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum<'a> {
+    algorithm: &'static str,
+    checksum_value: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef<'a> {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+fn spdx_package_id(package_key: &str) -> String {
+    // SPDX identifiers only allow letters, digits, `.` and `-`
+    format!("SPDXRef-Package-{}", package_key.replace('_', "-"))
+}
+
+fn spdx<'a>(packages: &'a [SbomPackage<'a>], document_id: &str) -> SpdxDocument<'a> {
+    let mut relationships = Vec::new();
+    for package in packages {
+        relationships.push(SpdxRelationship {
+            spdx_element_id: "SPDXRef-DOCUMENT".to_string(),
+            relationship_type: "DESCRIBES",
+            related_spdx_element: spdx_package_id(package.key),
+        });
+        for dependency in &package.entry.dependencies {
+            relationships.push(SpdxRelationship {
+                spdx_element_id: spdx_package_id(package.key),
+                relationship_type: "DEPENDS_ON",
+                related_spdx_element: spdx_package_id(dependency),
+            });
+        }
+    }
+
+    SpdxDocument {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: "aptprep-lockfile",
+        document_namespace: format!("https://spdx.org/spdxdocs/aptprep-{}", document_id),
+        creation_info: SpdxCreationInfo {
+            created: Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            creators: vec![format!("Tool: {}", tool_version())],
+        },
+        packages: packages
+            .iter()
+            .map(|package| SpdxPackage {
+                spdx_id: spdx_package_id(package.key),
+                name: &package.entry.name,
+                version_info: &package.entry.version,
+                // Debian maintainers are often teams, so suppliers are listed as organizations
+                supplier: match &package.maintainer {
+                    Some(Maintainer {
+                        name,
+                        email: Some(email),
+                    }) => format!("Organization: {} ({})", name, email),
+                    Some(Maintainer { name, email: None }) => format!("Organization: {}", name),
+                    None => "NOASSERTION".to_string(),
+                },
+                download_location: &package.entry.download_url,
+                files_analyzed: false,
+                homepage: package.homepage.as_deref(),
+                summary: package.description.as_deref(),
+                checksums: digest_algorithm(&package.entry.digest.algorithm)
+                    .map(|(_, algorithm)| SpdxChecksum {
+                        algorithm,
+                        checksum_value: &package.entry.digest.value,
+                    })
+                    .into_iter()
+                    .collect(),
+                license_concluded: "NOASSERTION",
+                license_declared: "NOASSERTION",
+                copyright_text: "NOASSERTION",
+                external_refs: vec![SpdxExternalRef {
+                    reference_category: "PACKAGE-MANAGER",
+                    reference_type: "purl",
+                    reference_locator: &package.purl,
+                }],
+            })
+            .collect(),
+        relationships,
+    }
+}
+
+/// CycloneDX and SPDX names of a lockfile digest algorithm.
+fn digest_algorithm(algorithm: &str) -> Option<(&'static str, &'static str)> {
+    match algorithm {
+        "MD5Sum" => Some(("MD5", "MD5")),
+        "SHA1" => Some(("SHA-1", "SHA1")),
+        "SHA256" => Some(("SHA-256", "SHA256")),
+        "SHA384" => Some(("SHA-384", "SHA384")),
+        "SHA512" => Some(("SHA-512", "SHA512")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder};
+
+    fn entry(name: &str, version: &str) -> PackageBuilder {
+        PackageBuilder::new("amd64", name, version)
+            .size(1)
+            .digest("ab")
+            .control_field("Maintainer", "Jane Doe <jane@example.com>")
+    }
+
+    #[test]
+    fn test_package_url_encodes_epochs() {
+        assert_eq!(
+            package_url(
+                "debian",
+                &entry("libc6", "1:2.36-9").build(),
+                Some("bookworm")
+            ),
+            "pkg:deb/debian/libc6@1%3A2.36-9?arch=amd64&distro=bookworm"
+        );
+    }
+
+    #[test]
+    fn test_purl_namespace_from_repository_host() {
+        assert_eq!(
+            purl_namespace("https://snapshot.ubuntu.com/ubuntu"),
+            "ubuntu"
+        );
+        assert_eq!(purl_namespace("http://deb.debian.org/debian"), "debian");
+        assert_eq!(
+            purl_namespace("https://developer.download.nvidia.com/compute/cuda/repos/"),
+            "nvidia"
+        );
+        assert_eq!(
+            purl_namespace("https://download.docker.com/linux/ubuntu"),
+            "docker"
+        );
+    }
+
+    #[test]
+    fn test_parse_maintainer() {
+        assert_eq!(
+            parse_maintainer("Jane Doe <jane@example.com>"),
+            Maintainer {
+                name: "Jane Doe".to_string(),
+                email: Some("jane@example.com".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_sbom_lists_dependencies() {
+        let lockfile = LockfileBuilder::new(&["a"])
+            .package(entry("a", "1.0").dependencies(&["amd64_b_1_0"]))
+            .package(entry("b", "1.0"))
+            .build();
+
+        let cyclonedx: serde_json::Value =
+            serde_json::from_str(&generate_sbom(&lockfile, SbomFormat::CycloneDx).unwrap())
+                .unwrap();
+        assert_eq!(cyclonedx["components"].as_array().unwrap().len(), 2);
+        assert_eq!(cyclonedx["dependencies"][0]["dependsOn"][0], "amd64_b_1_0");
+        assert_eq!(cyclonedx["components"][0]["supplier"]["name"], "Jane Doe");
+
+        let spdx: serde_json::Value =
+            serde_json::from_str(&generate_sbom(&lockfile, SbomFormat::Spdx).unwrap()).unwrap();
+        assert!(
+            spdx["relationships"].as_array().unwrap().iter().any(
+                |relationship| relationship["relationshipType"] == "DEPENDS_ON"
+                    && relationship["relatedSpdxElement"] == "SPDXRef-Package-amd64-b-1-0"
+            )
+        );
+    }
+}
@@ -1,8 +1,12 @@
+pub mod audit;
+pub mod cache;
 pub mod cli;
 pub mod config;
 pub mod dependency;
 pub mod download;
 pub mod error;
+pub mod export;
+pub mod licenses;
 pub mod lockfile;
 pub mod output;
 pub mod repository;
@@ -0,0 +1,168 @@
+use flate2::read::GzDecoder;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use xz2::read::XzDecoder;
+
+/// What a package ships in place of `usr/share/doc/<package>/copyright`.
+#[derive(Debug, PartialEq)]
+pub(super) enum DocCopyright {
+    /// Contents of the copyright file
+    File(String),
+    /// The documentation directory is a symlink to that of another package
+    LinkedTo(String),
+    Missing,
+}
+
+/// Reads the copyright file of `package_name` from the data member of a `.deb` archive.
+pub(super) fn read_copyright(deb: impl Read, package_name: &str) -> io::Result<DocCopyright> {
+    let mut archive = ar::Archive::new(deb);
+    while let Some(entry) = archive.next_entry() {
+        let entry = entry?;
+        let identifier = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+        let Some(compression) = identifier.strip_prefix("data.tar") else {
+            continue;
+        };
+        let data: Box<dyn Read + '_> = match compression {
+            "" => Box::new(entry),
+            ".gz" => Box::new(GzDecoder::new(entry)),
+            ".xz" => Box::new(XzDecoder::new(entry)),
+            ".zst" => Box::new(zstd::stream::read::Decoder::new(entry)?),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported data member {identifier}"),
+                ));
+            }
+        };
+        return find_copyright(data, package_name);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "archive has no data.tar member",
+    ))
+}
+
+fn find_copyright(data: impl Read, package_name: &str) -> io::Result<DocCopyright> {
+    let doc_dir = Path::new("usr/share/doc").join(package_name);
+    let copyright_path = doc_dir.join("copyright");
+
+    let mut archive = tar::Archive::new(data);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = normalize_member_path(&entry.path()?);
+        let entry_type = entry.header().entry_type();
+
+        if path == copyright_path && entry_type.is_file() {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            return Ok(DocCopyright::File(
+                String::from_utf8_lossy(&contents).into_owned(),
+            ));
+        }
+        if path == doc_dir && entry_type.is_symlink() {
+            let target = entry.link_name()?.and_then(|target| {
+                target
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            });
+            if let Some(target) = target {
+                return Ok(DocCopyright::LinkedTo(target));
+            }
+        }
+    }
+    Ok(DocCopyright::Missing)
+}
+
+/// Strips the leading `./` that dpkg-deb puts in front of member paths.
+fn normalize_member_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir | Component::RootDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::{Cursor, Write};
+    use xz2::write::XzEncoder;
+
+    fn data_tar(build: impl FnOnce(&mut tar::Builder<Vec<u8>>)) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        build(&mut builder);
+        builder.into_inner().unwrap()
+    }
+
+    fn add_file(builder: &mut tar::Builder<Vec<u8>>, path: &str, contents: &str) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, path, contents.as_bytes())
+            .unwrap();
+    }
+
+    fn deb(data_member: &str, data: Vec<u8>) -> Vec<u8> {
+        let mut builder = ar::Builder::new(Vec::new());
+        for (identifier, contents) in [
+            ("debian-binary", b"2.0\n".to_vec()),
+            ("control.tar.gz", Vec::new()),
+            (data_member, data),
+        ] {
+            let header = ar::Header::new(identifier.as_bytes().to_vec(), contents.len() as u64);
+            builder.append(&header, contents.as_slice()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn hello_data() -> Vec<u8> {
+        data_tar(|builder| {
+            add_file(builder, "./usr/bin/hello", "binary");
+            add_file(builder, "./usr/share/doc/hello/copyright", "Copyright text");
+        })
+    }
+
+    #[test]
+    fn test_reads_copyright_from_compressed_data_members() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&hello_data()).unwrap();
+        let mut xz = XzEncoder::new(Vec::new(), 6);
+        xz.write_all(&hello_data()).unwrap();
+        let zstd = zstd::stream::encode_all(hello_data().as_slice(), 0).unwrap();
+
+        for (member, data) in [
+            ("data.tar", hello_data()),
+            ("data.tar.gz", gzip.finish().unwrap()),
+            ("data.tar.xz", xz.finish().unwrap()),
+            ("data.tar.zst", zstd),
+        ] {
+            let copyright = read_copyright(Cursor::new(deb(member, data)), "hello").unwrap();
+            assert_eq!(copyright, DocCopyright::File("Copyright text".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_symlinked_doc_directory() {
+        let data = data_tar(|builder| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder
+                .append_link(&mut header, "./usr/share/doc/libhello-dev", "libhello1")
+                .unwrap();
+        });
+        let copyright = read_copyright(Cursor::new(deb("data.tar", data)), "libhello-dev");
+        assert_eq!(
+            copyright.unwrap(),
+            DocCopyright::LinkedTo("libhello1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_copyright() {
+        let copyright = read_copyright(Cursor::new(deb("data.tar", hello_data())), "other");
+        assert_eq!(copyright.unwrap(), DocCopyright::Missing);
+    }
+}
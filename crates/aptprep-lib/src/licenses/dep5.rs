@@ -0,0 +1,349 @@
+use std::collections::BTreeSet;
+
+/// A machine-readable copyright file as described by DEP-5.
+#[derive(Debug, PartialEq)]
+pub(super) struct Dep5Copyright {
+    /// `License` of the header paragraph
+    pub license: Option<String>,
+    pub files: Vec<FilesParagraph>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct FilesParagraph {
+    pub files: Vec<String>,
+    pub copyright: Option<String>,
+    /// First line of the `License` field, i.e. the license short names
+    pub license: String,
+}
+
+type Paragraph = Vec<(String, String)>;
+
+/// Parses a copyright file, returning `None` if it is not in the machine-readable format.
+pub(super) fn parse_dep5(text: &str) -> Option<Dep5Copyright> {
+    let paragraphs = paragraphs(text);
+    let header = paragraphs.first()?;
+    let format = field(header, "Format")?;
+    if !(format.contains("copyright-format") || format.contains("dep5")) {
+        return None;
+    }
+
+    let files = paragraphs
+        .iter()
+        .skip(1)
+        .filter_map(|paragraph| {
+            let files = field(paragraph, "Files")?;
+            let license = license_names(field(paragraph, "License")?)?;
+            Some(FilesParagraph {
+                files: files.split_whitespace().map(str::to_string).collect(),
+                copyright: field(paragraph, "Copyright").map(str::to_string),
+                license,
+            })
+        })
+        .collect();
+
+    Some(Dep5Copyright {
+        license: field(header, "License").and_then(license_names),
+        files,
+    })
+}
+
+/// Splits a copyright file into paragraphs of fields. Copyright files are written by hand,
+/// so this is more lenient than a control file parser: comments and stray lines are skipped.
+fn paragraphs(text: &str) -> Vec<Paragraph> {
+    let mut paragraphs = Vec::new();
+    let mut fields: Paragraph = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !fields.is_empty() {
+                paragraphs.push(std::mem::take(&mut fields));
+            }
+        } else if line.starts_with('#') {
+            continue;
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    if !fields.is_empty() {
+        paragraphs.push(fields);
+    }
+    paragraphs
+}
+
+fn field<'a>(paragraph: &'a Paragraph, name: &str) -> Option<&'a str> {
+    paragraph
+        .iter()
+        .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn license_names(license: &str) -> Option<String> {
+    license
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|names| !names.is_empty())
+        .map(str::to_string)
+}
+
+/// Converts a DEP-5 license specification such as `GPL-2+ or Artistic, and BSD-3-clause` into
+/// an SPDX license expression. Short names without an SPDX identifier become `LicenseRef-`
+/// identifiers.
+pub(super) fn to_spdx_expression(license: &str) -> String {
+    // Commas separate groups with a lower precedence than "and" and "or"
+    let groups = license
+        .split(',')
+        .map(|group| {
+            let group = group.trim();
+            let group = group
+                .get(..4)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("and "))
+                .map_or(group, |_| &group[4..]);
+            convert_group(group)
+        })
+        .filter(|group| !group.is_empty())
+        .collect::<Vec<_>>();
+    join_conjunction(groups, false)
+}
+
+/// Joins expressions with `AND`, parenthesizing those that contain `OR`. With `dedup`, the
+/// expressions are also sorted and deduplicated.
+pub(super) fn join_conjunction(expressions: Vec<String>, dedup: bool) -> String {
+    let expressions = if dedup {
+        expressions
+            .into_iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    } else {
+        expressions
+    };
+    if expressions.len() == 1 {
+        return expressions.into_iter().next().unwrap_or_default();
+    }
+    expressions
+        .iter()
+        .map(|expression| {
+            if expression.contains(" OR ") {
+                format!("({expression})")
+            } else {
+                expression.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn convert_group(group: &str) -> String {
+    let mut output = Vec::new();
+    let mut tokens = group.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        if token.eq_ignore_ascii_case("or") {
+            output.push("OR".to_string());
+        } else if token.eq_ignore_ascii_case("and") {
+            output.push("AND".to_string());
+        } else if tokens
+            .peek()
+            .is_some_and(|next| next.eq_ignore_ascii_case("with"))
+        {
+            tokens.next();
+            let exception = tokens
+                .by_ref()
+                .take_while(|word| !word.eq_ignore_ascii_case("exception"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            output.push(license_with_exception(token, &exception));
+        } else {
+            output.push(spdx_license_id(token));
+        }
+    }
+    output.join(" ")
+}
+
+/// DEP-5 short names that differ from, or match case-insensitively, an SPDX identifier.
+/// Versioned GNU licenses are handled separately.
+const SPDX_LICENSE_IDS: &[(&str, &str)] = &[
+    ("apache-2.0", "Apache-2.0"),
+    ("artistic", "Artistic-1.0"),
+    ("artistic-1.0", "Artistic-1.0"),
+    ("artistic-2.0", "Artistic-2.0"),
+    ("boost-1.0", "BSL-1.0"),
+    ("bsd-2-clause", "BSD-2-Clause"),
+    ("bsd-3-clause", "BSD-3-Clause"),
+    ("bsd-4-clause", "BSD-4-Clause"),
+    ("bsl-1.0", "BSL-1.0"),
+    ("cc-by-3.0", "CC-BY-3.0"),
+    ("cc-by-4.0", "CC-BY-4.0"),
+    ("cc-by-sa-3.0", "CC-BY-SA-3.0"),
+    ("cc-by-sa-4.0", "CC-BY-SA-4.0"),
+    ("cc0", "CC0-1.0"),
+    ("cc0-1.0", "CC0-1.0"),
+    ("curl", "curl"),
+    ("epl-1.0", "EPL-1.0"),
+    ("epl-2.0", "EPL-2.0"),
+    ("expat", "MIT"),
+    ("ftl", "FTL"),
+    ("isc", "ISC"),
+    ("mit", "MIT"),
+    ("mpl-1.1", "MPL-1.1"),
+    ("mpl-2.0", "MPL-2.0"),
+    ("ofl-1.1", "OFL-1.1"),
+    ("openssl", "OpenSSL"),
+    ("python-2.0", "Python-2.0"),
+    ("sil-ofl-1.1", "OFL-1.1"),
+    ("unicode-dfs-2016", "Unicode-DFS-2016"),
+    ("unlicense", "Unlicense"),
+    ("wtfpl", "WTFPL"),
+    ("x11", "X11"),
+    ("zlib", "Zlib"),
+    ("zpl-2.1", "ZPL-2.1"),
+];
+
+const GNU_LICENSES: &[&str] = &["AGPL", "GFDL", "GPL", "LGPL"];
+
+const SPDX_EXCEPTION_IDS: &[(&str, &str)] = &[
+    ("autoconf", "Autoconf-exception-2.0"),
+    ("bison", "Bison-exception-2.2"),
+    ("classpath", "Classpath-exception-2.0"),
+    ("font", "Font-exception-2.0"),
+    ("gcc-3.1", "GCC-exception-3.1"),
+    ("gcc-exception-3.1", "GCC-exception-3.1"),
+    ("libtool", "Libtool-exception"),
+];
+
+fn spdx_license_id(name: &str) -> String {
+    let (base, or_later) = match name.strip_suffix('+') {
+        Some(base) => (base, true),
+        None => (name, false),
+    };
+
+    let upper = base.to_ascii_uppercase();
+    for family in GNU_LICENSES {
+        let version = upper
+            .strip_prefix(family)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .filter(|version| {
+                !version.is_empty() && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+            });
+        if let Some(version) = version {
+            let version = if version.contains('.') {
+                version.to_string()
+            } else {
+                format!("{version}.0")
+            };
+            let suffix = if or_later { "or-later" } else { "only" };
+            return format!("{family}-{version}-{suffix}");
+        }
+    }
+
+    let lower = base.to_ascii_lowercase();
+    match SPDX_LICENSE_IDS.iter().find(|(dep5, _)| *dep5 == lower) {
+        Some((_, spdx)) if or_later => format!("{spdx}+"),
+        Some((_, spdx)) => spdx.to_string(),
+        None => license_ref(name),
+    }
+}
+
+fn license_with_exception(name: &str, exception: &str) -> String {
+    let lower = exception.to_ascii_lowercase();
+    match SPDX_EXCEPTION_IDS.iter().find(|(dep5, _)| *dep5 == lower) {
+        Some((_, spdx)) => format!("{} WITH {}", spdx_license_id(name), spdx),
+        // SPDX 2.3 only allows listed exceptions after WITH
+        None => format!(
+            "{}-with-{}-exception",
+            license_ref(name),
+            sanitize(exception)
+        ),
+    }
+}
+
+fn license_ref(name: &str) -> String {
+    match name.strip_suffix('+') {
+        Some(base) => format!("LicenseRef-{}-or-later", sanitize(base)),
+        None => format!("LicenseRef-{}", sanitize(name)),
+    }
+}
+
+/// Keeps the characters allowed in SPDX identifiers.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spdx_expressions() {
+        for (dep5, spdx) in [
+            ("GPL-2+", "GPL-2.0-or-later"),
+            ("LGPL-2.1", "LGPL-2.1-only"),
+            ("Expat", "MIT"),
+            ("BSD-3-clause", "BSD-3-Clause"),
+            ("MPL-1.1+", "MPL-1.1+"),
+            ("public-domain", "LicenseRef-public-domain"),
+            ("GPL-2+ or Artistic", "GPL-2.0-or-later OR Artistic-1.0"),
+            (
+                "GPL-2+ or Artistic, and BSD-3-clause",
+                "(GPL-2.0-or-later OR Artistic-1.0) AND BSD-3-Clause",
+            ),
+            (
+                "GPL-3+ with Font exception",
+                "GPL-3.0-or-later WITH Font-exception-2.0",
+            ),
+            (
+                "GPL-2+ with OpenSSL exception",
+                "LicenseRef-GPL-2-or-later-with-OpenSSL-exception",
+            ),
+        ] {
+            assert_eq!(to_spdx_expression(dep5), spdx, "{dep5}");
+        }
+    }
+
+    #[test]
+    fn test_parse_dep5() {
+        let copyright = parse_dep5(
+            "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/\n\
+             Upstream-Name: hello\n\
+             \n\
+             Files: *\n\
+             Copyright: 1992-2022 Free Software Foundation, Inc.\n\
+             License: GPL-3+\n\
+             \n\
+             Files: debian/*\n\
+             \x20     debian/patches/*\n\
+             Copyright: 2020 Jane Doe\n\
+             License: GPL-3+ or Expat\n\
+             \n\
+             License: GPL-3+\n\
+             \x20This program is free software.\n",
+        )
+        .unwrap();
+
+        assert_eq!(copyright.license, None);
+        assert_eq!(copyright.files.len(), 2);
+        assert_eq!(copyright.files[1].files, ["debian/*", "debian/patches/*"]);
+        assert_eq!(copyright.files[1].license, "GPL-3+ or Expat");
+    }
+
+    #[test]
+    fn test_free_form_copyright_is_not_dep5() {
+        assert_eq!(
+            parse_dep5("This package was debianized by someone.\n\nLicense: GPL\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,248 @@
+mod deb;
+mod dep5;
+
+use crate::config::OutputLayout;
+use crate::error::AptPrepError;
+use crate::lockfile::{Lockfile, LockfilePackageEntry};
+use deb::{DocCopyright, read_copyright};
+use dep5::{join_conjunction, parse_dep5, to_spdx_expression};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+/// License information gathered from the copyright files of downloaded packages.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LicenseReport {
+    /// Licenses by lockfile package key
+    pub packages: BTreeMap<String, PackageLicense>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyrightStatus {
+    /// Machine-readable (DEP-5) copyright file
+    MachineReadable,
+    /// Free-form copyright file from which no license expression could be derived
+    Unstructured,
+    /// The package does not ship a copyright file
+    Missing,
+    /// The package file is not in the package directory
+    NotDownloaded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageLicense {
+    pub name: String,
+    pub version: String,
+    pub architecture: String,
+    pub status: CopyrightStatus,
+    /// SPDX license expression covering all files of the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Licenses of the individual `Files` paragraphs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FilesLicense>,
+    /// Key of the package whose copyright file applies, for packages whose documentation
+    /// directory is a symlink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copyright_from: Option<String>,
+    /// Full text of the copyright file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copyright: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilesLicense {
+    pub files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copyright: Option<String>,
+    /// License short names as written in the copyright file
+    pub dep5_license: String,
+    /// The same license as an SPDX expression
+    pub license: String,
+}
+
+impl PackageLicense {
+    fn new(entry: &LockfilePackageEntry, status: CopyrightStatus) -> Self {
+        Self {
+            name: entry.name.clone(),
+            version: entry.version.clone(),
+            architecture: entry.architecture.clone(),
+            status,
+            license: None,
+            files: Vec::new(),
+            copyright_from: None,
+            copyright: None,
+        }
+    }
+
+    fn from_copyright(entry: &LockfilePackageEntry, copyright: String) -> Self {
+        let Some(dep5) = parse_dep5(&copyright) else {
+            return Self {
+                copyright: Some(copyright),
+                ..Self::new(entry, CopyrightStatus::Unstructured)
+            };
+        };
+
+        let files = dep5
+            .files
+            .into_iter()
+            .map(|paragraph| FilesLicense {
+                license: to_spdx_expression(&paragraph.license),
+                files: paragraph.files,
+                copyright: paragraph.copyright,
+                dep5_license: paragraph.license,
+            })
+            .collect::<Vec<_>>();
+
+        let mut expressions = files
+            .iter()
+            .map(|files| files.license.clone())
+            .collect::<Vec<_>>();
+        if expressions.is_empty() {
+            expressions.extend(dep5.license.as_deref().map(to_spdx_expression));
+        }
+
+        Self {
+            license: (!expressions.is_empty()).then(|| join_conjunction(expressions, true)),
+            files,
+            copyright: Some(copyright),
+            ..Self::new(entry, CopyrightStatus::MachineReadable)
+        }
+    }
+}
+
+impl LicenseReport {
+    /// Number of packages with each copyright status.
+    pub fn status_counts(&self) -> BTreeMap<CopyrightStatus, usize> {
+        let mut counts = BTreeMap::new();
+        for package in self.packages.values() {
+            *counts.entry(package.status).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Reads the copyright file of every lockfile package from the `.deb` files in `package_dir`,
+/// arranged by `layout`.
+pub fn collect_licenses(
+    lockfile: &Lockfile,
+    package_dir: &Path,
+    layout: OutputLayout,
+) -> Result<LicenseReport, AptPrepError> {
+    let package_paths = lockfile.package_paths(layout)?;
+    let mut packages = BTreeMap::new();
+    let mut doc_links = Vec::new();
+
+    for (package_key, entry) in &lockfile.packages {
+        let deb_path = package_dir.join(&package_paths[package_key]);
+
+        let copyright = match File::open(&deb_path) {
+            Ok(file) => read_copyright(BufReader::new(file), &entry.name).map_err(|e| {
+                AptPrepError::PackageValidation {
+                    package: package_key.clone(),
+                    details: format!("Failed to read {}: {}", deb_path.display(), e),
+                }
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                tracing::warn!("{} has not been downloaded", deb_path.display());
+                packages.insert(
+                    package_key.clone(),
+                    PackageLicense::new(entry, CopyrightStatus::NotDownloaded),
+                );
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let package = match copyright {
+            DocCopyright::File(copyright) => PackageLicense::from_copyright(entry, copyright),
+            DocCopyright::LinkedTo(target) => {
+                doc_links.push((package_key, target));
+                PackageLicense::new(entry, CopyrightStatus::Missing)
+            }
+            DocCopyright::Missing => PackageLicense::new(entry, CopyrightStatus::Missing),
+        };
+        packages.insert(package_key.clone(), package);
+    }
+
+    // Symlinked documentation directories point at a package of the same architecture,
+    // usually one the package depends on
+    for (package_key, target) in doc_links {
+        let architecture = &lockfile.packages[package_key].architecture;
+        let target_key = lockfile
+            .package_groups
+            .get(&target)
+            .into_iter()
+            .flatten()
+            .find(|target_key| {
+                lockfile.packages.get(*target_key).is_some_and(|target| {
+                    &target.architecture == architecture || target.architecture == "all"
+                })
+            });
+        let Some(target_license) = target_key.and_then(|target_key| packages.get(target_key))
+        else {
+            tracing::warn!(
+                "{} links its documentation to {}, which is not in the lockfile",
+                package_key,
+                target
+            );
+            continue;
+        };
+
+        let target_license = target_license.clone();
+        if let Some(package) = packages.get_mut(package_key) {
+            package.status = target_license.status;
+            package.license = target_license.license;
+            package.files = target_license.files;
+            package.copyright_from = target_key.cloned();
+        }
+    }
+
+    Ok(LicenseReport { packages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::builder::PackageBuilder;
+
+    fn entry() -> LockfilePackageEntry {
+        PackageBuilder::new("amd64", "hello", "2.10-3").build()
+    }
+
+    #[test]
+    fn test_package_license_combines_files_paragraphs() {
+        let license = PackageLicense::from_copyright(
+            &entry(),
+            "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/\n\
+             \n\
+             Files: *\n\
+             License: GPL-3+\n\
+             \n\
+             Files: lib/*\n\
+             License: GPL-3+\n\
+             \n\
+             Files: debian/*\n\
+             License: GPL-2+ or Expat\n"
+                .to_string(),
+        );
+
+        assert_eq!(license.status, CopyrightStatus::MachineReadable);
+        assert_eq!(
+            license.license.as_deref(),
+            Some("(GPL-2.0-or-later OR MIT) AND GPL-3.0-or-later")
+        );
+        assert_eq!(license.files.len(), 3);
+    }
+
+    #[test]
+    fn test_unstructured_copyright_keeps_text() {
+        let license = PackageLicense::from_copyright(&entry(), "Public domain.\n".to_string());
+        assert_eq!(license.status, CopyrightStatus::Unstructured);
+        assert_eq!(license.license, None);
+        assert_eq!(license.copyright.as_deref(), Some("Public domain.\n"));
+    }
+}
@@ -1,12 +1,28 @@
+mod diff;
+mod layout;
+mod migration;
 mod package_name_and_version;
+mod query;
+mod signature;
+mod size;
 
+use crate::config::{Config, ConfigHashes, SnapshotTimestamp, hash_config_file};
+use crate::repository::{BinaryPackage, SourceInfo};
 use crate::utils::arch_matches;
+use chrono::{DateTime, Utc};
 use debian_packaging::binary_package_control::BinaryPackageControlFile;
 use debian_packaging::checksum::{AnyChecksumType, AnyContentDigest};
 use debian_packaging::dependency::SingleDependency;
+pub use diff::{ArchitectureDiff, DiffFormat, LockfileDiff, PackageSummary, PackageVersionChange};
+use migration::{LockfileV1, LockfileVersion};
 use package_name_and_version::PackageNameAndVersion;
+pub use query::{LockfileQuery, PackageFilter, PackageKind, QueriedPackage, QueryFormat};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+pub use signature::{default_signature_path, sign_lockfile, verify_lockfile_signature};
+pub use size::{ArchitectureSize, LargePackage, ReportFormat, RootClosure, SizeReport};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
 use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -17,8 +33,14 @@ pub struct LockfilePackageEntry {
     pub version: String,
     /// Target architecture
     pub architecture: String,
+    /// Key of the entry in `sources` the package was taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
     /// Complete download URL
     pub download_url: String,
+    /// Download URLs on fallback mirrors, in order of preference
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirror_urls: Vec<String>,
     /// File size in bytes
     pub size: u64,
     /// Content digest for verification
@@ -35,6 +57,47 @@ pub struct LockfileDigest {
     pub value: String,
 }
 
+/// Distribution component that packages were taken from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LockfileSource {
+    /// `source_url` of the configured repository. Unknown for sources migrated from version 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<String>,
+    /// URL that the `Filename` paths of the distribution are relative to
+    pub distribution_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suite: Option<String>,
+    /// `Codename` of the Release file, which security advisories are keyed by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    /// `Date` of the Release file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<DateTime<Utc>>,
+    /// SHA256 of the Release (or InRelease) file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_sha256: Option<String>,
+    /// Fingerprint of the key that signed the Release file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_fingerprint: Option<String>,
+}
+
+impl From<&SourceInfo> for LockfileSource {
+    fn from(source_info: &SourceInfo) -> Self {
+        Self {
+            repository_url: Some(source_info.repository_url.clone()),
+            distribution_url: source_info.url.to_string(),
+            suite: source_info.suite.clone(),
+            codename: source_info.codename.clone(),
+            component: source_info.component.clone(),
+            release_date: source_info.release_date,
+            release_sha256: source_info.release_sha256.clone(),
+            signing_fingerprint: source_info.signing_fingerprint.clone(),
+        }
+    }
+}
+
 impl From<&AnyContentDigest> for LockfileDigest {
     fn from(digest: &AnyContentDigest) -> Self {
         match digest {
@@ -62,6 +125,30 @@ impl From<&AnyContentDigest> for LockfileDigest {
     }
 }
 
+impl LockfileDigest {
+    /// Parses the digest for verifying the package file.
+    pub fn content_digest(&self) -> Result<AnyContentDigest, crate::error::AptPrepError> {
+        let checksum_type = match self.algorithm.as_str() {
+            "MD5Sum" => AnyChecksumType::Md5,
+            "SHA1" => AnyChecksumType::Sha1,
+            "SHA256" => AnyChecksumType::Sha256,
+            "SHA384" => AnyChecksumType::Sha384,
+            "SHA512" => AnyChecksumType::Sha512,
+            _ => {
+                return Err(crate::error::AptPrepError::PackageVerification {
+                    package: "unknown".to_string(),
+                    expected: "supported digest algorithm".to_string(),
+                    actual: self.algorithm.clone(),
+                });
+            }
+        };
+        Ok(AnyContentDigest::from_hex_digest(
+            checksum_type,
+            &self.value,
+        )?)
+    }
+}
+
 impl LockfilePackageEntry {
     /// Get package name
     pub fn package_name(&self) -> Result<String, crate::error::AptPrepError> {
@@ -72,16 +159,35 @@ impl LockfilePackageEntry {
     pub fn package_version(&self) -> Result<String, crate::error::AptPrepError> {
         Ok(self.version.clone())
     }
+
+    /// Name of the downloaded package file, i.e. the last segment of the download URL
+    pub fn file_name(&self) -> Option<&str> {
+        self.download_url
+            .rsplit('/')
+            .next()
+            .filter(|file_name| !file_name.is_empty())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Lockfile {
     /// Version of the lockfile format
     pub version: u32,
-    /// Hash of the configuration used to generate this lockfile
-    pub config_hash: String,
+    /// Hashes of the configuration sections used to generate this lockfile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_hashes: Option<ConfigHashes>,
+    /// SHA-256 of the raw config file, kept from version 1 lockfiles, which recorded it instead
+    /// of `config_hashes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_file_hash: Option<String>,
     /// Required packages from config
     pub required_packages: Vec<Arc<str>>,
+    /// Timestamps of snapshot repositories by base URL
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub snapshots: BTreeMap<String, SnapshotTimestamp>,
+    /// Distribution components the packages were taken from, by source key
+    #[serde(default)]
+    pub sources: BTreeMap<String, LockfileSource>,
     /// Resolved packages by unique key
     pub packages: BTreeMap<String, LockfilePackageEntry>,
     /// Package groups by name for multi-arch support
@@ -107,33 +213,143 @@ fn generate_package_key(architecture: &str, name: &str, version: &str) -> String
     )
 }
 
+fn generate_source_key(
+    distribution_url: &str,
+    suite: Option<&str>,
+    component: Option<&str>,
+) -> String {
+    let location = distribution_url
+        .split_once("://")
+        .map_or(distribution_url, |(_, location)| location)
+        .trim_end_matches('/');
+    std::iter::once(location)
+        .chain(suite)
+        .chain(component)
+        .map(sanitize_package_key_component)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn package_download_url(distribution_url: &Url, path: &str) -> String {
+    let base_url = distribution_url.as_str().trim_end_matches("/");
+    if path.starts_with("/") {
+        format!("{}{}", base_url, path)
+    } else {
+        format!("{}/{}", base_url, path.strip_prefix("./").unwrap_or(path))
+    }
+}
+
 impl Lockfile {
-    pub const VERSION: u32 = 1;
+    pub const VERSION: u32 = 2;
 
-    pub fn new(config_hash: String, mut required_packages: Vec<Arc<str>>) -> Self {
+    pub fn new(config_hashes: ConfigHashes, mut required_packages: Vec<Arc<str>>) -> Self {
         required_packages.sort();
         required_packages.dedup();
 
         Self {
             version: Self::VERSION,
-            config_hash,
+            config_hashes: Some(config_hashes),
+            config_file_hash: None,
             required_packages,
+            snapshots: BTreeMap::new(),
+            sources: BTreeMap::new(),
             packages: BTreeMap::new(),
             package_groups: BTreeMap::new(),
         }
     }
 
+    /// Checks that the lockfile was generated from a configuration that resolves the same way
+    /// as `config`, read from `config_path`, and covers all of its target architectures.
+    /// Lockfiles migrated from version 1 are checked against the raw config file instead.
+    pub fn check_config(
+        &self,
+        config: &Config,
+        config_path: &Path,
+    ) -> Result<(), crate::error::AptPrepError> {
+        match (&self.config_hashes, &self.config_file_hash) {
+            (Some(locked_hashes), _) => {
+                let config_hashes = ConfigHashes::of(config)?;
+                let changed = locked_hashes.changed_resolution_sections(&config_hashes);
+                if !changed.is_empty() {
+                    return Err(crate::error::AptPrepError::LockfileValidation {
+                        details: format!(
+                            "Configuration of {} changed since the lockfile was generated. Please regenerate the lockfile with 'aptprep lock'.",
+                            changed.join(" and ")
+                        ),
+                    });
+                }
+                if locked_hashes.output != config_hashes.output {
+                    tracing::info!("Output settings changed since the lockfile was generated");
+                }
+            }
+            (None, Some(locked_hash)) => {
+                if *locked_hash != hash_config_file(config_path)? {
+                    return Err(crate::error::AptPrepError::LockfileValidation {
+                        details: "Configuration file changed since the lockfile was generated. Please regenerate the lockfile with 'aptprep lock'.".to_string(),
+                    });
+                }
+            }
+            (None, None) => {
+                return Err(crate::error::AptPrepError::LockfileValidation {
+                    details: "Lockfile does not record a configuration hash. Please regenerate the lockfile with 'aptprep lock'.".to_string(),
+                });
+            }
+        }
+
+        if let Some(architecture) = config
+            .output
+            .target_architectures
+            .iter()
+            .find(|architecture| {
+                !self
+                    .packages
+                    .values()
+                    .any(|package| &package.architecture == *architecture)
+            })
+        {
+            return Err(crate::error::AptPrepError::LockfileValidation {
+                details: format!(
+                    "Lockfile has no packages for target architecture {}. Please regenerate the lockfile with 'aptprep lock'.",
+                    architecture
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Names of the required packages without their version constraints.
+    pub fn root_package_names(&self) -> BTreeSet<String> {
+        self.required_packages
+            .iter()
+            .filter_map(|package| SingleDependency::parse(package).ok())
+            .map(|dependency| dependency.package)
+            .collect()
+    }
+
+    /// Adds the source of a package to `sources` and returns its key.
+    fn register_source(&mut self, source_info: &SourceInfo) -> String {
+        let source_key = generate_source_key(
+            source_info.url.as_str(),
+            source_info.suite.as_deref(),
+            source_info.component.as_deref(),
+        );
+        self.sources
+            .entry(source_key.clone())
+            .or_insert_with(|| LockfileSource::from(source_info));
+        source_key
+    }
+
     pub fn add_packages(
         &mut self,
         architecture: String,
-        resolved_packages: &std::collections::BTreeSet<Arc<BinaryPackageControlFile<'static>>>,
-        binary_packages_by_arch: &HashMap<String, Vec<crate::repository::BinaryPackage>>,
+        resolved_packages: &[BinaryPackage],
     ) -> Result<(), crate::error::AptPrepError> {
         // Keep lookup sorted by package name (asc), package version (desc).
         let mut package_lookup: BTreeMap<PackageNameAndVersion, String> = BTreeMap::new();
 
         // First pass: create all package entries and build lookup map
-        for control_file in resolved_packages {
+        for binary_package in resolved_packages {
+            let control_file = &binary_package.control_file;
             let package_name = control_file.package()?;
             let package_version = control_file.version()?;
             let package_name_and_version =
@@ -144,42 +360,10 @@ impl Lockfile {
         }
 
         // Second pass: create package entries with dependencies
-        for control_file in resolved_packages {
+        for binary_package in resolved_packages {
+            let control_file = &binary_package.control_file;
             let package_name = control_file.package()?;
             let package_version = control_file.version()?;
-            let package_arch = control_file.architecture()?;
-
-            // Find the binary package by matching package name, version, and architecture
-            let mut binary_package = None;
-
-            // First try the package's own architecture
-            if let Some(packages) = binary_packages_by_arch.get(package_arch) {
-                binary_package = packages.iter().find(|pkg| {
-                    let cf = &pkg.control_file;
-                    cf.package().unwrap() == package_name
-                        && cf.version().unwrap() == package_version
-                });
-            }
-
-            // If not found and package arch is "all", try the target architecture list too
-            if binary_package.is_none()
-                && package_arch == "all"
-                && let Some(packages) = binary_packages_by_arch.get(&architecture)
-            {
-                binary_package = packages.iter().find(|pkg| {
-                    let cf = &pkg.control_file;
-                    cf.package().unwrap() == package_name
-                        && cf.version().unwrap() == package_version
-                });
-            }
-
-            let binary_package =
-                binary_package.ok_or_else(|| crate::error::AptPrepError::LockfileValidation {
-                    details: format!(
-                        "Binary package not found for {}/{}/{}",
-                        package_name, package_version, package_arch
-                    ),
-                })?;
 
             let path = control_file.required_field_str("Filename")?.to_string();
             let size = control_file.field_u64("Size").ok_or_else(|| {
@@ -203,27 +387,28 @@ impl Lockfile {
             let dependencies =
                 self.parse_dependencies(control_file, &package_lookup, &architecture);
 
-            // Construct the download URL
-            let base_url = binary_package
+            // Construct the download URLs
+            let download_url = package_download_url(&binary_package.source_info.url, &path);
+            let mirror_urls = binary_package
                 .source_info
-                .url
-                .as_str()
-                .trim_end_matches("/");
-            let download_url = if path.starts_with("/") {
-                format!("{}{}", base_url, path)
-            } else {
-                format!("{}/{}", base_url, path.strip_prefix("./").unwrap_or(&path))
-            };
+                .mirror_urls
+                .iter()
+                .map(|mirror_url| package_download_url(mirror_url, &path))
+                .collect();
 
             // Generate package key
             let package_key =
                 generate_package_key(&architecture, package_name, &package_version.to_string());
 
+            let source = self.register_source(&binary_package.source_info);
+
             let lockfile_package = LockfilePackageEntry {
                 name: package_name.to_string(),
                 version: package_version.to_string(),
                 architecture: architecture.clone(),
+                source: Some(source),
                 download_url,
+                mirror_urls,
                 size,
                 digest: LockfileDigest::from(&digest?),
                 dependencies,
@@ -323,23 +508,158 @@ impl Lockfile {
                 reason: e.to_string(),
             }
         })?;
-        let lockfile: Lockfile = serde_json::from_str(&content).map_err(|e| {
-            crate::error::AptPrepError::LockfileLoad {
-                path: path.to_path_buf(),
-                reason: format!("JSON parsing failed: {}", e),
+        let parse_error = |e: serde_json::Error| crate::error::AptPrepError::LockfileLoad {
+            path: path.to_path_buf(),
+            reason: format!("JSON parsing failed: {}", e),
+        };
+
+        let LockfileVersion { version } = serde_json::from_str(&content).map_err(parse_error)?;
+        let lockfile = match version {
+            1 => {
+                tracing::info!("Migrating lockfile {} from version 1", path.display());
+                serde_json::from_str::<LockfileV1>(&content)
+                    .map_err(parse_error)?
+                    .into()
             }
-        })?;
+            Self::VERSION => serde_json::from_str::<Lockfile>(&content).map_err(parse_error)?,
+            version => {
+                return Err(crate::error::AptPrepError::LockfileValidation {
+                    details: format!(
+                        "Lockfile version {} is not supported. Expected version {}",
+                        version,
+                        Self::VERSION
+                    ),
+                });
+            }
+        };
 
-        if lockfile.version != Self::VERSION {
-            return Err(crate::error::AptPrepError::LockfileValidation {
-                details: format!(
-                    "Lockfile version {} is not supported. Expected version {}",
-                    lockfile.version,
-                    Self::VERSION
+        Ok(lockfile)
+    }
+}
+
+/// Builders for the lockfiles of unit tests.
+#[cfg(test)]
+pub(crate) mod builder {
+    use super::*;
+
+    /// Package entry with a Debian-style download URL, an empty digest and a minimal control
+    /// file.
+    pub(crate) struct PackageBuilder(LockfilePackageEntry);
+
+    impl PackageBuilder {
+        pub(crate) fn new(architecture: &str, name: &str, version: &str) -> Self {
+            let file_version = version
+                .split_once(':')
+                .map_or(version, |(_, version)| version);
+            Self(LockfilePackageEntry {
+                name: name.to_string(),
+                version: version.to_string(),
+                architecture: architecture.to_string(),
+                source: None,
+                download_url: format!(
+                    "https://deb.debian.org/debian/pool/main/{name}_{file_version}_{architecture}.deb"
                 ),
-            });
+                mirror_urls: Vec::new(),
+                size: 0,
+                digest: LockfileDigest {
+                    algorithm: "SHA256".to_string(),
+                    value: String::new(),
+                },
+                dependencies: Vec::new(),
+                control_file: format!("Package: {name}\nVersion: {version}\n"),
+            })
         }
 
-        Ok(lockfile)
+        /// Key of the entry in `sources`
+        pub(crate) fn source(mut self, source: &str) -> Self {
+            self.0.source = Some(source.to_string());
+            self
+        }
+
+        pub(crate) fn download_url(mut self, download_url: &str) -> Self {
+            self.0.download_url = download_url.to_string();
+            self
+        }
+
+        pub(crate) fn size(mut self, size: u64) -> Self {
+            self.0.size = size;
+            self
+        }
+
+        /// Hex SHA256 digest
+        pub(crate) fn digest(mut self, value: &str) -> Self {
+            self.0.digest.value = value.to_string();
+            self
+        }
+
+        /// Dependencies as package keys
+        pub(crate) fn dependencies<S: ToString>(mut self, dependencies: &[S]) -> Self {
+            self.0.dependencies = dependencies.iter().map(ToString::to_string).collect();
+            self
+        }
+
+        /// Appends a field to the control file.
+        pub(crate) fn control_field(mut self, name: &str, value: &str) -> Self {
+            self.0.control_file.push_str(&format!("{name}: {value}\n"));
+            self
+        }
+
+        pub(crate) fn build(self) -> LockfilePackageEntry {
+            self.0
+        }
+    }
+
+    /// Source without the metadata of a Release file.
+    pub(crate) fn source(distribution_url: &str) -> LockfileSource {
+        LockfileSource {
+            repository_url: None,
+            distribution_url: distribution_url.to_string(),
+            suite: None,
+            codename: None,
+            component: None,
+            release_date: None,
+            release_sha256: None,
+            signing_fingerprint: None,
+        }
+    }
+
+    /// Lockfile without config hashes whose packages are keyed like those of `aptprep lock`.
+    pub(crate) struct LockfileBuilder(Lockfile);
+
+    impl LockfileBuilder {
+        pub(crate) fn new(required_packages: &[&str]) -> Self {
+            Self(Lockfile {
+                version: Lockfile::VERSION,
+                config_hashes: None,
+                config_file_hash: None,
+                required_packages: required_packages
+                    .iter()
+                    .map(|package| Arc::from(*package))
+                    .collect(),
+                snapshots: BTreeMap::new(),
+                sources: BTreeMap::new(),
+                packages: BTreeMap::new(),
+                package_groups: BTreeMap::new(),
+            })
+        }
+
+        pub(crate) fn source(mut self, key: &str, source: LockfileSource) -> Self {
+            self.0.sources.insert(key.to_string(), source);
+            self
+        }
+
+        pub(crate) fn package(mut self, package: PackageBuilder) -> Self {
+            let entry = package.build();
+            let key = generate_package_key(&entry.architecture, &entry.name, &entry.version);
+            let group = self.0.package_groups.entry(entry.name.clone()).or_default();
+            group.push(key.clone());
+            group.sort();
+            self.0.packages.insert(key, entry);
+            self
+        }
+
+        pub(crate) fn build(self) -> Lockfile {
+            self.0
+        }
     }
 }
@@ -0,0 +1,436 @@
+use super::{Lockfile, LockfilePackageEntry};
+use crate::error::AptPrepError;
+use crate::utils::format_size;
+use debian_packaging::package_version::PackageVersion;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+/// Differences between two lockfiles.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct LockfileDiff {
+    pub roots_added: Vec<String>,
+    pub roots_removed: Vec<String>,
+    /// Architectures with changed packages
+    pub architectures: BTreeMap<String, ArchitectureDiff>,
+    /// Change of the total download size in bytes
+    pub size_delta: i64,
+}
+
+/// Package changes of a single architecture.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ArchitectureDiff {
+    pub added: Vec<PackageSummary>,
+    pub removed: Vec<PackageSummary>,
+    pub upgraded: Vec<PackageVersionChange>,
+    pub downgraded: Vec<PackageVersionChange>,
+    /// Change of the download size of this architecture in bytes
+    pub size_delta: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PackageSummary {
+    pub name: String,
+    pub version: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PackageVersionChange {
+    pub name: String,
+    pub old_version: String,
+    pub new_version: String,
+    pub size_delta: i64,
+}
+
+/// Output format of a lockfile diff.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Text,
+    Markdown,
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Unknown diff format '{value}'. Expected text, markdown or json"
+            )),
+        }
+    }
+}
+
+impl Display for DiffFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Markdown => write!(f, "markdown"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl ArchitectureDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.upgraded.is_empty()
+            && self.downgraded.is_empty()
+    }
+}
+
+impl LockfileDiff {
+    /// Compares the packages of `old` and `new` by architecture and name, ordering versions
+    /// the way Debian does.
+    pub fn between(old: &Lockfile, new: &Lockfile) -> Result<Self, AptPrepError> {
+        let old_roots = old.required_packages.iter().collect::<BTreeSet<_>>();
+        let new_roots = new.required_packages.iter().collect::<BTreeSet<_>>();
+
+        let old_packages = group_packages(old);
+        let new_packages = group_packages(new);
+
+        let mut architectures = BTreeMap::new();
+        let all_architectures = old_packages
+            .keys()
+            .chain(new_packages.keys())
+            .collect::<BTreeSet<_>>();
+        for architecture in all_architectures {
+            let empty = BTreeMap::new();
+            let old_by_name = old_packages.get(architecture).unwrap_or(&empty);
+            let new_by_name = new_packages.get(architecture).unwrap_or(&empty);
+            let architecture_diff = diff_architecture(old_by_name, new_by_name)?;
+            if !architecture_diff.is_empty() {
+                architectures.insert(architecture.to_string(), architecture_diff);
+            }
+        }
+
+        Ok(Self {
+            roots_added: new_roots
+                .difference(&old_roots)
+                .map(|root| root.to_string())
+                .collect(),
+            roots_removed: old_roots
+                .difference(&new_roots)
+                .map(|root| root.to_string())
+                .collect(),
+            architectures,
+            size_delta: total_size(new) - total_size(old),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots_added.is_empty()
+            && self.roots_removed.is_empty()
+            && self.architectures.is_empty()
+    }
+
+    pub fn render(&self, format: DiffFormat) -> Result<String, AptPrepError> {
+        match format {
+            DiffFormat::Text => Ok(self.render_text()),
+            DiffFormat::Markdown => Ok(self.render_markdown()),
+            DiffFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        if self.is_empty() {
+            return "No changes\n".to_string();
+        }
+        let mut out = String::new();
+        if !self.roots_added.is_empty() {
+            writeln!(out, "Roots added: {}", self.roots_added.join(", ")).unwrap();
+        }
+        if !self.roots_removed.is_empty() {
+            writeln!(out, "Roots removed: {}", self.roots_removed.join(", ")).unwrap();
+        }
+        for (architecture, diff) in &self.architectures {
+            writeln!(
+                out,
+                "{} ({}):",
+                architecture,
+                format_size_delta(diff.size_delta)
+            )
+            .unwrap();
+            for package in &diff.added {
+                writeln!(out, "  + {} {}", package.name, package.version).unwrap();
+            }
+            for package in &diff.removed {
+                writeln!(out, "  - {} {}", package.name, package.version).unwrap();
+            }
+            for change in &diff.upgraded {
+                writeln!(
+                    out,
+                    "  ^ {} {} -> {}",
+                    change.name, change.old_version, change.new_version
+                )
+                .unwrap();
+            }
+            for change in &diff.downgraded {
+                writeln!(
+                    out,
+                    "  v {} {} -> {}",
+                    change.name, change.old_version, change.new_version
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "Download size: {}", format_size_delta(self.size_delta)).unwrap();
+        out
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::from("## Lockfile changes\n\n");
+        if self.is_empty() {
+            out.push_str("No changes.\n");
+            return out;
+        }
+        if !self.roots_added.is_empty() {
+            writeln!(out, "**Roots added:** {}  ", code_list(&self.roots_added)).unwrap();
+        }
+        if !self.roots_removed.is_empty() {
+            writeln!(
+                out,
+                "**Roots removed:** {}  ",
+                code_list(&self.roots_removed)
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "**Download size:** {}\n",
+            format_size_delta(self.size_delta)
+        )
+        .unwrap();
+
+        for (architecture, diff) in &self.architectures {
+            writeln!(
+                out,
+                "### {} ({})\n",
+                architecture,
+                format_size_delta(diff.size_delta)
+            )
+            .unwrap();
+            out.push_str("| Change | Package | Old version | New version |\n");
+            out.push_str("|---|---|---|---|\n");
+            for package in &diff.added {
+                writeln!(
+                    out,
+                    "| added | `{}` | | {} |",
+                    package.name, package.version
+                )
+                .unwrap();
+            }
+            for package in &diff.removed {
+                writeln!(
+                    out,
+                    "| removed | `{}` | {} | |",
+                    package.name, package.version
+                )
+                .unwrap();
+            }
+            for (label, changes) in [
+                ("upgraded", &diff.upgraded),
+                ("downgraded", &diff.downgraded),
+            ] {
+                for change in changes {
+                    writeln!(
+                        out,
+                        "| {} | `{}` | {} | {} |",
+                        label, change.name, change.old_version, change.new_version
+                    )
+                    .unwrap();
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+type PackagesByName<'a> = BTreeMap<&'a str, Vec<&'a LockfilePackageEntry>>;
+
+fn group_packages(lockfile: &Lockfile) -> BTreeMap<&str, PackagesByName<'_>> {
+    let mut grouped: BTreeMap<&str, PackagesByName<'_>> = BTreeMap::new();
+    for package in lockfile.packages.values() {
+        grouped
+            .entry(package.architecture.as_str())
+            .or_default()
+            .entry(package.name.as_str())
+            .or_default()
+            .push(package);
+    }
+    grouped
+}
+
+fn diff_architecture(
+    old_by_name: &PackagesByName<'_>,
+    new_by_name: &PackagesByName<'_>,
+) -> Result<ArchitectureDiff, AptPrepError> {
+    let mut diff = ArchitectureDiff::default();
+    let names = old_by_name
+        .keys()
+        .chain(new_by_name.keys())
+        .collect::<BTreeSet<_>>();
+
+    for name in names {
+        let old_entries = old_by_name.get(name).map(Vec::as_slice).unwrap_or_default();
+        let new_entries = new_by_name.get(name).map(Vec::as_slice).unwrap_or_default();
+        diff.size_delta += new_entries
+            .iter()
+            .map(|entry| entry.size as i64)
+            .sum::<i64>()
+            - old_entries
+                .iter()
+                .map(|entry| entry.size as i64)
+                .sum::<i64>();
+
+        if let ([old], [new]) = (old_entries, new_entries) {
+            let old_version = parse_version(old)?;
+            let new_version = parse_version(new)?;
+            if old_version != new_version {
+                let change = PackageVersionChange {
+                    name: name.to_string(),
+                    old_version: old.version.clone(),
+                    new_version: new.version.clone(),
+                    size_delta: new.size as i64 - old.size as i64,
+                };
+                if new_version > old_version {
+                    diff.upgraded.push(change);
+                } else {
+                    diff.downgraded.push(change);
+                }
+            }
+            continue;
+        }
+
+        // Several versions of the package are locked on one side, so only report set changes
+        for entry in new_entries {
+            if !old_entries.iter().any(|old| old.version == entry.version) {
+                diff.added.push(PackageSummary::from(*entry));
+            }
+        }
+        for entry in old_entries {
+            if !new_entries.iter().any(|new| new.version == entry.version) {
+                diff.removed.push(PackageSummary::from(*entry));
+            }
+        }
+    }
+    Ok(diff)
+}
+
+impl From<&LockfilePackageEntry> for PackageSummary {
+    fn from(entry: &LockfilePackageEntry) -> Self {
+        Self {
+            name: entry.name.clone(),
+            version: entry.version.clone(),
+            size: entry.size,
+        }
+    }
+}
+
+fn parse_version(entry: &LockfilePackageEntry) -> Result<PackageVersion, AptPrepError> {
+    PackageVersion::parse(&entry.version).map_err(|e| AptPrepError::LockfileValidation {
+        details: format!("Invalid version {} of {}: {}", entry.version, entry.name, e),
+    })
+}
+
+fn total_size(lockfile: &Lockfile) -> i64 {
+    lockfile
+        .packages
+        .values()
+        .map(|package| package.size as i64)
+        .sum()
+}
+
+fn format_size_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(delta.unsigned_abs()))
+}
+
+fn code_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("`{}`", item))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder};
+
+    fn entry(architecture: &str, name: &str, version: &str, size: u64) -> PackageBuilder {
+        PackageBuilder::new(architecture, name, version).size(size)
+    }
+
+    fn lockfile(roots: &[&str], entries: Vec<PackageBuilder>) -> Lockfile {
+        entries
+            .into_iter()
+            .fold(LockfileBuilder::new(roots), LockfileBuilder::package)
+            .build()
+    }
+
+    #[test]
+    fn test_versions_are_ordered_the_debian_way() {
+        let old = lockfile(
+            &["a"],
+            vec![
+                entry("amd64", "a", "1.9", 10),
+                entry("amd64", "b", "1:1.0", 10),
+            ],
+        );
+        let new = lockfile(
+            &["a"],
+            vec![
+                entry("amd64", "a", "1.10", 15),
+                entry("amd64", "b", "2.0", 10),
+            ],
+        );
+
+        let diff = LockfileDiff::between(&old, &new).unwrap();
+        let amd64 = &diff.architectures["amd64"];
+        assert_eq!(amd64.upgraded.len(), 1);
+        assert_eq!(amd64.upgraded[0].name, "a");
+        assert_eq!(amd64.downgraded.len(), 1);
+        assert_eq!(amd64.downgraded[0].name, "b");
+        assert_eq!(diff.size_delta, 5);
+    }
+
+    #[test]
+    fn test_added_and_removed_packages_and_roots() {
+        let old = lockfile(&["a"], vec![entry("amd64", "a", "1.0", 10)]);
+        let new = lockfile(
+            &["b"],
+            vec![
+                entry("amd64", "b", "1.0", 30),
+                entry("arm64", "b", "1.0", 30),
+            ],
+        );
+
+        let diff = LockfileDiff::between(&old, &new).unwrap();
+        assert_eq!(diff.roots_added, vec!["b"]);
+        assert_eq!(diff.roots_removed, vec!["a"]);
+        assert_eq!(diff.architectures["amd64"].added[0].name, "b");
+        assert_eq!(diff.architectures["amd64"].removed[0].name, "a");
+        assert_eq!(diff.architectures["amd64"].size_delta, 20);
+        assert_eq!(diff.architectures["arm64"].added.len(), 1);
+        assert_eq!(diff.size_delta, 50);
+    }
+
+    #[test]
+    fn test_identical_lockfiles_have_no_changes() {
+        let old = lockfile(&["a"], vec![entry("amd64", "a", "1.0", 10)]);
+        let diff = LockfileDiff::between(&old, &old.clone()).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.render(DiffFormat::Text).unwrap(), "No changes\n");
+    }
+}
@@ -0,0 +1,264 @@
+use super::{Lockfile, LockfilePackageEntry};
+use crate::config::OutputLayout;
+use crate::error::AptPrepError;
+use debian_packaging::control::ControlFile;
+use std::collections::BTreeMap;
+
+/// Component of packages whose source does not record one, e.g. from flat repositories.
+const DEFAULT_COMPONENT: &str = "main";
+
+impl Lockfile {
+    /// Paths of the package files relative to the output directory, by package key.
+    ///
+    /// Packages that end up at the same path must be the same file. Otherwise this fails
+    /// naming both packages and where they come from. Identical files, such as an `all`
+    /// package locked for several architectures, share the path.
+    pub fn package_paths(
+        &self,
+        layout: OutputLayout,
+    ) -> Result<BTreeMap<String, String>, AptPrepError> {
+        let mut paths = BTreeMap::new();
+        let mut owners: BTreeMap<String, &str> = BTreeMap::new();
+
+        for (package_key, entry) in &self.packages {
+            let path = match layout {
+                OutputLayout::Flat => self.entry_file_name(package_key, entry)?.to_string(),
+                OutputLayout::Pool => self.pool_path(package_key, entry)?,
+            };
+
+            if let Some(owner_key) = owners.get(&path) {
+                let owner = &self.packages[*owner_key];
+                if owner.digest != entry.digest {
+                    return Err(AptPrepError::OutputPathCollision {
+                        path,
+                        first: format!("{} from {}", owner_key, owner.download_url),
+                        second: format!("{} from {}", package_key, entry.download_url),
+                    });
+                }
+            } else {
+                owners.insert(path.clone(), package_key);
+            }
+            paths.insert(package_key.clone(), path);
+        }
+        Ok(paths)
+    }
+
+    fn entry_file_name<'a>(
+        &self,
+        package_key: &str,
+        entry: &'a LockfilePackageEntry,
+    ) -> Result<&'a str, AptPrepError> {
+        entry
+            .file_name()
+            .ok_or_else(|| AptPrepError::PackageValidation {
+                package: package_key.to_string(),
+                details: format!("Invalid download URL: {}", entry.download_url),
+            })
+    }
+
+    /// `pool/<component>/<prefix>/<source>/<file name>`, where the prefix is the first letter of
+    /// the source package, or its first four for `lib` packages.
+    fn pool_path(
+        &self,
+        package_key: &str,
+        entry: &LockfilePackageEntry,
+    ) -> Result<String, AptPrepError> {
+        let component = entry
+            .source
+            .as_ref()
+            .and_then(|source| self.sources.get(source))
+            .and_then(|source| source.component.as_deref())
+            .unwrap_or(DEFAULT_COMPONENT);
+        // Older security archives use components like `updates/main`
+        if !component.split('/').all(is_valid_pool_name) {
+            return Err(AptPrepError::PackageValidation {
+                package: package_key.to_string(),
+                details: format!("Invalid component name: {}", component),
+            });
+        }
+
+        let control_file = ControlFile::parse_str(&entry.control_file)?;
+        let source_package = control_file
+            .paragraphs()
+            .next()
+            .and_then(|paragraph| paragraph.field_str("Source"))
+            .and_then(|source| source.split_whitespace().next())
+            .unwrap_or(entry.name.as_str());
+        if !is_valid_pool_name(source_package) {
+            return Err(AptPrepError::PackageValidation {
+                package: package_key.to_string(),
+                details: format!("Invalid source package name: {}", source_package),
+            });
+        }
+
+        Ok(format!(
+            "pool/{}/{}/{}/{}",
+            component,
+            pool_prefix(source_package),
+            source_package,
+            self.entry_file_name(package_key, entry)?
+        ))
+    }
+}
+
+/// Whether `name` is a valid Debian package or component name, `[a-z0-9][a-z0-9+.-]*`. This
+/// keeps names taken from the repository from escaping the pool directory.
+fn is_valid_pool_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '+' | '.' | '-'))
+}
+
+fn pool_prefix(source_package: &str) -> &str {
+    let prefix_length = if source_package.starts_with("lib") && source_package.len() > 3 {
+        4
+    } else {
+        1
+    };
+    source_package
+        .get(..prefix_length)
+        .unwrap_or(source_package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::LockfileSource;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder, source};
+
+    fn package(architecture: &str, name: &str, download_url: &str, digest: &str) -> PackageBuilder {
+        PackageBuilder::new(architecture, name, "1.0")
+            .source("debian")
+            .download_url(download_url)
+            .digest(digest)
+    }
+
+    fn lockfile(packages: Vec<PackageBuilder>) -> Lockfile {
+        let builder = LockfileBuilder::new(&[]).source(
+            "debian",
+            LockfileSource {
+                suite: Some("bookworm".to_string()),
+                component: Some("contrib".to_string()),
+                ..source("https://deb.debian.org/debian/")
+            },
+        );
+        packages
+            .into_iter()
+            .fold(builder, LockfileBuilder::package)
+            .build()
+    }
+
+    #[test]
+    fn test_pool_paths() {
+        let lockfile = lockfile(vec![
+            package(
+                "amd64",
+                "libssl3",
+                "https://example.com/libssl3_1.0_amd64.deb",
+                "aa",
+            )
+            .control_field("Source", "openssl (3.0.15-1)"),
+            package(
+                "amd64",
+                "hello",
+                "https://example.com/hello_1.0_amd64.deb",
+                "bb",
+            ),
+            package(
+                "amd64",
+                "libc6",
+                "https://example.com/libc6_1.0_amd64.deb",
+                "cc",
+            )
+            .control_field("Source", "glibc"),
+        ]);
+
+        let paths = lockfile.package_paths(OutputLayout::Pool).unwrap();
+        assert_eq!(
+            paths["amd64_libssl3_1_0"],
+            "pool/contrib/o/openssl/libssl3_1.0_amd64.deb"
+        );
+        assert_eq!(
+            paths["amd64_hello_1_0"],
+            "pool/contrib/h/hello/hello_1.0_amd64.deb"
+        );
+        assert_eq!(
+            paths["amd64_libc6_1_0"],
+            "pool/contrib/g/glibc/libc6_1.0_amd64.deb"
+        );
+        assert_eq!(pool_prefix("libxml2"), "libx");
+    }
+
+    #[test]
+    fn test_identical_files_share_a_path() {
+        let lockfile = lockfile(vec![
+            package(
+                "amd64",
+                "tzdata",
+                "https://example.com/tzdata_1.0_all.deb",
+                "aa",
+            ),
+            package(
+                "arm64",
+                "tzdata",
+                "https://example.com/tzdata_1.0_all.deb",
+                "aa",
+            ),
+        ]);
+
+        let paths = lockfile.package_paths(OutputLayout::Flat).unwrap();
+        assert_eq!(paths["amd64_tzdata_1_0"], "tzdata_1.0_all.deb");
+        assert_eq!(paths["arm64_tzdata_1_0"], "tzdata_1.0_all.deb");
+    }
+
+    #[test]
+    fn test_collision_lists_both_origins() {
+        let lockfile = lockfile(vec![
+            package(
+                "amd64",
+                "hello",
+                "https://one.example.com/hello_1.0_amd64.deb",
+                "aa",
+            ),
+            package(
+                "arm64",
+                "hello",
+                "https://two.example.com/hello_1.0_amd64.deb",
+                "bb",
+            ),
+        ]);
+
+        let error = lockfile.package_paths(OutputLayout::Flat).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("https://one.example.com/hello_1.0_amd64.deb"));
+        assert!(message.contains("https://two.example.com/hello_1.0_amd64.deb"));
+    }
+
+    #[test]
+    fn test_pool_rejects_path_traversal() {
+        let traversal = lockfile(vec![
+            package(
+                "amd64",
+                "hello",
+                "https://example.com/hello_1.0_amd64.deb",
+                "aa",
+            )
+            .control_field("Source", "../../.."),
+        ]);
+        assert!(traversal.package_paths(OutputLayout::Pool).is_err());
+
+        let mut component = lockfile(vec![package(
+            "amd64",
+            "hello",
+            "https://example.com/hello_1.0_amd64.deb",
+            "aa",
+        )]);
+        component.sources.get_mut("debian").unwrap().component = Some("../etc".to_string());
+        assert!(component.package_paths(OutputLayout::Pool).is_err());
+
+        assert!(is_valid_pool_name("libstdc++6"));
+        assert!(!is_valid_pool_name("Hello"));
+        assert!(!is_valid_pool_name(".hidden"));
+    }
+}
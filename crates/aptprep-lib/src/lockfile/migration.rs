@@ -0,0 +1,226 @@
+use super::{Lockfile, LockfilePackageEntry, LockfileSource, generate_source_key};
+use crate::config::SnapshotTimestamp;
+use chrono::{DateTime, Utc};
+use debian_packaging::control::ControlFile;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Just the format version, read before deciding how to parse the rest of the lockfile.
+#[derive(Debug, Deserialize)]
+pub struct LockfileVersion {
+    pub version: u32,
+}
+
+/// Lockfile format version 1. Its `config_hash` covered the raw config file and is kept to
+/// check the config against. `snapshots` and `release_dates` were only written by later
+/// version 1 lockfiles.
+#[derive(Debug, Deserialize)]
+pub struct LockfileV1 {
+    config_hash: String,
+    required_packages: Vec<Arc<str>>,
+    #[serde(default)]
+    snapshots: BTreeMap<String, SnapshotTimestamp>,
+    #[serde(default)]
+    release_dates: BTreeMap<String, DateTime<Utc>>,
+    packages: BTreeMap<String, LockfilePackageEntry>,
+    package_groups: BTreeMap<String, Vec<String>>,
+}
+
+impl From<LockfileV1> for Lockfile {
+    /// Derives the distribution URL of every package from its download URL and `Filename`
+    /// and records one source per distribution URL, with the Release date if version 1 kept
+    /// it. Suites, components and Release digests were not recorded by version 1 and stay
+    /// unknown until the lockfile is regenerated.
+    fn from(v1: LockfileV1) -> Self {
+        let release_dates = v1
+            .release_dates
+            .into_iter()
+            .map(|(distribution_url, release_date)| {
+                (
+                    distribution_url.trim_end_matches('/').to_string(),
+                    release_date,
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut sources = BTreeMap::new();
+        let mut packages = v1.packages;
+        for package in packages.values_mut() {
+            let Some(distribution_url) = distribution_url(package) else {
+                continue;
+            };
+            let source_key = generate_source_key(&distribution_url, None, None);
+            sources
+                .entry(source_key.clone())
+                .or_insert_with(|| LockfileSource {
+                    repository_url: None,
+                    release_date: release_dates
+                        .get(distribution_url.trim_end_matches('/'))
+                        .copied(),
+                    distribution_url,
+                    suite: None,
+                    codename: None,
+                    component: None,
+                    release_sha256: None,
+                    signing_fingerprint: None,
+                });
+            package.source = Some(source_key);
+        }
+
+        Self {
+            version: Self::VERSION,
+            // Version 1 hashed the raw config file, which cannot be compared with section hashes
+            // and is compared with the config file as a whole instead
+            config_hashes: None,
+            config_file_hash: Some(v1.config_hash),
+            required_packages: v1.required_packages,
+            snapshots: v1.snapshots,
+            sources,
+            packages,
+            package_groups: v1.package_groups,
+        }
+    }
+}
+
+/// The part of the download URL before the package's `Filename`, which `Filename` paths are
+/// relative to.
+fn distribution_url(package: &LockfilePackageEntry) -> Option<String> {
+    let filename = ControlFile::parse_str(&package.control_file)
+        .ok()?
+        .paragraphs()
+        .next()?
+        .field_str("Filename")?
+        .to_string();
+    let filename = filename.strip_prefix("./").unwrap_or(&filename);
+    let distribution_url = package
+        .download_url
+        .strip_suffix(filename.trim_start_matches('/'))?;
+    distribution_url
+        .ends_with('/')
+        .then(|| distribution_url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, hash_config_file};
+
+    /// A lockfile as written before the format was versioned beyond 1.
+    const V1_LOCKFILE: &str = r#"{
+        "version": 1,
+        "config_hash": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        "required_packages": ["hello"],
+        "packages": {
+            "amd64_hello_2_10_3": {
+                "name": "hello",
+                "version": "2.10-3",
+                "architecture": "amd64",
+                "download_url": "https://deb.debian.org/debian/pool/main/h/hello/hello_2.10-3_amd64.deb",
+                "size": 10,
+                "digest": { "algorithm": "SHA256", "value": "00" },
+                "dependencies": ["amd64_libc6_2_36_9"],
+                "control_file": "Package: hello\nVersion: 2.10-3\nArchitecture: amd64\nFilename: pool/main/h/hello/hello_2.10-3_amd64.deb\n"
+            },
+            "amd64_libc6_2_36_9": {
+                "name": "libc6",
+                "version": "2.36-9",
+                "architecture": "amd64",
+                "download_url": "https://deb.debian.org/debian/pool/main/g/glibc/libc6_2.36-9_amd64.deb",
+                "size": 20,
+                "digest": { "algorithm": "SHA256", "value": "01" },
+                "dependencies": [],
+                "control_file": "Package: libc6\nVersion: 2.36-9\nArchitecture: amd64\nFilename: pool/main/g/glibc/libc6_2.36-9_amd64.deb\n"
+            },
+            "amd64_tool_1_0": {
+                "name": "tool",
+                "version": "1.0",
+                "architecture": "amd64",
+                "download_url": "https://example.com/flat/tool_1.0_amd64.deb",
+                "size": 30,
+                "digest": { "algorithm": "SHA256", "value": "02" },
+                "dependencies": [],
+                "control_file": "Package: tool\nVersion: 1.0\nArchitecture: amd64\nFilename: ./tool_1.0_amd64.deb\n"
+            }
+        },
+        "package_groups": {
+            "hello": ["amd64_hello_2_10_3"],
+            "libc6": ["amd64_libc6_2_36_9"],
+            "tool": ["amd64_tool_1_0"]
+        }
+    }"#;
+
+    #[test]
+    fn test_v1_lockfile_is_migrated() {
+        let v1: LockfileV1 = serde_json::from_str(V1_LOCKFILE).unwrap();
+        let lockfile = Lockfile::from(v1);
+
+        assert_eq!(lockfile.version, Lockfile::VERSION);
+        assert_eq!(lockfile.config_hashes, None);
+        assert_eq!(
+            lockfile.config_file_hash.as_deref(),
+            Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")
+        );
+        let distribution_urls = lockfile
+            .sources
+            .values()
+            .map(|source| source.distribution_url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            distribution_urls,
+            [
+                "https://deb.debian.org/debian/",
+                "https://example.com/flat/"
+            ]
+        );
+
+        let hello = &lockfile.packages["amd64_hello_2_10_3"];
+        let libc6 = &lockfile.packages["amd64_libc6_2_36_9"];
+        assert!(hello.source.is_some());
+        assert_eq!(hello.source, libc6.source);
+    }
+
+    #[test]
+    fn test_release_dates_of_later_v1_lockfiles_are_kept() {
+        let mut v1: serde_json::Value = serde_json::from_str(V1_LOCKFILE).unwrap();
+        v1["release_dates"] =
+            serde_json::json!({ "https://deb.debian.org/debian": "2025-10-29T14:00:00Z" });
+        let lockfile = Lockfile::from(serde_json::from_value::<LockfileV1>(v1).unwrap());
+
+        let hello = &lockfile.packages["amd64_hello_2_10_3"];
+        let source = &lockfile.sources[hello.source.as_ref().unwrap()];
+        assert_eq!(
+            source.release_date,
+            Some("2025-10-29T14:00:00Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_migrated_lockfile_is_checked_against_the_config_file() {
+        const CONFIG: &str = r#"{
+            "source_repositories": [
+                {"source_url": "https://deb.debian.org/debian", "architectures": ["amd64"], "distributions": ["bookworm"]}
+            ],
+            "packages": ["hello"],
+            "output": {"target_architectures": ["amd64"]}
+        }"#;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        std::fs::write(&config_path, CONFIG).unwrap();
+        let config: Config = serde_json::from_str(CONFIG).unwrap();
+
+        let mut v1: serde_json::Value = serde_json::from_str(V1_LOCKFILE).unwrap();
+        v1["config_hash"] = hash_config_file(&config_path).unwrap().into();
+        let lockfile = Lockfile::from(serde_json::from_value::<LockfileV1>(v1).unwrap());
+        lockfile
+            .check_config(&config, &config_path)
+            .expect("Unchanged config should match the migrated lockfile");
+
+        let changed = CONFIG.replace("\"hello\"", "\"hello\", \"curl\"");
+        std::fs::write(&config_path, &changed).unwrap();
+        let error = lockfile
+            .check_config(&serde_json::from_str(&changed).unwrap(), &config_path)
+            .unwrap_err();
+        assert!(error.to_string().contains("aptprep lock"));
+    }
+}
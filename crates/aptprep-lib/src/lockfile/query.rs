@@ -0,0 +1,425 @@
+use super::Lockfile;
+use crate::error::AptPrepError;
+use crate::utils::format_size;
+use debian_packaging::control::ControlFile;
+use globset::Glob;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+/// Criteria for selecting lockfile packages. Criteria that are not set match every package.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PackageFilter {
+    /// Glob over the package name, e.g. `lib*-dev`
+    pub name: Option<String>,
+    pub architectures: Vec<String>,
+    /// Section, with or without the archive area, e.g. `libs` or `non-free/libs`
+    pub section: Option<String>,
+    pub priority: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Repository `source_url`, distribution URL or source key the package was taken from
+    pub repository: Option<String>,
+    pub kind: Option<PackageKind>,
+}
+
+/// Whether a package was requested in the config or pulled in as a dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageKind {
+    Direct,
+    Transitive,
+}
+
+/// Output format of a lockfile query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueryFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+    /// `name=version` lines for `apt-get install`
+    Apt,
+}
+
+impl FromStr for QueryFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "apt" => Ok(Self::Apt),
+            _ => Err(format!(
+                "Unknown query format '{value}'. Expected table, json, csv or apt"
+            )),
+        }
+    }
+}
+
+impl Display for QueryFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Json => write!(f, "json"),
+            Self::Csv => write!(f, "csv"),
+            Self::Apt => write!(f, "apt"),
+        }
+    }
+}
+
+/// A package selected by a query.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QueriedPackage {
+    pub key: String,
+    pub name: String,
+    pub version: String,
+    pub architecture: String,
+    pub size: u64,
+    pub section: Option<String>,
+    pub priority: Option<String>,
+    pub source: Option<String>,
+    /// Whether the package is one of the required packages
+    pub direct: bool,
+}
+
+/// Packages of a lockfile matching a [`PackageFilter`], ordered by package key.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(transparent)]
+pub struct LockfileQuery {
+    pub packages: Vec<QueriedPackage>,
+}
+
+impl LockfileQuery {
+    pub fn run(lockfile: &Lockfile, filter: &PackageFilter) -> Result<Self, AptPrepError> {
+        let name_matcher = filter
+            .name
+            .as_deref()
+            .map(|pattern| {
+                Glob::new(pattern)
+                    .map(|glob| glob.compile_matcher())
+                    .map_err(|e| AptPrepError::CliArgumentValidation {
+                        details: format!("Invalid package name pattern '{pattern}': {e}"),
+                    })
+            })
+            .transpose()?;
+        let repository = filter
+            .repository
+            .as_deref()
+            .map(|repository| repository.trim_end_matches('/'));
+        let roots = lockfile.root_package_names();
+
+        let mut packages = Vec::new();
+        for (package_key, entry) in &lockfile.packages {
+            let direct = roots.contains(&entry.name);
+            let matches = name_matcher
+                .as_ref()
+                .is_none_or(|matcher| matcher.is_match(&entry.name))
+                && (filter.architectures.is_empty()
+                    || filter.architectures.contains(&entry.architecture))
+                && filter
+                    .min_size
+                    .is_none_or(|min_size| entry.size >= min_size)
+                && filter
+                    .max_size
+                    .is_none_or(|max_size| entry.size <= max_size)
+                && filter.kind.is_none_or(|kind| match kind {
+                    PackageKind::Direct => direct,
+                    PackageKind::Transitive => !direct,
+                })
+                && repository.is_none_or(|repository| {
+                    entry.source.as_deref().is_some_and(|source_key| {
+                        source_key == repository
+                            || lockfile.sources.get(source_key).is_some_and(|source| {
+                                source
+                                    .repository_url
+                                    .iter()
+                                    .chain([&source.distribution_url])
+                                    .any(|url| url.trim_end_matches('/') == repository)
+                            })
+                    })
+                });
+            if !matches {
+                continue;
+            }
+
+            let control_file = ControlFile::parse_str(&entry.control_file)?;
+            let paragraph = control_file.paragraphs().next();
+            let field = |name: &str| {
+                paragraph
+                    .and_then(|paragraph| paragraph.field_str(name))
+                    .map(str::to_string)
+            };
+            let section = field("Section");
+            let priority = field("Priority");
+
+            if let Some(wanted) = &filter.section {
+                let matches_section = section.as_deref().is_some_and(|section| {
+                    section == wanted
+                        || section
+                            .rsplit_once('/')
+                            .is_some_and(|(_, section)| section == wanted)
+                });
+                if !matches_section {
+                    continue;
+                }
+            }
+            if filter
+                .priority
+                .as_ref()
+                .is_some_and(|wanted| priority.as_ref() != Some(wanted))
+            {
+                continue;
+            }
+
+            packages.push(QueriedPackage {
+                key: package_key.clone(),
+                name: entry.name.clone(),
+                version: entry.version.clone(),
+                architecture: entry.architecture.clone(),
+                size: entry.size,
+                section,
+                priority,
+                source: entry.source.clone(),
+                direct,
+            });
+        }
+
+        Ok(Self { packages })
+    }
+
+    pub fn render(&self, format: QueryFormat) -> Result<String, AptPrepError> {
+        match format {
+            QueryFormat::Table => Ok(self.render_table()),
+            QueryFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            QueryFormat::Csv => Ok(self.render_csv()),
+            QueryFormat::Apt => Ok(self.render_apt()),
+        }
+    }
+
+    fn render_table(&self) -> String {
+        let header = [
+            "NAME", "VERSION", "ARCH", "SIZE", "SECTION", "PRIORITY", "KIND",
+        ];
+        let rows = self
+            .packages
+            .iter()
+            .map(|package| {
+                [
+                    package.name.clone(),
+                    package.version.clone(),
+                    package.architecture.clone(),
+                    format_size(package.size),
+                    package.section.clone().unwrap_or_default(),
+                    package.priority.clone().unwrap_or_default(),
+                    kind_label(package.direct).to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let mut output = String::new();
+        let header = header.map(str::to_string);
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            let _ = writeln!(output, "{}", line.trim_end());
+        }
+        let total_size = self.packages.iter().map(|package| package.size).sum();
+        let _ = writeln!(
+            output,
+            "\n{} packages, {}",
+            self.packages.len(),
+            format_size(total_size)
+        );
+        output
+    }
+
+    fn render_csv(&self) -> String {
+        let mut output =
+            String::from("key,name,version,architecture,size,section,priority,source,kind\n");
+        for package in &self.packages {
+            let size = package.size.to_string();
+            let row = [
+                package.key.as_str(),
+                &package.name,
+                &package.version,
+                &package.architecture,
+                &size,
+                package.section.as_deref().unwrap_or_default(),
+                package.priority.as_deref().unwrap_or_default(),
+                package.source.as_deref().unwrap_or_default(),
+                kind_label(package.direct),
+            ];
+            let _ = writeln!(output, "{}", row.map(csv_field).join(","));
+        }
+        output
+    }
+
+    /// Writes one `name=version` line per package. Names are qualified with the architecture
+    /// when the result spans several architectures, as `apt-get` expects for foreign packages.
+    fn render_apt(&self) -> String {
+        let architectures = self
+            .packages
+            .iter()
+            .map(|package| package.architecture.as_str())
+            .filter(|architecture| *architecture != "all")
+            .collect::<BTreeSet<_>>();
+        let qualify = architectures.len() > 1;
+
+        let mut output = String::new();
+        for package in &self.packages {
+            if qualify && package.architecture != "all" {
+                let _ = writeln!(
+                    output,
+                    "{}:{}={}",
+                    package.name, package.architecture, package.version
+                );
+            } else {
+                let _ = writeln!(output, "{}={}", package.name, package.version);
+            }
+        }
+        output
+    }
+}
+
+fn kind_label(direct: bool) -> &'static str {
+    if direct { "direct" } else { "transitive" }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::LockfileSource;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder, source};
+
+    fn package(name: &str, architecture: &str, size: u64, section: &str) -> PackageBuilder {
+        PackageBuilder::new(architecture, name, "1.0")
+            .source("main")
+            .download_url(&format!(
+                "https://example.com/debian/pool/{name}_1.0_{architecture}.deb"
+            ))
+            .size(size)
+            .control_field("Section", section)
+            .control_field("Priority", "optional")
+    }
+
+    fn lockfile() -> Lockfile {
+        LockfileBuilder::new(&["hello (>= 1.0)", "libfoo-dev"])
+            .source(
+                "main",
+                LockfileSource {
+                    repository_url: Some("https://example.com/debian".to_string()),
+                    ..source("https://example.com/debian/dists/stable/")
+                },
+            )
+            .package(package("hello", "amd64", 50_000, "devel"))
+            .package(package("libc6", "amd64", 3_000_000, "libs"))
+            .package(package("libc6", "arm64", 2_900_000, "libs"))
+            .package(package("libfoo-dev", "arm64", 10_000, "non-free/libdevel"))
+            .build()
+    }
+
+    fn names(filter: PackageFilter) -> Vec<String> {
+        LockfileQuery::run(&lockfile(), &filter)
+            .unwrap()
+            .packages
+            .into_iter()
+            .map(|package| package.key)
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(
+            names(PackageFilter {
+                name: Some("lib*".to_string()),
+                architectures: vec!["arm64".to_string()],
+                ..Default::default()
+            }),
+            ["arm64_libc6_1_0", "arm64_libfoo_dev_1_0"]
+        );
+        assert_eq!(
+            names(PackageFilter {
+                section: Some("libdevel".to_string()),
+                ..Default::default()
+            }),
+            ["arm64_libfoo_dev_1_0"]
+        );
+        assert_eq!(
+            names(PackageFilter {
+                min_size: Some(1_000_000),
+                kind: Some(PackageKind::Transitive),
+                ..Default::default()
+            }),
+            ["amd64_libc6_1_0", "arm64_libc6_1_0"]
+        );
+        assert_eq!(
+            names(PackageFilter {
+                kind: Some(PackageKind::Direct),
+                repository: Some("https://example.com/debian/".to_string()),
+                ..Default::default()
+            }),
+            ["amd64_hello_1_0", "arm64_libfoo_dev_1_0"]
+        );
+        assert!(
+            names(PackageFilter {
+                repository: Some("https://example.org".to_string()),
+                ..Default::default()
+            })
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_apt_format_qualifies_multiple_architectures() {
+        let query = LockfileQuery::run(
+            &lockfile(),
+            &PackageFilter {
+                name: Some("libc6".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            query.render(QueryFormat::Apt).unwrap(),
+            "libc6:amd64=1.0\nlibc6:arm64=1.0\n"
+        );
+
+        let query = LockfileQuery::run(
+            &lockfile(),
+            &PackageFilter {
+                name: Some("hello".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(query.render(QueryFormat::Apt).unwrap(), "hello=1.0\n");
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,191 @@
+use super::Lockfile;
+use crate::error::AptPrepError;
+use crate::repository::{load_keyring, verify_detached_signature};
+use chrono::{SubsecRound, Utc};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::packet::{SignatureConfig, SignatureType, Subpacket, SubpacketData};
+use pgp::types::PublicKeyTrait;
+use pgp::{ArmorOptions, Deserializable, SignedSecretKey, StandaloneSignature};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+impl Lockfile {
+    /// Compact JSON serialization that signatures are made over. Package maps are ordered, so
+    /// reformatting the lockfile file does not change it.
+    pub fn canonical_bytes(&self) -> Result<Vec<u8>, AptPrepError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+/// Path of the detached signature next to a lockfile, `<lockfile>.asc`.
+pub fn default_signature_path(lockfile_path: &Path) -> PathBuf {
+    let mut path = lockfile_path.as_os_str().to_owned();
+    path.push(".asc");
+    PathBuf::from(path)
+}
+
+/// Makes an armored detached OpenPGP signature over the canonical serialization of the
+/// lockfile with an armored or binary secret key.
+pub fn sign_lockfile(
+    lockfile: &Lockfile,
+    secret_key_path: &Path,
+    passphrase: String,
+) -> Result<String, AptPrepError> {
+    let key_error = |reason: String| AptPrepError::Signature {
+        location: secret_key_path.display().to_string(),
+        reason,
+    };
+    let content = std::fs::read(secret_key_path).map_err(|e| key_error(e.to_string()))?;
+    let key = if content.starts_with(b"-----BEGIN PGP") {
+        SignedSecretKey::from_armor_single(Cursor::new(&content)).map(|(key, _headers)| key)
+    } else {
+        SignedSecretKey::from_bytes(Cursor::new(&content))
+    }
+    .map_err(|e| key_error(e.to_string()))?;
+
+    let mut config = SignatureConfig::v4(
+        SignatureType::Binary,
+        key.algorithm(),
+        HashAlgorithm::SHA2_256,
+    );
+    config.hashed_subpackets = vec![
+        Subpacket::regular(SubpacketData::SignatureCreationTime(
+            Utc::now().trunc_subsecs(0),
+        )),
+        Subpacket::regular(SubpacketData::Issuer(key.key_id())),
+    ];
+    let signature = config
+        .sign(
+            &key,
+            || passphrase,
+            Cursor::new(lockfile.canonical_bytes()?),
+        )
+        .map_err(|e| key_error(e.to_string()))?;
+
+    StandaloneSignature::new(signature)
+        .to_armored_string(ArmorOptions::default())
+        .map_err(|e| key_error(e.to_string()))
+}
+
+/// Checks the detached signature at `signature_path` against the keys in `keyring_path` and
+/// returns the fingerprint of the signing key. A missing signature counts as a failure.
+pub fn verify_lockfile_signature(
+    lockfile: &Lockfile,
+    signature_path: &Path,
+    keyring_path: &Path,
+) -> Result<String, AptPrepError> {
+    let keys = load_keyring(keyring_path)?;
+    let signature_error = |reason: String| AptPrepError::Signature {
+        location: signature_path.display().to_string(),
+        reason,
+    };
+    let signature = match std::fs::read(signature_path) {
+        Ok(signature) => signature,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(signature_error("Lockfile is not signed".to_string()));
+        }
+        Err(e) => return Err(signature_error(e.to_string())),
+    };
+
+    verify_detached_signature(&keys, &signature, &lockfile.canonical_bytes()?)
+        .map_err(signature_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder};
+    use pgp::crypto::ecc_curve::ECCCurve;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SignedPublicKey};
+
+    /// Writes a new signing key to `<name>.key` and its public key to `<name>.asc`.
+    fn generate_key(directory: &Path, name: &str) -> (PathBuf, PathBuf) {
+        let params = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::ECDSA(ECCCurve::P256))
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id(format!("{} <{}@example.com>", name, name))
+            .build()
+            .unwrap();
+        let key = params
+            .generate(rand::thread_rng())
+            .unwrap()
+            .sign(rand::thread_rng(), String::new)
+            .unwrap();
+
+        let secret_key_path = directory.join(format!("{}.key", name));
+        let keyring_path = directory.join(format!("{}.asc", name));
+        std::fs::write(
+            &secret_key_path,
+            key.to_armored_string(ArmorOptions::default()).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            &keyring_path,
+            SignedPublicKey::from(key)
+                .to_armored_string(ArmorOptions::default())
+                .unwrap(),
+        )
+        .unwrap();
+        (secret_key_path, keyring_path)
+    }
+
+    #[test]
+    fn test_signature_covers_the_lockfile() {
+        let directory = tempfile::tempdir().unwrap();
+        let (secret_key_path, keyring_path) = generate_key(directory.path(), "release");
+        let (_, other_keyring_path) = generate_key(directory.path(), "other");
+        let mut lockfile = LockfileBuilder::new(&["hello"])
+            .package(PackageBuilder::new("amd64", "hello", "1.0"))
+            .build();
+        let signature_path = directory.path().join("aptprep.lock.asc");
+
+        // Unsigned lockfiles do not verify
+        let error =
+            verify_lockfile_signature(&lockfile, &signature_path, &keyring_path).unwrap_err();
+        assert!(error.to_string().contains("not signed"));
+
+        let signature = sign_lockfile(&lockfile, &secret_key_path, String::new()).unwrap();
+        std::fs::write(&signature_path, signature).unwrap();
+        verify_lockfile_signature(&lockfile, &signature_path, &keyring_path)
+            .expect("Signature should verify with the signing key");
+        assert!(
+            verify_lockfile_signature(&lockfile, &signature_path, &other_keyring_path).is_err()
+        );
+
+        lockfile
+            .packages
+            .get_mut("amd64_hello_1_0")
+            .unwrap()
+            .download_url = "https://attacker.example.com/hello_1.0_amd64.deb".to_string();
+        assert!(verify_lockfile_signature(&lockfile, &signature_path, &keyring_path).is_err());
+    }
+
+    #[test]
+    fn test_canonical_bytes_ignore_formatting() {
+        let lockfile: Lockfile = serde_json::from_str(
+            r#"{
+                "version": 2,
+                "required_packages": ["curl"],
+                "packages": {},
+                "package_groups": {}
+            }"#,
+        )
+        .unwrap();
+        let reformatted: Lockfile =
+            serde_json::from_str(&serde_json::to_string_pretty(&lockfile).unwrap()).unwrap();
+
+        assert_eq!(
+            lockfile.canonical_bytes().unwrap(),
+            reformatted.canonical_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_default_signature_path() {
+        assert_eq!(
+            default_signature_path(Path::new("locks/aptprep.lock")),
+            PathBuf::from("locks/aptprep.lock.asc")
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use super::Lockfile;
+use crate::error::AptPrepError;
+use crate::utils::format_size;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+/// Download sizes of a lockfile and how much each required package contributes to them.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct SizeReport {
+    /// Download size of all packages in bytes, counting files that several architectures share
+    /// once
+    pub total_size: u64,
+    /// Size of the files, e.g. of `all` packages, that are included in the totals of more than
+    /// one architecture
+    pub shared_between_architectures: u64,
+    pub architectures: BTreeMap<String, ArchitectureSize>,
+    /// Largest packages of all architectures, biggest first
+    pub largest_packages: Vec<LargePackage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ArchitectureSize {
+    pub package_count: usize,
+    /// Includes packages that are shared with other architectures
+    pub total_size: u64,
+    /// Closures of the required packages, largest exclusive size first
+    pub roots: Vec<RootClosure>,
+}
+
+/// The packages a required package pulls in, directly or through dependencies.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RootClosure {
+    pub name: String,
+    pub package_count: usize,
+    /// Size of the root package and all of its dependencies
+    pub closure_size: u64,
+    /// Size of the packages no other root depends on, i.e. what dropping the root would save
+    pub exclusive_size: u64,
+    /// Size of the packages that other roots depend on as well
+    pub shared_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LargePackage {
+    pub key: String,
+    pub name: String,
+    pub version: String,
+    pub architecture: String,
+    pub size: u64,
+    /// Number of required packages whose closure contains this package
+    pub required_by_roots: usize,
+}
+
+/// Output format of a size report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Unknown report format '{value}'. Expected text or json"
+            )),
+        }
+    }
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl SizeReport {
+    /// Computes the closures of the required packages from the `dependencies` of each
+    /// package and lists the `top` largest packages.
+    pub fn of(lockfile: &Lockfile, top: usize) -> Self {
+        let roots = lockfile.root_package_names();
+        let mut architectures = BTreeMap::new();
+        let mut roots_by_package: BTreeMap<&str, usize> = BTreeMap::new();
+
+        let mut keys_by_architecture: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        // Package files by name and digest, with how many architectures include them
+        let mut files: BTreeMap<(Option<&str>, &str), (u64, usize)> = BTreeMap::new();
+        for (package_key, entry) in &lockfile.packages {
+            keys_by_architecture
+                .entry(entry.architecture.as_str())
+                .or_default()
+                .push(package_key);
+            files
+                .entry((entry.file_name(), entry.digest.value.as_str()))
+                .or_insert((entry.size, 0))
+                .1 += 1;
+        }
+
+        for (architecture, package_keys) in keys_by_architecture {
+            let closures = roots
+                .iter()
+                .filter_map(|root| {
+                    let root_keys = package_keys
+                        .iter()
+                        .copied()
+                        .filter(|key| lockfile.packages[*key].name == *root)
+                        .collect::<Vec<_>>();
+                    (!root_keys.is_empty()).then(|| (root, closure(lockfile, root_keys)))
+                })
+                .collect::<Vec<_>>();
+
+            let mut root_counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for (_, closure) in &closures {
+                for key in closure {
+                    *root_counts.entry(*key).or_default() += 1;
+                }
+            }
+
+            let mut root_closures = closures
+                .iter()
+                .map(|(root, closure)| {
+                    let size_of = |shared: bool| {
+                        closure
+                            .iter()
+                            .filter(|key| (root_counts[**key] > 1) == shared)
+                            .map(|key| lockfile.packages[*key].size)
+                            .sum::<u64>()
+                    };
+                    let exclusive_size = size_of(false);
+                    let shared_size = size_of(true);
+                    RootClosure {
+                        name: root.to_string(),
+                        package_count: closure.len(),
+                        closure_size: exclusive_size + shared_size,
+                        exclusive_size,
+                        shared_size,
+                    }
+                })
+                .collect::<Vec<_>>();
+            root_closures.sort_by(|a, b| {
+                b.exclusive_size
+                    .cmp(&a.exclusive_size)
+                    .then_with(|| a.name.cmp(&b.name))
+            });
+
+            architectures.insert(
+                architecture.to_string(),
+                ArchitectureSize {
+                    package_count: package_keys.len(),
+                    total_size: package_keys
+                        .iter()
+                        .map(|key| lockfile.packages[*key].size)
+                        .sum(),
+                    roots: root_closures,
+                },
+            );
+            roots_by_package.extend(root_counts);
+        }
+
+        let mut largest_packages = lockfile
+            .packages
+            .iter()
+            .map(|(package_key, entry)| LargePackage {
+                key: package_key.clone(),
+                name: entry.name.clone(),
+                version: entry.version.clone(),
+                architecture: entry.architecture.clone(),
+                size: entry.size,
+                required_by_roots: roots_by_package
+                    .get(package_key.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        largest_packages.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.key.cmp(&b.key)));
+        largest_packages.truncate(top);
+
+        Self {
+            total_size: files.values().map(|(size, _)| size).sum(),
+            shared_between_architectures: files
+                .values()
+                .filter(|(_, count)| *count > 1)
+                .map(|(size, _)| size)
+                .sum(),
+            architectures,
+            largest_packages,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String, AptPrepError> {
+        match format {
+            ReportFormat::Text => Ok(self.render_text()),
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = String::new();
+        for (architecture, size) in &self.architectures {
+            writeln!(
+                out,
+                "{}: {} packages, {}",
+                architecture,
+                size.package_count,
+                format_size(size.total_size)
+            )
+            .unwrap();
+            let name_width = size
+                .roots
+                .iter()
+                .map(|root| root.name.len())
+                .max()
+                .unwrap_or_default()
+                .max("ROOT".len());
+            writeln!(
+                out,
+                "  {:name_width$}  {:>8}  {:>10}  {:>10}  {:>10}",
+                "ROOT", "PACKAGES", "CLOSURE", "EXCLUSIVE", "SHARED"
+            )
+            .unwrap();
+            for root in &size.roots {
+                writeln!(
+                    out,
+                    "  {:name_width$}  {:>8}  {:>10}  {:>10}  {:>10}",
+                    root.name,
+                    root.package_count,
+                    format_size(root.closure_size),
+                    format_size(root.exclusive_size),
+                    format_size(root.shared_size)
+                )
+                .unwrap();
+            }
+            out.push('\n');
+        }
+
+        if !self.largest_packages.is_empty() {
+            out.push_str("Largest packages:\n");
+            for package in &self.largest_packages {
+                writeln!(
+                    out,
+                    "  {:>10}  {} {} ({}), required by {} roots",
+                    format_size(package.size),
+                    package.name,
+                    package.version,
+                    package.architecture,
+                    package.required_by_roots
+                )
+                .unwrap();
+            }
+            out.push('\n');
+        }
+        writeln!(out, "Total download size: {}", format_size(self.total_size)).unwrap();
+        if self.shared_between_architectures > 0 {
+            writeln!(
+                out,
+                "Architecture totals include {} of packages shared between architectures",
+                format_size(self.shared_between_architectures)
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// Keys of the packages reachable from `start` through `dependencies`, including `start`.
+fn closure<'a>(lockfile: &'a Lockfile, start: Vec<&'a str>) -> BTreeSet<&'a str> {
+    let mut visited = BTreeSet::new();
+    let mut pending = start;
+    while let Some(key) = pending.pop() {
+        if !visited.insert(key) {
+            continue;
+        }
+        if let Some(entry) = lockfile.packages.get(key) {
+            pending.extend(entry.dependencies.iter().map(String::as_str));
+        }
+    }
+    visited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::builder::{LockfileBuilder, PackageBuilder};
+    use crate::lockfile::generate_package_key;
+
+    fn package(name: &str, size: u64, dependencies: &[&str]) -> PackageBuilder {
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| generate_package_key("amd64", dependency, "1.0"))
+            .collect::<Vec<_>>();
+        PackageBuilder::new("amd64", name, "1.0")
+            .size(size)
+            .dependencies(&dependencies)
+    }
+
+    fn lockfile() -> Lockfile {
+        LockfileBuilder::new(&["curl", "python3 (>= 3.11)"])
+            .package(package("curl", 300, &["libcurl4", "libc6"]))
+            .package(package("libcurl4", 400, &["libc6"]))
+            .package(package("python3", 500, &["libpython3", "libc6"]))
+            .package(package("libpython3", 5000, &["libc6"]))
+            .package(package("libc6", 3000, &[]))
+            .build()
+    }
+
+    #[test]
+    fn test_exclusive_and_shared_closure_sizes() {
+        let report = SizeReport::of(&lockfile(), 2);
+        let amd64 = &report.architectures["amd64"];
+
+        assert_eq!(report.total_size, 9200);
+        assert_eq!(report.shared_between_architectures, 0);
+        assert_eq!(amd64.package_count, 5);
+        assert_eq!(
+            amd64.roots,
+            [
+                RootClosure {
+                    name: "python3".to_string(),
+                    package_count: 3,
+                    closure_size: 8500,
+                    exclusive_size: 5500,
+                    shared_size: 3000,
+                },
+                RootClosure {
+                    name: "curl".to_string(),
+                    package_count: 3,
+                    closure_size: 3700,
+                    exclusive_size: 700,
+                    shared_size: 3000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_largest_packages() {
+        let report = SizeReport::of(&lockfile(), 2);
+        let largest = report
+            .largest_packages
+            .iter()
+            .map(|package| (package.name.as_str(), package.required_by_roots))
+            .collect::<Vec<_>>();
+        assert_eq!(largest, [("libpython3", 1), ("libc6", 2)]);
+    }
+
+    #[test]
+    fn test_shared_packages_count_once_in_the_total() {
+        let tzdata = |architecture| {
+            PackageBuilder::new(architecture, "tzdata", "1.0")
+                .download_url("https://deb.debian.org/debian/pool/main/t/tzdata/tzdata_1.0_all.deb")
+                .digest("aa")
+                .size(300)
+        };
+        let lockfile = LockfileBuilder::new(&["tzdata"])
+            .package(tzdata("amd64"))
+            .package(tzdata("arm64"))
+            .package(PackageBuilder::new("arm64", "hello", "1.0").size(50))
+            .build();
+
+        let report = SizeReport::of(&lockfile, 0);
+        assert_eq!(report.architectures["amd64"].total_size, 300);
+        assert_eq!(report.architectures["arm64"].total_size, 350);
+        assert_eq!(report.total_size, 350);
+        assert_eq!(report.shared_between_architectures, 300);
+        assert!(
+            report
+                .render(ReportFormat::Text)
+                .unwrap()
+                .contains("shared between architectures")
+        );
+    }
+}
@@ -1,3 +1,9 @@
 mod packages;
+mod prune;
 
-pub use packages::{generate_packages_file, generate_packages_file_from_lockfile};
+pub use packages::{
+    generate_packages_file, generate_packages_file_from_lockfile, render_packages_index,
+};
+pub use prune::{
+    GENERATED_INDEX_FILES, PruneOptions, PruneReport, prune_output_dir, referenced_files,
+};
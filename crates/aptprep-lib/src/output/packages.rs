@@ -1,31 +1,26 @@
+use crate::config::OutputLayout;
 use crate::download::DownloadItem;
 use crate::error::AptPrepError;
 use crate::lockfile::Lockfile;
 use crate::repository::BinaryPackage;
-use debian_packaging::binary_package_control::BinaryPackageControlFile;
 use debian_packaging::checksum::{AnyChecksumType, AnyContentDigest};
 use debian_packaging::control::{ControlFile, ControlParagraph};
 use debian_packaging::error::DebianError;
 use debian_packaging::repository::builder::DebPackageReference;
 use itertools::Itertools;
-use std::collections::{BTreeSet, HashMap};
 use std::io::BufWriter;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 
 pub fn generate_packages_file(
-    collected_packages: &BTreeSet<Arc<BinaryPackageControlFile<'static>>>,
-    binary_packages_by_control_file: &HashMap<
-        Arc<BinaryPackageControlFile<'static>>,
-        &BinaryPackage,
-    >,
+    collected_packages: &[BinaryPackage],
     output_dir: &Path,
 ) -> Result<(Vec<DownloadItem>, PathBuf), AptPrepError> {
     let mut fetches = Vec::new();
     let mut control_file = ControlFile::default();
 
-    for cf in collected_packages {
+    for package in collected_packages {
+        let cf = &package.control_file;
         let Ok(_filename) = cf.deb_filename() else {
             tracing::warn!("Skipping package, no Debian package name specified");
             continue;
@@ -43,10 +38,6 @@ pub fn generate_packages_file(
             })
             .ok_or(DebianError::RepositoryReadCouldNotDeterminePackageDigest)??;
 
-        let package = binary_packages_by_control_file
-            .get(cf)
-            .expect("Failed to get binary package");
-
         let url = package.source_info.url.join(&path).expect("Invalid URL");
         let filename = url
             .path_segments()
@@ -63,6 +54,7 @@ pub fn generate_packages_file(
                 .trim_end_matches("/")
                 .to_string(),
             rel_path: format!("/{}", path.strip_prefix("./").unwrap_or(&path)),
+            mirrors: Vec::new(),
             size: cf.deb_size_bytes().ok(),
             digest,
             output_path: Some(filename.to_string()),
@@ -86,37 +78,48 @@ pub fn generate_packages_file(
     Ok((fetches, packages_path))
 }
 
-pub fn generate_packages_file_from_lockfile(
+/// Renders the Packages index of the lockfile, with `Filename` fields pointing to where
+/// `layout` puts the package files relative to the index.
+pub fn render_packages_index(
     lockfile: &Lockfile,
-    output_path: &Path,
-) -> Result<PathBuf, AptPrepError> {
+    layout: OutputLayout,
+) -> Result<Vec<u8>, AptPrepError> {
+    let package_paths = lockfile.package_paths(layout)?;
     let mut control_file = ControlFile::default();
 
-    for lockfile_package in lockfile
+    for (package_key, lockfile_package) in lockfile
         .packages
-        .values()
-        .sorted_by_key(|v| v.package_name().unwrap())
+        .iter()
+        .sorted_by_key(|(_, v)| v.package_name().unwrap())
     {
         // Create a control paragraph from the package information
 
         let cur_control_file = ControlFile::parse_str(&lockfile_package.control_file)?;
         for cur_paragraph in cur_control_file.paragraphs() {
             let mut paragraph = cur_paragraph.clone();
-            if let Some(filename_field) = paragraph.field_str("Filename") {
-                // Extract filename from download URL
-                let filename = filename_field.split('/').next_back().ok_or_else(|| {
-                    AptPrepError::Download {
-                        message: format!("Invalid download URL: {}", lockfile_package.download_url),
-                    }
-                })?;
-
-                paragraph
-                    .set_field_from_string("Filename".into(), format!("./{}", filename).into());
+            if paragraph.field_str("Filename").is_some() {
+                paragraph.set_field_from_string(
+                    "Filename".into(),
+                    format!("./{}", package_paths[package_key]).into(),
+                );
             }
             control_file.add_paragraph(paragraph);
         }
     }
 
+    let mut index = Vec::new();
+    control_file.write(&mut index).map_err(AptPrepError::Io)?;
+    Ok(index)
+}
+
+/// Writes the Packages index of the lockfile to `output_path`.
+pub fn generate_packages_file_from_lockfile(
+    lockfile: &Lockfile,
+    output_path: &Path,
+    layout: OutputLayout,
+) -> Result<PathBuf, AptPrepError> {
+    let index = render_packages_index(lockfile, layout)?;
+
     let output_dir = output_path
         .parent()
         .filter(|path| !path.as_os_str().is_empty())
@@ -126,9 +129,7 @@ pub fn generate_packages_file_from_lockfile(
         path: output_dir.to_path_buf(),
         reason: e.to_string(),
     })?;
-    let packages_file = std::fs::File::create(output_path).map_err(AptPrepError::Io)?;
-    let mut writer = BufWriter::new(packages_file);
-    control_file.write(&mut writer).map_err(AptPrepError::Io)?;
+    std::fs::write(output_path, index).map_err(AptPrepError::Io)?;
 
     Ok(output_path.to_path_buf())
 }
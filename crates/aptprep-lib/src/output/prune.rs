@@ -0,0 +1,164 @@
+use crate::config::OutputLayout;
+use crate::download::MANIFEST_FILE_NAME;
+use crate::error::AptPrepError;
+use crate::lockfile::Lockfile;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Index files generated into the output directory next to the packages.
+pub const GENERATED_INDEX_FILES: [&str; 2] = ["Packages", "Packages.aptprep"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneOptions {
+    /// Only report what would be pruned
+    pub dry_run: bool,
+    /// Moves pruned files here, keeping their relative paths, instead of deleting them
+    pub quarantine_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PruneReport {
+    /// Pruned files relative to the output directory
+    pub pruned_files: Vec<PathBuf>,
+    pub pruned_bytes: u64,
+    pub kept_files: usize,
+}
+
+/// Files in the output directory that belong to `lockfile`, relative to the output directory,
+/// including the generated index files and the verification manifest.
+pub fn referenced_files(
+    lockfile: &Lockfile,
+    layout: OutputLayout,
+) -> Result<BTreeSet<PathBuf>, AptPrepError> {
+    Ok(lockfile
+        .package_paths(layout)?
+        .into_values()
+        .map(PathBuf::from)
+        .chain(GENERATED_INDEX_FILES.iter().map(PathBuf::from))
+        .chain([PathBuf::from(MANIFEST_FILE_NAME)])
+        .collect())
+}
+
+/// Deletes or quarantines every file below `output_dir` that is not in `referenced`, then
+/// removes directories left empty. The quarantine directory is skipped if it is inside
+/// `output_dir`.
+pub fn prune_output_dir(
+    output_dir: &Path,
+    referenced: &BTreeSet<PathBuf>,
+    options: &PruneOptions,
+) -> Result<PruneReport, AptPrepError> {
+    let quarantine_dir = match &options.quarantine_dir {
+        Some(quarantine_dir) => {
+            if !options.dry_run {
+                std::fs::create_dir_all(quarantine_dir)?;
+            }
+            Some(canonicalize_if_exists(quarantine_dir)?)
+        }
+        None => None,
+    };
+    let mut pruner = Pruner {
+        output_dir,
+        referenced,
+        options,
+        quarantine_dir,
+        report: PruneReport::default(),
+    };
+    pruner.prune_directory(Path::new(""))?;
+    Ok(pruner.report)
+}
+
+struct Pruner<'a> {
+    output_dir: &'a Path,
+    referenced: &'a BTreeSet<PathBuf>,
+    options: &'a PruneOptions,
+    /// Canonical path of the quarantine directory
+    quarantine_dir: Option<PathBuf>,
+    report: PruneReport,
+}
+
+impl Pruner<'_> {
+    /// Prunes the directory at `relative_dir` and returns whether it is empty afterwards.
+    fn prune_directory(&mut self, relative_dir: &Path) -> Result<bool, AptPrepError> {
+        let directory = self.output_dir.join(relative_dir);
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(true),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = entries.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut empty = true;
+        for entry in entries {
+            let relative_path = relative_dir.join(entry.file_name());
+            // Symlinks are pruned like files and never followed
+            if entry.file_type()?.is_dir() {
+                if self.is_quarantine_dir(&entry.path())? {
+                    empty = false;
+                    continue;
+                }
+                let child_empty = self.prune_directory(&relative_path)?;
+                if child_empty && !self.options.dry_run {
+                    std::fs::remove_dir(entry.path())?;
+                } else {
+                    empty = false;
+                }
+            } else if self.referenced.contains(&relative_path) {
+                self.report.kept_files += 1;
+                empty = false;
+            } else {
+                self.prune_file(&entry.path(), relative_path)?;
+                empty &= !self.options.dry_run;
+            }
+        }
+        Ok(empty)
+    }
+
+    fn prune_file(&mut self, path: &Path, relative_path: PathBuf) -> Result<(), AptPrepError> {
+        let size = std::fs::symlink_metadata(path)?.len();
+        if !self.options.dry_run {
+            match &self.options.quarantine_dir {
+                Some(quarantine_dir) => move_file(path, &quarantine_dir.join(&relative_path))?,
+                None => std::fs::remove_file(path)?,
+            }
+            tracing::debug!("Pruned {}", relative_path.display());
+        }
+        self.report.pruned_bytes += size;
+        self.report.pruned_files.push(relative_path);
+        Ok(())
+    }
+
+    fn is_quarantine_dir(&self, directory: &Path) -> Result<bool, AptPrepError> {
+        match &self.quarantine_dir {
+            Some(quarantine_dir) => Ok(&std::fs::canonicalize(directory)? == quarantine_dir),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Renames `from` to `to`, copying across file systems.
+fn move_file(from: &Path, to: &Path) -> Result<(), AptPrepError> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Canonical form of `path`, which need not exist yet.
+fn canonicalize_if_exists(path: &Path) -> Result<PathBuf, AptPrepError> {
+    match std::fs::canonicalize(path) {
+        Ok(path) => Ok(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(path.to_path_buf()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -1,74 +1,112 @@
-use super::types::{BinaryPackage, SourceInfo};
-use crate::config::{Config, DistributionDef};
+use super::flat::read_flat_distribution;
+use super::freshness::check_release_freshness;
+use super::signature::{FetchedRelease, fetch_release, load_keyring};
+use super::types::{BinaryPackage, CollectedPackages, SourceInfo};
+use crate::config::{Config, DistributionDef, SourceRepository};
 use crate::error::AptPrepError;
-use debian_packaging::repository::reader_from_str;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use debian_packaging::binary_package_control::BinaryPackageControlFile;
+use debian_packaging::repository::release::ReleaseFile;
+use debian_packaging::repository::{RepositoryRootReader, reader_from_str};
+use pgp::SignedPublicKey;
+use reqwest::Url;
+use std::collections::BTreeMap;
+use std::io::Cursor;
 use std::sync::Arc;
 use tracing;
 
 pub async fn collect_binary_packages(
     app_config: &Config,
-) -> Result<HashMap<String, Vec<BinaryPackage>>, AptPrepError> {
-    let mut binary_packages_by_arch: HashMap<String, Vec<BinaryPackage>> = HashMap::new();
+) -> Result<CollectedPackages, AptPrepError> {
+    let mut collected = CollectedPackages::default();
 
     for source_repository in app_config.source_repositories.iter() {
-        let reader = reader_from_str(&source_repository.source_url).map_err(|e| {
-            AptPrepError::RepositoryAccess {
-                repository: source_repository.source_url.clone(),
-                reason: format!("Couldn't read repository: {}", e),
-            }
-        })?;
-        tracing::info!(
-            "Processing source repository: {}",
-            source_repository.source_url
-        );
+        let repository_urls = source_repository.repository_urls()?;
+        let readers = repository_urls
+            .iter()
+            .map(|repository_url| {
+                reader_from_str(repository_url).map_err(|e| AptPrepError::RepositoryAccess {
+                    repository: repository_url.clone(),
+                    reason: format!("Couldn't read repository: {}", e),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let keyring = source_repository
+            .signed_by
+            .as_deref()
+            .map(load_keyring)
+            .transpose()?;
+        tracing::info!("Processing source repository: {}", repository_urls[0]);
 
         for distribution in source_repository.distributions.iter() {
-            let (release, url) = match distribution {
-                DistributionDef::Simple(name) => (
-                    reader.release_reader(name).await.map_err(|e| {
+            let distribution_urls = repository_urls
+                .iter()
+                .map(|repository_url| {
+                    distribution.base_url(repository_url).map_err(|e| {
                         AptPrepError::RepositoryAccess {
-                            repository: source_repository.source_url.clone(),
-                            reason: format!("Couldn't fetch release: {}", e),
+                            repository: repository_url.clone(),
+                            reason: format!("Invalid distribution URL: {}", e),
                         }
-                    })?,
-                    reader.url().expect("Release has no URL"),
-                ),
-                DistributionDef::Advanced { distribution_path } => (
-                    reader
-                        .release_reader_with_distribution_path(distribution_path)
-                        .await
-                        .map_err(|e| AptPrepError::RepositoryAccess {
-                            repository: source_repository.source_url.clone(),
-                            reason: format!("Couldn't fetch release: {}", e),
-                        })?,
-                    reader
-                        .url()
-                        .expect("Release has no URL")
-                        .join(distribution_path)
-                        .expect("Invalid URL"),
-                ),
-            };
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
 
-            let package_indices = release
-                .packages_indices_entries_preferred_compression()
-                .map_err(|e| AptPrepError::RepositoryAccess {
-                    repository: source_repository.source_url.clone(),
-                    reason: format!("Couldn't read package indices list: {}", e),
-                })?;
-
-            for package_entry in package_indices.iter() {
-                if package_entry.architecture != "all"
-                    && !source_repository
-                        .architectures
-                        .iter()
-                        .any(|architecture| architecture.as_str() == package_entry.architecture)
+            // Fall back through the mirrors in order until one of them serves the distribution
+            let mut last_error = None;
+            let mut fetched = None;
+            for (mirror_index, reader) in readers.iter().enumerate() {
+                match read_distribution(
+                    reader.as_ref(),
+                    distribution,
+                    source_repository,
+                    keyring.as_deref(),
+                    &distribution_urls[mirror_index],
+                )
+                .await
                 {
-                    continue;
+                    Ok(contents) => {
+                        fetched = Some((mirror_index, contents));
+                        break;
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to read distribution from {}: {}",
+                            distribution_urls[mirror_index],
+                            e
+                        );
+                        last_error = Some(e);
+                    }
                 }
+            }
+            let Some((mirror_index, contents)) = fetched else {
+                return Err(last_error.expect("a repository has at least one URL"));
+            };
+
+            if mirror_index > 0 {
+                tracing::info!(
+                    "Read distribution from mirror {}",
+                    distribution_urls[mirror_index]
+                );
+            }
+            // Packages are recorded under the configured source URL whichever mirror served the
+            // index, so that a failover does not change the lockfile
+            let mut mirror_urls = distribution_urls;
+            let url = mirror_urls.remove(0);
+
+            for (component, binary_packages) in contents.packages_by_component {
+                let source_info = Arc::new(SourceInfo {
+                    repository_url: source_repository.source_url.clone(),
+                    url: url.clone(),
+                    mirror_urls: mirror_urls.clone(),
+                    suite: contents.suite.clone(),
+                    codename: contents.codename.clone(),
+                    component,
+                    release_date: contents.release_date,
+                    release_sha256: contents.release_sha256.clone(),
+                    signing_fingerprint: contents.signing_fingerprint.clone(),
+                });
 
-                let packages_list = release.resolve_packages_from_entry(package_entry).await?;
-                for binary_package in packages_list.iter() {
+                for binary_package in binary_packages {
                     let Ok(package_name) = binary_package.package() else {
                         tracing::warn!("Skipping package, no package name specified");
                         continue;
@@ -81,16 +119,162 @@ pub async fn collect_binary_packages(
                         continue;
                     };
 
-                    binary_packages_by_arch
-                        .entry(architecture.to_string())
+                    collected
+                        .binary_packages_by_arch
+                        .entry(
+                            source_repository
+                                .debian_architecture(architecture)
+                                .to_string(),
+                        )
                         .or_default()
                         .push(BinaryPackage {
-                            source_info: Arc::new(SourceInfo { url: url.clone() }),
-                            control_file: Arc::new(binary_package.clone()),
+                            source_info: source_info.clone(),
+                            control_file: Arc::new(binary_package),
                         });
                 }
             }
         }
     }
-    Ok(binary_packages_by_arch)
+    Ok(collected)
+}
+
+/// Release metadata and package lists of a distribution as read from one mirror.
+pub(super) struct DistributionContents {
+    pub suite: Option<String>,
+    pub codename: Option<String>,
+    pub release_date: Option<DateTime<Utc>>,
+    pub release_sha256: Option<String>,
+    pub signing_fingerprint: Option<String>,
+    /// Packages by archive component. Flat repositories have a single `None` component.
+    pub packages_by_component: BTreeMap<Option<String>, Vec<BinaryPackageControlFile<'static>>>,
+}
+
+/// Reads the Release file and the relevant package indices of a distribution from one mirror.
+async fn read_distribution(
+    reader: &dyn RepositoryRootReader,
+    distribution: &DistributionDef,
+    source_repository: &SourceRepository,
+    keyring: Option<&[SignedPublicKey]>,
+    url: &Url,
+) -> Result<DistributionContents, AptPrepError> {
+    let (release_directory, release) = match distribution {
+        DistributionDef::Simple(name) => {
+            (format!("dists/{}", name), reader.release_reader(name).await)
+        }
+        DistributionDef::Advanced { distribution_path } => (
+            distribution_path.clone(),
+            reader
+                .release_reader_with_distribution_path(distribution_path)
+                .await,
+        ),
+        DistributionDef::Flat { flat_path } => {
+            return read_flat_distribution(reader, flat_path, source_repository, keyring, url)
+                .await;
+        }
+    };
+    let release = release.map_err(|e| AptPrepError::RepositoryAccess {
+        repository: url.to_string(),
+        reason: format!("Couldn't fetch release: {}", e),
+    })?;
+
+    let fetched_release =
+        fetch_matching_release(reader, &release_directory, keyring, release.release_file()).await?;
+
+    let release_date =
+        check_release_freshness(release.release_file(), source_repository, url.as_str())?;
+    let suite = release
+        .release_file()
+        .field_str("Suite")
+        .or_else(|| release.release_file().field_str("Codename"))
+        .map(str::to_string)
+        .or_else(|| match distribution {
+            DistributionDef::Simple(name) => Some(name.clone()),
+            _ => None,
+        });
+
+    let codename = release
+        .release_file()
+        .field_str("Codename")
+        .map(str::to_string);
+
+    let package_indices = release
+        .packages_indices_entries_preferred_compression()
+        .map_err(|e| AptPrepError::RepositoryAccess {
+            repository: url.to_string(),
+            reason: format!("Couldn't read package indices list: {}", e),
+        })?;
+
+    let mut packages_by_component: BTreeMap<Option<String>, Vec<_>> = BTreeMap::new();
+    for package_entry in package_indices.iter() {
+        if !source_repository.serves_architecture(&package_entry.architecture) {
+            continue;
+        }
+
+        let packages_list = release.resolve_packages_from_entry(package_entry).await?;
+        packages_by_component
+            .entry(Some(package_entry.component.to_string()))
+            .or_default()
+            .extend(packages_list.iter().cloned());
+    }
+
+    Ok(DistributionContents {
+        suite,
+        codename,
+        release_date,
+        release_sha256: fetched_release.as_ref().map(FetchedRelease::sha256),
+        signing_fingerprint: fetched_release.and_then(|release| release.signing_fingerprint),
+        packages_by_component,
+    })
+}
+
+/// Fetches the raw Release file of a distribution, requiring a valid signature when a keyring
+/// is given.
+///
+/// The raw file is fetched separately from the one used for reading the distribution, so their
+/// checksums are compared to make sure the recorded digest and signature cover the file in use.
+async fn fetch_matching_release(
+    reader: &dyn RepositoryRootReader,
+    release_directory: &str,
+    keyring: Option<&[SignedPublicKey]>,
+    release_file: &ReleaseFile<'_>,
+) -> Result<Option<FetchedRelease>, AptPrepError> {
+    let release_error = |reason: String| match keyring {
+        Some(_) => AptPrepError::Signature {
+            location: release_directory.to_string(),
+            reason,
+        },
+        None => AptPrepError::RepositoryAccess {
+            repository: release_directory.to_string(),
+            reason,
+        },
+    };
+    let Some(fetched) = fetch_release(reader, release_directory, keyring).await? else {
+        if keyring.is_some() {
+            return Err(release_error("Release file not found".to_string()));
+        }
+        return Ok(None);
+    };
+    let fetched_release_file = if fetched.clearsigned {
+        ReleaseFile::from_armored_reader(Cursor::new(&fetched.content))
+    } else {
+        ReleaseFile::from_reader(Cursor::new(&fetched.content))
+    }
+    .map_err(|e| release_error(format!("Couldn't parse Release file: {}", e)))?;
+
+    for field in ["Date", "Valid-Until", "SHA512", "SHA256", "SHA1", "MD5Sum"] {
+        if fetched_release_file.field_str(field) != release_file.field_str(field) {
+            return Err(release_error(format!(
+                "{} of the fetched Release file differs from the one in use",
+                field
+            )));
+        }
+    }
+    if let Some(fingerprint) = &fetched.signing_fingerprint {
+        tracing::debug!(
+            "Release file in {} is signed by {}",
+            release_directory,
+            fingerprint
+        );
+    }
+    Ok(Some(fetched))
 }
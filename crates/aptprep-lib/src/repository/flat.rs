@@ -0,0 +1,191 @@
+use super::collector::DistributionContents;
+use super::freshness::check_release_freshness;
+use super::signature::{FetchedRelease, fetch_optional, fetch_release};
+use crate::config::SourceRepository;
+use crate::error::AptPrepError;
+use crate::verification::content_digest_hasher::ContentDigestVerifier;
+use debian_packaging::binary_package_control::BinaryPackageControlFile;
+use debian_packaging::checksum::{AnyChecksumType, AnyContentDigest};
+use debian_packaging::control::ControlParagraphReader;
+use debian_packaging::repository::RepositoryRootReader;
+use debian_packaging::repository::release::ReleaseFile;
+use futures::AsyncReadExt;
+use pgp::SignedPublicKey;
+use reqwest::Url;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+
+/// Package index names in order of preference.
+const PACKAGES_INDEX_NAMES: [&str; 3] = ["Packages.xz", "Packages.gz", "Packages"];
+
+/// Reads a flat repository (`deb <uri> <directory>/`), which keeps `Release` and `Packages`
+/// directly in `directory` instead of a `dists/` tree.
+///
+/// The package indices are verified against the Release file when one is published.
+pub async fn read_flat_distribution(
+    reader: &dyn RepositoryRootReader,
+    flat_path: &str,
+    source_repository: &SourceRepository,
+    keyring: Option<&[SignedPublicKey]>,
+    url: &Url,
+) -> Result<DistributionContents, AptPrepError> {
+    let repository_error = |reason: String| AptPrepError::RepositoryAccess {
+        repository: url.to_string(),
+        reason,
+    };
+
+    let directory = flat_path.trim_matches('/');
+    let directory = match directory.strip_prefix("./").unwrap_or(directory) {
+        "." => "",
+        directory => directory,
+    };
+    let index_path = |name: &str| {
+        if directory.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", directory, name)
+        }
+    };
+
+    let fetched_release = fetch_release(reader, directory, keyring).await?;
+    let (release_date, suite, codename, packages_content) = match &fetched_release {
+        Some(release) => {
+            let release_file = if release.clearsigned {
+                ReleaseFile::from_armored_reader(Cursor::new(&release.content))
+            } else {
+                ReleaseFile::from_reader(Cursor::new(&release.content))
+            }
+            .map_err(|e| repository_error(format!("Couldn't parse Release file: {}", e)))?;
+            let release_date =
+                check_release_freshness(&release_file, source_repository, url.as_str())?;
+            let suite = release_file.field_str("Suite").map(str::to_string);
+            let codename = release_file.field_str("Codename").map(str::to_string);
+
+            let (name, digest, size) = find_packages_index(&release_file).ok_or_else(|| {
+                repository_error("Release file does not list a Packages index".to_string())
+            })?;
+            let content = fetch_index(reader, &index_path(name))
+                .await
+                .map_err(|e| repository_error(format!("Couldn't fetch {}: {}", name, e)))?;
+
+            if content.len() as u64 != size {
+                return Err(repository_error(format!(
+                    "{} has size {}, but the Release file lists {}",
+                    name,
+                    content.len(),
+                    size
+                )));
+            }
+            let mut verifier = ContentDigestVerifier::new(digest);
+            verifier.update(&content);
+            verifier
+                .verify()
+                .map_err(|e| repository_error(format!("{}: {}", name, e)))?;
+
+            (
+                release_date,
+                suite,
+                codename,
+                decompress_index(name, content)?,
+            )
+        }
+        None => {
+            if keyring.is_some() {
+                return Err(AptPrepError::Signature {
+                    location: url.to_string(),
+                    reason: "Flat repository has no Release file to verify".to_string(),
+                });
+            }
+            tracing::warn!(
+                "Flat repository {} has no Release file, its package index cannot be verified",
+                url
+            );
+
+            // Only a missing index moves on to the next compression, other failures are errors
+            let mut fetched = None;
+            for name in PACKAGES_INDEX_NAMES {
+                if let Some(content) = fetch_optional(reader, &index_path(name)).await? {
+                    fetched = Some((name, content));
+                    break;
+                }
+            }
+            let (name, content) = fetched
+                .ok_or_else(|| repository_error("Couldn't find a Packages index".to_string()))?;
+            (None, None, None, decompress_index(name, content)?)
+        }
+    };
+
+    let mut binary_packages = Vec::new();
+    for paragraph in ControlParagraphReader::new(Cursor::new(packages_content)) {
+        let binary_package = BinaryPackageControlFile::from(paragraph?);
+        let Ok(architecture) = binary_package.architecture() else {
+            continue;
+        };
+        // Flat indices may spell the architecture either way
+        if source_repository.serves_architecture(architecture)
+            || source_repository
+                .architectures
+                .iter()
+                .any(|configured| configured == architecture)
+        {
+            binary_packages.push(binary_package);
+        }
+    }
+
+    Ok(DistributionContents {
+        suite,
+        codename,
+        release_date,
+        release_sha256: fetched_release.as_ref().map(FetchedRelease::sha256),
+        signing_fingerprint: fetched_release.and_then(|release| release.signing_fingerprint),
+        packages_by_component: BTreeMap::from([(None, binary_packages)]),
+    })
+}
+
+/// Finds the most preferred Packages index listed in the Release file, with its digest and size.
+fn find_packages_index(
+    release_file: &ReleaseFile<'_>,
+) -> Option<(&'static str, AnyContentDigest, u64)> {
+    AnyChecksumType::preferred_order().find_map(|checksum| {
+        let entries = release_file.field_str(checksum.field_name())?;
+        let entries = entries
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                Some((parts.next()?, parts.next()?, parts.next()?))
+            })
+            .collect::<Vec<_>>();
+
+        let (name, hex_digest, size) = PACKAGES_INDEX_NAMES.into_iter().find_map(|name| {
+            let (hex_digest, size, _) = entries.iter().find(|(_, _, path)| *path == name)?;
+            Some((name, *hex_digest, *size))
+        })?;
+        Some((
+            name,
+            AnyContentDigest::from_hex_digest(checksum, hex_digest).ok()?,
+            size.parse().ok()?,
+        ))
+    })
+}
+
+async fn fetch_index(
+    reader: &dyn RepositoryRootReader,
+    path: &str,
+) -> Result<Vec<u8>, AptPrepError> {
+    let mut stream = reader.get_path(path).await?;
+    let mut content = Vec::new();
+    stream.read_to_end(&mut content).await?;
+    Ok(content)
+}
+
+fn decompress_index(name: &str, content: Vec<u8>) -> Result<Vec<u8>, AptPrepError> {
+    let mut decoded = Vec::new();
+    if name.ends_with(".xz") {
+        xz2::read::XzDecoder::new(content.as_slice()).read_to_end(&mut decoded)?;
+    } else if name.ends_with(".gz") {
+        flate2::read::GzDecoder::new(content.as_slice()).read_to_end(&mut decoded)?;
+    } else {
+        return Ok(content);
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,128 @@
+use crate::config::{ReleaseChecks, SourceRepository};
+use crate::error::AptPrepError;
+use chrono::{DateTime, Utc};
+use debian_packaging::repository::release::ReleaseFile;
+
+/// Checks the `Date` and `Valid-Until` fields of a Release file and returns its `Date`.
+///
+/// Snapshot repositories are checked as of their snapshot timestamp rather than the current time.
+pub fn check_release_freshness(
+    release_file: &ReleaseFile<'_>,
+    repository: &SourceRepository,
+    distribution_url: &str,
+) -> Result<Option<DateTime<Utc>>, AptPrepError> {
+    let freshness_error = |details: String| AptPrepError::ReleaseFreshness {
+        distribution: distribution_url.to_string(),
+        details,
+    };
+
+    let release_date = release_file
+        .date()
+        .transpose()
+        .map_err(|e| freshness_error(format!("Invalid Date field: {}", e)))?;
+    let valid_until = release_file
+        .valid_until()
+        .transpose()
+        .map_err(|e| freshness_error(format!("Invalid Valid-Until field: {}", e)))?;
+
+    let reference_time = repository
+        .snapshot
+        .as_ref()
+        .and_then(|snapshot| snapshot.timestamp)
+        .map(|timestamp| *timestamp.as_datetime())
+        .unwrap_or_else(Utc::now);
+
+    check_release_dates(
+        release_date,
+        valid_until,
+        &repository.release_checks,
+        reference_time,
+    )
+    .map_err(freshness_error)?;
+
+    Ok(release_date)
+}
+
+fn check_release_dates(
+    release_date: Option<DateTime<Utc>>,
+    valid_until: Option<DateTime<Utc>>,
+    checks: &ReleaseChecks,
+    reference_time: DateTime<Utc>,
+) -> Result<(), String> {
+    if checks.check_valid_until
+        && let Some(valid_until) = valid_until
+        && valid_until < reference_time
+    {
+        return Err(format!(
+            "Release expired at {} (checked as of {})",
+            valid_until.to_rfc3339(),
+            reference_time.to_rfc3339()
+        ));
+    }
+
+    if let Some(max_age) = checks.max_age {
+        let Some(release_date) = release_date else {
+            return Err(format!(
+                "Release has no Date field, but max_age is set to {}",
+                max_age
+            ));
+        };
+        if reference_time - release_date > max_age.as_time_delta() {
+            return Err(format!(
+                "Release dated {} is older than the allowed {} (checked as of {})",
+                release_date.to_rfc3339(),
+                max_age,
+                reference_time.to_rfc3339()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn now() -> DateTime<Utc> {
+        "2025-10-29T14:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_valid_until_in_the_future_passes() {
+        let checks = ReleaseChecks::default();
+        let valid_until = Some(now() + TimeDelta::days(1));
+        assert!(check_release_dates(Some(now()), valid_until, &checks, now()).is_ok());
+    }
+
+    #[test]
+    fn test_expired_release_is_rejected() {
+        let checks = ReleaseChecks::default();
+        let valid_until = Some(now() - TimeDelta::days(1));
+        assert!(check_release_dates(Some(now()), valid_until, &checks, now()).is_err());
+    }
+
+    #[test]
+    fn test_expired_release_passes_when_valid_until_is_ignored() {
+        let checks = ReleaseChecks {
+            check_valid_until: false,
+            max_age: None,
+        };
+        let valid_until = Some(now() - TimeDelta::days(1));
+        assert!(check_release_dates(Some(now()), valid_until, &checks, now()).is_ok());
+    }
+
+    #[test]
+    fn test_max_age_is_enforced() {
+        let checks = ReleaseChecks {
+            check_valid_until: true,
+            max_age: Some("7d".parse().unwrap()),
+        };
+        let fresh = Some(now() - TimeDelta::days(6));
+        let stale = Some(now() - TimeDelta::days(8));
+        assert!(check_release_dates(fresh, None, &checks, now()).is_ok());
+        assert!(check_release_dates(stale, None, &checks, now()).is_err());
+        assert!(check_release_dates(None, None, &checks, now()).is_err());
+    }
+}
@@ -1,5 +1,10 @@
 mod collector;
+mod flat;
+mod freshness;
+mod signature;
 pub mod types;
 
 pub use collector::collect_binary_packages;
-pub use types::{BinaryPackage, SourceInfo};
+pub use freshness::check_release_freshness;
+pub use signature::{load_keyring, verify_detached_signature};
+pub use types::{BinaryPackage, CollectedPackages, SourceInfo};
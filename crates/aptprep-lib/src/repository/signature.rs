@@ -0,0 +1,208 @@
+use crate::error::AptPrepError;
+use debian_packaging::error::DebianError;
+use debian_packaging::repository::RepositoryRootReader;
+use futures::AsyncReadExt;
+use pgp::cleartext::CleartextSignedMessage;
+use pgp::types::PublicKeyTrait;
+use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
+use sha2::{Digest, Sha256};
+use std::io::{self, Cursor};
+use std::path::Path;
+
+/// Raw Release file of a distribution together with the key that signed it, if checked.
+pub struct FetchedRelease {
+    /// Contents of `InRelease`, or of `Release` when no `InRelease` exists
+    pub content: Vec<u8>,
+    /// Whether `content` is a clearsigned `InRelease` file
+    pub clearsigned: bool,
+    /// Fingerprint of the primary key that made a valid signature
+    pub signing_fingerprint: Option<String>,
+}
+
+impl FetchedRelease {
+    /// Hex-encoded SHA256 of the fetched file.
+    pub fn sha256(&self) -> String {
+        format!("{:x}", Sha256::digest(&self.content))
+    }
+}
+
+/// Loads OpenPGP public keys from an armored or binary keyring file.
+pub fn load_keyring(path: &Path) -> Result<Vec<SignedPublicKey>, AptPrepError> {
+    let keyring_error = |reason: String| AptPrepError::Signature {
+        location: path.display().to_string(),
+        reason,
+    };
+    let content = std::fs::read(path).map_err(|e| keyring_error(e.to_string()))?;
+
+    let keys = if content.starts_with(b"-----BEGIN PGP") {
+        let (keys, _headers) = SignedPublicKey::from_armor_many(Cursor::new(&content))
+            .map_err(|e| keyring_error(e.to_string()))?;
+        keys.collect::<Result<Vec<_>, _>>()
+    } else {
+        SignedPublicKey::from_bytes_many(Cursor::new(&content)).collect::<Result<Vec<_>, _>>()
+    }
+    .map_err(|e| keyring_error(e.to_string()))?;
+
+    if keys.is_empty() {
+        return Err(keyring_error("Keyring contains no public keys".to_string()));
+    }
+    Ok(keys)
+}
+
+/// Fetches `InRelease` or `Release` (with `Release.gpg`) from `directory` and, when a keyring
+/// is given, requires a valid signature from one of its keys.
+///
+/// Returns `None` if the directory has no Release file at all.
+pub async fn fetch_release(
+    reader: &dyn RepositoryRootReader,
+    directory: &str,
+    keyring: Option<&[SignedPublicKey]>,
+) -> Result<Option<FetchedRelease>, AptPrepError> {
+    let path = |name: &str| {
+        if directory.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", directory.trim_end_matches('/'), name)
+        }
+    };
+    let signature_error = |name: &str, reason: String| AptPrepError::Signature {
+        location: path(name),
+        reason,
+    };
+
+    if let Some(content) = fetch_optional(reader, &path("InRelease")).await? {
+        let signing_fingerprint = match keyring {
+            Some(keys) => {
+                let text = String::from_utf8(content.clone())
+                    .map_err(|e| signature_error("InRelease", e.to_string()))?;
+                let (message, _headers) = CleartextSignedMessage::from_string(&text)
+                    .map_err(|e| signature_error("InRelease", e.to_string()))?;
+                let fingerprint = keys
+                    .iter()
+                    .find(|key| {
+                        message.verify(*key).is_ok()
+                            || key
+                                .public_subkeys
+                                .iter()
+                                .any(|subkey| message.verify(subkey).is_ok())
+                    })
+                    .map(|key| hex::encode_upper(key.fingerprint().as_bytes()))
+                    .ok_or_else(|| {
+                        signature_error(
+                            "InRelease",
+                            "No valid signature from a trusted key".to_string(),
+                        )
+                    })?;
+                Some(fingerprint)
+            }
+            None => None,
+        };
+        return Ok(Some(FetchedRelease {
+            content,
+            clearsigned: true,
+            signing_fingerprint,
+        }));
+    }
+
+    let Some(content) = fetch_optional(reader, &path("Release")).await? else {
+        return Ok(None);
+    };
+    let signing_fingerprint = match keyring {
+        Some(keys) => {
+            let signature = fetch_optional(reader, &path("Release.gpg"))
+                .await?
+                .ok_or_else(|| {
+                    signature_error("Release.gpg", "Detached signature not found".to_string())
+                })?;
+            let fingerprint = verify_detached_signature(keys, &signature, &content)
+                .map_err(|reason| signature_error("Release.gpg", reason))?;
+            Some(fingerprint)
+        }
+        None => None,
+    };
+    Ok(Some(FetchedRelease {
+        content,
+        clearsigned: false,
+        signing_fingerprint,
+    }))
+}
+
+/// Checks an armored or binary detached signature over `content`, returning the fingerprint
+/// of the primary key whose key or subkey made it.
+pub fn verify_detached_signature(
+    keys: &[SignedPublicKey],
+    signature: &[u8],
+    content: &[u8],
+) -> Result<String, String> {
+    let signature = if signature.starts_with(b"-----BEGIN PGP") {
+        StandaloneSignature::from_armor_single(Cursor::new(signature))
+            .map(|(signature, _)| signature)
+    } else {
+        StandaloneSignature::from_bytes(Cursor::new(signature))
+    }
+    .map_err(|e| e.to_string())?;
+
+    keys.iter()
+        .find(|key| {
+            signature.verify(*key, content).is_ok()
+                || key
+                    .public_subkeys
+                    .iter()
+                    .any(|subkey| signature.verify(subkey, content).is_ok())
+        })
+        .map(|key| hex::encode_upper(key.fingerprint().as_bytes()))
+        .ok_or_else(|| "No valid signature from a trusted key".to_string())
+}
+
+/// Fetches `path`, or returns `None` if the repository does not have it. Any other failure,
+/// such as a timeout or a server error, is an error, so that an unreachable Release file is
+/// never mistaken for a missing one.
+pub(super) async fn fetch_optional(
+    reader: &dyn RepositoryRootReader,
+    path: &str,
+) -> Result<Option<Vec<u8>>, AptPrepError> {
+    let fetch_error = |reason: String| AptPrepError::RepositoryAccess {
+        repository: path.to_string(),
+        reason,
+    };
+    let mut stream = match reader.get_path(path).await {
+        Ok(stream) => stream,
+        Err(e) if is_not_found(&e) => {
+            tracing::debug!("{} does not exist", path);
+            return Ok(None);
+        }
+        Err(e) => return Err(fetch_error(format!("Couldn't fetch: {}", e))),
+    };
+    let mut content = Vec::new();
+    stream
+        .read_to_end(&mut content)
+        .await
+        .map_err(|e| fetch_error(format!("Couldn't read: {}", e)))?;
+    Ok(Some(content))
+}
+
+/// Whether a repository reader failed because the file does not exist, e.g. with HTTP 404.
+fn is_not_found(error: &DebianError) -> bool {
+    match error {
+        DebianError::RepositoryIoPath(_, e) | DebianError::Io(e) => {
+            e.kind() == io::ErrorKind::NotFound
+        }
+        DebianError::Reqwest(e) => e.status() == Some(reqwest::StatusCode::NOT_FOUND),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_missing_files_are_optional() {
+        let error = |kind: io::ErrorKind| {
+            DebianError::RepositoryIoPath("InRelease".to_string(), kind.into())
+        };
+        assert!(is_not_found(&error(io::ErrorKind::NotFound)));
+        assert!(!is_not_found(&error(io::ErrorKind::TimedOut)));
+        assert!(!is_not_found(&error(io::ErrorKind::ConnectionReset)));
+    }
+}
@@ -1,10 +1,29 @@
+use chrono::{DateTime, Utc};
 use debian_packaging::binary_package_control::BinaryPackageControlFile;
 use reqwest::Url;
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Provenance of packages: one component of a distribution, as served by one mirror.
 #[derive(Debug)]
 pub struct SourceInfo {
+    /// `source_url` of the configured repository
+    pub repository_url: String,
     pub url: Url,
+    /// The same distribution on the remaining mirrors, in order of preference
+    pub mirror_urls: Vec<Url>,
+    /// `Suite` of the Release file, or the configured distribution name
+    pub suite: Option<String>,
+    /// `Codename` of the Release file, e.g. `bookworm` or `bookworm-security`
+    pub codename: Option<String>,
+    /// Archive component, absent for flat repositories
+    pub component: Option<String>,
+    /// `Date` of the Release file the package was listed in
+    pub release_date: Option<DateTime<Utc>>,
+    /// SHA256 of the Release (or InRelease) file as fetched
+    pub release_sha256: Option<String>,
+    /// Fingerprint of the key that signed the Release file, when `signed_by` is set
+    pub signing_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -19,8 +38,14 @@ impl BinaryPackage {
     }
 }
 
+/// Packages read from all configured repositories.
+#[derive(Debug, Default)]
+pub struct CollectedPackages {
+    pub binary_packages_by_arch: HashMap<String, Vec<BinaryPackage>>,
+}
+
 pub fn iterate_all_relevant_packages<'a>(
-    binary_packages: &'a std::collections::HashMap<String, Vec<BinaryPackage>>,
+    binary_packages: &'a HashMap<String, Vec<BinaryPackage>>,
     architecture: &'a String,
 ) -> impl Iterator<Item = &'a BinaryPackage> + 'a {
     binary_packages
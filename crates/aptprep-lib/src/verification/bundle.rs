@@ -0,0 +1,150 @@
+use crate::config::OutputLayout;
+use crate::error::AptPrepError;
+use crate::lockfile::Lockfile;
+use crate::output::{PruneOptions, prune_output_dir, render_packages_index};
+use crate::verification::content_digest_hasher::ContentDigestVerifier;
+use debian_packaging::checksum::AnyContentDigest;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Name of the generated index in the bundle directory.
+const PACKAGES_INDEX: &str = "Packages";
+
+/// Outcome of checking a bundle directory against a lockfile.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BundleReport {
+    /// Files present with the expected size and digest
+    pub verified: usize,
+    /// Expected files that are not in the directory
+    pub missing: Vec<PathBuf>,
+    /// Files with the wrong size or digest, or an index that does not match the lockfile
+    pub corrupt: Vec<CorruptFile>,
+    /// Files in the directory that the lockfile does not reference
+    pub extra: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CorruptFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl BundleReport {
+    /// Turns the report into the error whose exit code tells the problems apart.
+    pub fn into_result(self) -> Result<Self, AptPrepError> {
+        if self.missing.is_empty() && self.corrupt.is_empty() && self.extra.is_empty() {
+            Ok(self)
+        } else {
+            Err(AptPrepError::BundleVerification {
+                missing: self.missing.len(),
+                corrupt: self.corrupt.len(),
+                extra: self.extra.len(),
+            })
+        }
+    }
+}
+
+enum FileStatus {
+    Verified,
+    Missing,
+    Corrupt(String),
+}
+
+/// Checks that `dir` holds exactly the packages of `lockfile`, arranged by `layout`, and the
+/// Packages index generated for them. Only the local file system is read. Files in `allowed`
+/// are not reported as extra even though they are not packages.
+pub async fn verify_bundle(
+    lockfile: &Lockfile,
+    dir: &Path,
+    layout: OutputLayout,
+    allowed: &BTreeSet<PathBuf>,
+    parallelism: usize,
+) -> Result<BundleReport, AptPrepError> {
+    let mut expected = BTreeMap::new();
+    for (package_key, path) in lockfile.package_paths(layout)? {
+        let entry = &lockfile.packages[&package_key];
+        expected.insert(path, (entry.size, entry.digest.content_digest()?));
+    }
+
+    let mut report = BundleReport::default();
+    let mut results = stream::iter(expected)
+        .map(|(relative_path, (size, digest))| {
+            let path = dir.join(&relative_path);
+            async move {
+                let status =
+                    tokio::task::spawn_blocking(move || check_package(&path, size, digest))
+                        .await
+                        .map_err(|e| AptPrepError::Unexpected(e.into()))??;
+                Ok::<_, AptPrepError>((PathBuf::from(relative_path), status))
+            }
+        })
+        .buffer_unordered(parallelism);
+    while let Some(result) = results.next().await {
+        let (path, status) = result?;
+        match status {
+            FileStatus::Verified => report.verified += 1,
+            FileStatus::Missing => report.missing.push(path),
+            FileStatus::Corrupt(reason) => report.corrupt.push(CorruptFile { path, reason }),
+        }
+    }
+
+    match std::fs::read(dir.join(PACKAGES_INDEX)) {
+        Ok(index) if index == render_packages_index(lockfile, layout)? => report.verified += 1,
+        Ok(_) => report.corrupt.push(CorruptFile {
+            path: PathBuf::from(PACKAGES_INDEX),
+            reason: "Does not match the lockfile".to_string(),
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            report.missing.push(PathBuf::from(PACKAGES_INDEX));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // Listing what pruning would remove finds the extra files without touching them
+    report.extra = prune_output_dir(
+        dir,
+        allowed,
+        &PruneOptions {
+            dry_run: true,
+            quarantine_dir: None,
+        },
+    )?
+    .pruned_files;
+
+    report.missing.sort();
+    report.corrupt.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+fn check_package(path: &Path, size: u64, digest: AnyContentDigest) -> io::Result<FileStatus> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FileStatus::Missing),
+        Err(e) => return Err(e),
+    };
+    let length = file.metadata()?.len();
+    if length != size {
+        return Ok(FileStatus::Corrupt(format!(
+            "Size is {} bytes instead of {}",
+            length, size
+        )));
+    }
+
+    let mut verifier = ContentDigestVerifier::new(digest);
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        verifier.update(&buffer[..bytes_read]);
+    }
+    Ok(match verifier.verify() {
+        Ok(()) => FileStatus::Verified,
+        Err(e) => FileStatus::Corrupt(e.to_string()),
+    })
+}
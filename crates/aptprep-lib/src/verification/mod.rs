@@ -1 +1,4 @@
+mod bundle;
 pub mod content_digest_hasher;
+
+pub use bundle::{BundleReport, CorruptFile, verify_bundle};